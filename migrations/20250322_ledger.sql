@@ -0,0 +1,97 @@
+-- Create ledger_accounts table
+-- Every wallet gets exactly one account; system accounts (reserve, fee income,
+-- suspense) exist once per currency and have no wallet.
+CREATE TABLE ledger_accounts (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    account_type VARCHAR(20) NOT NULL,
+    wallet_id UUID UNIQUE REFERENCES wallets(id),
+    currency VARCHAR(3) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT wallet_account_has_wallet CHECK (
+        (account_type = 'wallet') = (wallet_id IS NOT NULL)
+    )
+);
+
+CREATE UNIQUE INDEX idx_ledger_accounts_system
+    ON ledger_accounts(account_type, currency)
+    WHERE wallet_id IS NULL;
+
+-- Create journal_entries table
+CREATE TABLE journal_entries (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    transaction_id UUID REFERENCES transactions(id),
+    description VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create postings table
+-- A positive amount increases the account, a negative amount decreases it.
+CREATE TABLE postings (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    journal_entry_id UUID NOT NULL REFERENCES journal_entries(id),
+    ledger_account_id UUID NOT NULL REFERENCES ledger_accounts(id),
+    amount DECIMAL(20,2) NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT non_zero_posting CHECK (amount <> 0)
+);
+
+CREATE INDEX idx_journal_entries_transaction_id ON journal_entries(transaction_id);
+CREATE INDEX idx_postings_journal_entry_id ON postings(journal_entry_id);
+CREATE INDEX idx_postings_ledger_account_id ON postings(ledger_account_id);
+
+-- Every journal entry must balance to zero per currency. The check is deferred
+-- to commit time so that all legs of an entry can be inserted first.
+CREATE FUNCTION check_journal_entry_balanced() RETURNS TRIGGER AS $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM postings
+        WHERE journal_entry_id = NEW.journal_entry_id
+        GROUP BY currency
+        HAVING SUM(amount) <> 0
+    ) THEN
+        RAISE EXCEPTION 'journal entry % is not balanced', NEW.journal_entry_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE CONSTRAINT TRIGGER postings_balanced
+    AFTER INSERT ON postings
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW EXECUTE FUNCTION check_journal_entry_balanced();
+
+-- Postings are append-only; corrections are made with new journal entries.
+CREATE FUNCTION reject_posting_changes() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'postings are append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER postings_append_only
+    BEFORE UPDATE OR DELETE ON postings
+    FOR EACH ROW EXECUTE FUNCTION reject_posting_changes();
+
+-- Backfill opening balances for existing wallets against the suspense account
+INSERT INTO ledger_accounts (account_type, wallet_id, currency)
+SELECT 'wallet', id, currency FROM wallets;
+
+INSERT INTO ledger_accounts (account_type, currency)
+SELECT DISTINCT 'suspense', currency FROM wallets WHERE balance <> 0;
+
+WITH opening AS (
+    INSERT INTO journal_entries (description)
+    SELECT 'opening_balance:' || w.id FROM wallets w WHERE w.balance <> 0
+    RETURNING id, description
+)
+INSERT INTO postings (journal_entry_id, ledger_account_id, amount, currency)
+SELECT o.id, a.id, leg.amount, w.currency
+FROM opening o
+JOIN wallets w ON o.description = 'opening_balance:' || w.id
+CROSS JOIN LATERAL (
+    VALUES
+        ((SELECT id FROM ledger_accounts WHERE wallet_id = w.id), w.balance),
+        ((SELECT id FROM ledger_accounts
+          WHERE account_type = 'suspense' AND currency = w.currency AND wallet_id IS NULL), -w.balance)
+) AS leg(account_id, amount)
+JOIN ledger_accounts a ON a.id = leg.account_id;
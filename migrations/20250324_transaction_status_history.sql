@@ -0,0 +1,17 @@
+-- Create transaction_status_history table
+-- One row per lifecycle transition, including the initial Pending state.
+CREATE TABLE transaction_status_history (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    transaction_id UUID NOT NULL REFERENCES transactions(id),
+    from_status VARCHAR(20),
+    to_status VARCHAR(20) NOT NULL,
+    reason TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_transaction_status_history_transaction_id
+    ON transaction_status_history(transaction_id);
+
+-- Seed history for transactions created before the lifecycle was tracked
+INSERT INTO transaction_status_history (transaction_id, from_status, to_status, reason, created_at)
+SELECT id, NULL, status, 'backfill', created_at FROM transactions;
@@ -0,0 +1,26 @@
+-- Track funds reserved by holds separately from the total balance
+ALTER TABLE wallets
+    ADD COLUMN held_balance DECIMAL(20,2) NOT NULL DEFAULT 0.00,
+    ADD COLUMN available_balance DECIMAL(20,2) GENERATED ALWAYS AS (balance - held_balance) STORED,
+    ADD CONSTRAINT non_negative_held_balance CHECK (held_balance >= 0),
+    ADD CONSTRAINT held_within_balance CHECK (held_balance <= balance);
+
+-- Create wallet_holds table
+CREATE TABLE wallet_holds (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    wallet_id UUID NOT NULL REFERENCES wallets(id),
+    transaction_id UUID REFERENCES transactions(id),
+    amount DECIMAL(20,2) NOT NULL,
+    captured_amount DECIMAL(20,2) NOT NULL DEFAULT 0.00,
+    status VARCHAR(20) NOT NULL DEFAULT 'active',
+    reference_id VARCHAR(255),
+    expires_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT positive_hold CHECK (amount > 0),
+    CONSTRAINT capture_within_hold CHECK (captured_amount >= 0 AND captured_amount <= amount)
+);
+
+CREATE INDEX idx_wallet_holds_wallet_id ON wallet_holds(wallet_id);
+CREATE INDEX idx_wallet_holds_transaction_id ON wallet_holds(transaction_id);
+CREATE INDEX idx_wallet_holds_expiry ON wallet_holds(expires_at) WHERE status = 'active';
@@ -0,0 +1,31 @@
+-- Create fee_schedules table
+-- A rule applies to a transaction type and currency, optionally only to one
+-- KYC level, for amounts in [min_amount, max_amount). The fee is
+-- flat_fee + amount * percentage / 100, clamped to [min_fee, max_fee].
+CREATE TABLE fee_schedules (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    transaction_type VARCHAR(50) NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    kyc_level INTEGER,
+    min_amount DECIMAL(20,2) NOT NULL DEFAULT 0.00,
+    max_amount DECIMAL(20,2),
+    flat_fee DECIMAL(20,2) NOT NULL DEFAULT 0.00,
+    percentage DECIMAL(7,4) NOT NULL DEFAULT 0.0000,
+    min_fee DECIMAL(20,2),
+    max_fee DECIMAL(20,2),
+    active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT valid_amount_band CHECK (max_amount IS NULL OR max_amount > min_amount),
+    CONSTRAINT valid_fee_caps CHECK (min_fee IS NULL OR max_fee IS NULL OR max_fee >= min_fee),
+    CONSTRAINT non_negative_fee CHECK (flat_fee >= 0 AND percentage >= 0)
+);
+
+CREATE INDEX idx_fee_schedules_lookup
+    ON fee_schedules(transaction_type, currency)
+    WHERE active;
+
+-- Record the fee charged on each transaction
+ALTER TABLE transactions
+    ADD COLUMN fee_amount DECIMAL(20,2) NOT NULL DEFAULT 0.00,
+    ADD COLUMN fee_breakdown JSONB;
@@ -0,0 +1,37 @@
+-- Create fx_rates table
+-- Mid-market rates; the rate in force is the latest one already effective.
+CREATE TABLE fx_rates (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    base_currency VARCHAR(3) NOT NULL,
+    quote_currency VARCHAR(3) NOT NULL,
+    rate DECIMAL(20,10) NOT NULL,
+    effective_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT positive_rate CHECK (rate > 0),
+    CONSTRAINT distinct_pair CHECK (base_currency <> quote_currency)
+);
+
+CREATE INDEX idx_fx_rates_pair ON fx_rates(base_currency, quote_currency, effective_at DESC);
+
+-- Create fx_quotes table
+-- A quote locks a client rate (mid rate less spread) until it expires and can
+-- be used for at most one conversion.
+CREATE TABLE fx_quotes (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id),
+    from_currency VARCHAR(3) NOT NULL,
+    to_currency VARCHAR(3) NOT NULL,
+    mid_rate DECIMAL(20,10) NOT NULL,
+    rate DECIMAL(20,10) NOT NULL,
+    from_amount DECIMAL(20,2) NOT NULL,
+    to_amount DECIMAL(20,2) NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    transaction_id UUID REFERENCES transactions(id),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT positive_quote CHECK (from_amount > 0 AND to_amount > 0)
+);
+
+CREATE INDEX idx_fx_quotes_user_id ON fx_quotes(user_id);
+
+-- Link conversions to the quote they were priced with
+ALTER TABLE transactions ADD COLUMN fx_quote_id UUID REFERENCES fx_quotes(id);
@@ -0,0 +1,36 @@
+-- Money columns were fixed at two decimals; three-decimal currencies
+-- (BHD, KWD, OMR) need one more. Precision per currency is enforced by the
+-- application's ISO 4217 registry.
+
+-- The generated column has to be dropped before the columns it reads change type
+ALTER TABLE wallets DROP COLUMN available_balance;
+
+ALTER TABLE wallets
+    ALTER COLUMN balance TYPE DECIMAL(21,3),
+    ALTER COLUMN held_balance TYPE DECIMAL(21,3);
+
+ALTER TABLE wallets
+    ADD COLUMN available_balance DECIMAL(21,3) GENERATED ALWAYS AS (balance - held_balance) STORED;
+
+ALTER TABLE transactions
+    ALTER COLUMN amount TYPE DECIMAL(21,3),
+    ALTER COLUMN fee_amount TYPE DECIMAL(21,3);
+
+ALTER TABLE reserve_accounts ALTER COLUMN balance TYPE DECIMAL(21,3);
+ALTER TABLE reserve_transactions ALTER COLUMN amount TYPE DECIMAL(21,3);
+ALTER TABLE postings ALTER COLUMN amount TYPE DECIMAL(21,3);
+
+ALTER TABLE wallet_holds
+    ALTER COLUMN amount TYPE DECIMAL(21,3),
+    ALTER COLUMN captured_amount TYPE DECIMAL(21,3);
+
+ALTER TABLE fee_schedules
+    ALTER COLUMN min_amount TYPE DECIMAL(21,3),
+    ALTER COLUMN max_amount TYPE DECIMAL(21,3),
+    ALTER COLUMN flat_fee TYPE DECIMAL(21,3),
+    ALTER COLUMN min_fee TYPE DECIMAL(21,3),
+    ALTER COLUMN max_fee TYPE DECIMAL(21,3);
+
+ALTER TABLE fx_quotes
+    ALTER COLUMN from_amount TYPE DECIMAL(21,3),
+    ALTER COLUMN to_amount TYPE DECIMAL(21,3);
@@ -0,0 +1,50 @@
+-- Create scheduled_transfers table
+-- Standing orders. `scheduled_for` is the nominal time of the pending
+-- occurrence; `next_run_at` is when it is next attempted, which moves later
+-- while a failed occurrence is being retried.
+CREATE TABLE scheduled_transfers (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id),
+    debit_wallet_id UUID NOT NULL REFERENCES wallets(id),
+    credit_wallet_id UUID NOT NULL REFERENCES wallets(id),
+    amount DECIMAL(21,3) NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    frequency VARCHAR(20) NOT NULL,
+    interval_count INTEGER NOT NULL DEFAULT 1,
+    cron_expression VARCHAR(255),
+    description VARCHAR(255),
+    starts_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    ends_at TIMESTAMP WITH TIME ZONE,
+    scheduled_for TIMESTAMP WITH TIME ZONE,
+    next_run_at TIMESTAMP WITH TIME ZONE,
+    occurrences INTEGER NOT NULL DEFAULT 0,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    last_transaction_id UUID REFERENCES transactions(id),
+    status VARCHAR(20) NOT NULL DEFAULT 'active',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT positive_scheduled_amount CHECK (amount > 0),
+    CONSTRAINT distinct_scheduled_wallets CHECK (debit_wallet_id <> credit_wallet_id),
+    CONSTRAINT positive_interval CHECK (interval_count > 0),
+    CONSTRAINT cron_has_expression CHECK ((frequency = 'cron') = (cron_expression IS NOT NULL)),
+    CONSTRAINT valid_schedule_window CHECK (ends_at IS NULL OR ends_at > starts_at)
+);
+
+CREATE INDEX idx_scheduled_transfers_user_id ON scheduled_transfers(user_id);
+CREATE INDEX idx_scheduled_transfers_due ON scheduled_transfers(next_run_at) WHERE status = 'active';
+
+-- Create scheduled_transfer_runs table
+-- One row per execution attempt, successful or not.
+CREATE TABLE scheduled_transfer_runs (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    scheduled_transfer_id UUID NOT NULL REFERENCES scheduled_transfers(id),
+    occurrence INTEGER NOT NULL,
+    attempt INTEGER NOT NULL,
+    transaction_id UUID REFERENCES transactions(id),
+    error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_scheduled_transfer_runs_schedule_id
+    ON scheduled_transfer_runs(scheduled_transfer_id);
@@ -0,0 +1,16 @@
+-- Link refunds to the transaction they reverse
+ALTER TABLE transactions
+    ADD COLUMN parent_transaction_id UUID REFERENCES transactions(id),
+    ADD CONSTRAINT not_own_parent CHECK (parent_transaction_id <> id);
+
+CREATE INDEX idx_transactions_parent_transaction_id
+    ON transactions(parent_transaction_id)
+    WHERE parent_transaction_id IS NOT NULL;
+
+-- Reversals used to be linked only through a 'reversal_<id>' reference
+UPDATE transactions r
+SET parent_transaction_id = p.id
+FROM transactions p
+WHERE r.transaction_type = 'refund'
+  AND r.reference_id ~ '^reversal_[0-9a-f-]{36}$'
+  AND p.id = substring(r.reference_id FROM 10)::uuid;
@@ -0,0 +1,47 @@
+-- Phone numbers are matched in E.164 form from now on. Normalize the ones
+-- already stored in international form where that does not collide with
+-- another user; national numbers are left for their owners to update.
+WITH normalized AS (
+    SELECT id, '+' || regexp_replace(regexp_replace(phone_number, '[\s().-]', '', 'g'), '^(\+|00)', '') AS phone
+    FROM users
+    WHERE regexp_replace(phone_number, '[\s().-]', '', 'g') ~ '^(\+|00)[1-9][0-9]{7,14}$'
+)
+UPDATE users u
+SET phone_number = n.phone
+FROM normalized n
+WHERE u.id = n.id
+  AND u.phone_number <> n.phone
+  AND NOT EXISTS (SELECT 1 FROM users o WHERE o.phone_number = n.phone);
+
+-- Create pending_transfers table
+-- P2P transfers to someone without an account. The money sits in the
+-- escrow transaction's contra account until the recipient registers and
+-- claims it, or it expires and is refunded to the sender.
+CREATE TABLE pending_transfers (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    sender_id UUID NOT NULL REFERENCES users(id),
+    debit_wallet_id UUID NOT NULL REFERENCES wallets(id),
+    transaction_id UUID NOT NULL UNIQUE REFERENCES transactions(id),
+    recipient_type VARCHAR(20) NOT NULL,
+    recipient VARCHAR(255) NOT NULL,
+    amount DECIMAL(21,3) NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    note VARCHAR(255),
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    claimed_by UUID REFERENCES users(id),
+    claim_transaction_id UUID REFERENCES transactions(id),
+    refund_transaction_id UUID REFERENCES transactions(id),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT positive_pending_amount CHECK (amount > 0),
+    CONSTRAINT valid_recipient_type CHECK (recipient_type IN ('phone', 'email')),
+    CONSTRAINT claimed_has_transaction CHECK ((status = 'claimed') = (claim_transaction_id IS NOT NULL)),
+    CONSTRAINT refunded_has_transaction CHECK ((status = 'refunded') = (refund_transaction_id IS NOT NULL))
+);
+
+CREATE INDEX idx_pending_transfers_sender_id ON pending_transfers(sender_id);
+CREATE INDEX idx_pending_transfers_recipient ON pending_transfers(recipient_type, recipient)
+    WHERE status = 'pending';
+CREATE INDEX idx_pending_transfers_expires_at ON pending_transfers(expires_at)
+    WHERE status = 'pending';
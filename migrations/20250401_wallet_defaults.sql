@@ -0,0 +1,19 @@
+-- Wallet nicknames and one default wallet per user and currency. Incoming
+-- payments that name a user rather than a wallet are routed to the default.
+ALTER TABLE wallets
+    ADD COLUMN nickname VARCHAR(50),
+    ADD COLUMN is_default BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD CONSTRAINT closed_wallet_not_default CHECK (NOT (is_default AND status = 'closed'));
+
+-- The oldest open wallet in each currency becomes the default
+UPDATE wallets w
+SET is_default = TRUE
+FROM (
+    SELECT DISTINCT ON (user_id, currency) id
+    FROM wallets
+    WHERE status <> 'closed'
+    ORDER BY user_id, currency, created_at, id
+) d
+WHERE w.id = d.id;
+
+CREATE UNIQUE INDEX idx_wallets_default ON wallets(user_id, currency) WHERE is_default;
@@ -0,0 +1,6 @@
+-- Why a wallet was last frozen, unfrozen or closed. The full history of
+-- status changes is kept in audit_logs.
+ALTER TABLE wallets
+    ADD COLUMN status_reason VARCHAR(30),
+    ADD COLUMN status_note TEXT,
+    ADD COLUMN status_changed_at TIMESTAMP WITH TIME ZONE;
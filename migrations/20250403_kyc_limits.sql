@@ -0,0 +1,48 @@
+-- Create kyc_limits table
+-- Caps on what a user at a KYC level may move in a currency. A row for a
+-- transaction type caps that type; a row without one caps all outgoing
+-- transactions together and also carries the maximum balance. Daily and
+-- monthly windows are calendar days and months in UTC. NULL means no cap.
+CREATE TABLE kyc_limits (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    kyc_level INTEGER NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    transaction_type VARCHAR(50),
+    per_transaction_max DECIMAL(21,3),
+    daily_amount_max DECIMAL(21,3),
+    monthly_amount_max DECIMAL(21,3),
+    daily_count_max INTEGER,
+    monthly_count_max INTEGER,
+    max_balance DECIMAL(21,3),
+    active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT non_negative_limits CHECK (
+        COALESCE(per_transaction_max, 0) >= 0 AND COALESCE(daily_amount_max, 0) >= 0
+        AND COALESCE(monthly_amount_max, 0) >= 0 AND COALESCE(daily_count_max, 0) >= 0
+        AND COALESCE(monthly_count_max, 0) >= 0 AND COALESCE(max_balance, 0) >= 0
+    ),
+    CONSTRAINT balance_cap_is_overall CHECK (max_balance IS NULL OR transaction_type IS NULL)
+);
+
+CREATE UNIQUE INDEX idx_kyc_limits_lookup
+    ON kyc_limits(kyc_level, currency, COALESCE(transaction_type, ''))
+    WHERE active;
+
+-- Usage is summed per user over the debited wallets
+CREATE INDEX idx_transactions_debit_wallet_created_at
+    ON transactions(debit_wallet_id, created_at)
+    WHERE debit_wallet_id IS NOT NULL;
+
+-- Carry over the thresholds that used to be hardcoded in the API: transfers
+-- over 1000 and withdrawals over 500 needed KYC level 2
+INSERT INTO kyc_limits (kyc_level, currency, transaction_type, per_transaction_max) VALUES
+    (1, 'USD', 'transfer', 1000.000),
+    (1, 'USD', 'withdrawal', 500.000);
+
+INSERT INTO kyc_limits (
+    kyc_level, currency, transaction_type, per_transaction_max, daily_amount_max,
+    monthly_amount_max, daily_count_max, monthly_count_max, max_balance
+) VALUES
+    (1, 'USD', NULL, NULL, 2000.000, 10000.000, 20, 200, 5000.000),
+    (2, 'USD', NULL, 25000.000, 50000.000, 250000.000, 100, 1000, 500000.000);
@@ -0,0 +1,25 @@
+-- Listings page by (created_at, id), newest first. These indexes let a
+-- page start at its cursor instead of scanning and skipping rows.
+
+CREATE INDEX idx_transactions_created_at_id ON transactions(created_at DESC, id DESC);
+
+-- A user's transactions are found through either side's wallet
+CREATE INDEX idx_transactions_debit_wallet_page
+    ON transactions(debit_wallet_id, created_at DESC, id DESC)
+    WHERE debit_wallet_id IS NOT NULL;
+CREATE INDEX idx_transactions_credit_wallet_page
+    ON transactions(credit_wallet_id, created_at DESC, id DESC)
+    WHERE credit_wallet_id IS NOT NULL;
+
+CREATE INDEX idx_wallets_user_page ON wallets(user_id, created_at DESC, id DESC);
+
+CREATE INDEX idx_users_created_at_id ON users(created_at DESC, id DESC);
+
+CREATE INDEX idx_audit_logs_created_at_id ON audit_logs(created_at DESC, id DESC);
+CREATE INDEX idx_audit_logs_admin_page ON audit_logs(admin_id, created_at DESC, id DESC);
+CREATE INDEX idx_audit_logs_entity_page
+    ON audit_logs(entity_type, entity_id, created_at DESC, id DESC);
+
+-- Superseded by the indexes above
+DROP INDEX IF EXISTS idx_transactions_created_at;
+DROP INDEX IF EXISTS idx_audit_logs_entity;
@@ -0,0 +1,17 @@
+-- Admin transaction search
+
+-- Reference prefix matches (LIKE 'abc%')
+CREATE INDEX idx_transactions_reference_prefix
+    ON transactions(reference_id text_pattern_ops)
+    WHERE reference_id IS NOT NULL;
+
+-- Metadata equality conditions are expressed as containment (@>)
+CREATE INDEX idx_transactions_metadata ON transactions USING GIN (metadata jsonb_path_ops);
+
+-- Free-text search over the reference and metadata. The expression must stay
+-- identical to the one in AdminService's search filter.
+CREATE INDEX idx_transactions_search ON transactions USING GIN (
+    to_tsvector('simple', coalesce(reference_id, '') || ' ' || coalesce(metadata::text, ''))
+);
+
+CREATE INDEX idx_transactions_currency_amount ON transactions(currency, amount);
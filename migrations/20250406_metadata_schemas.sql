@@ -0,0 +1,15 @@
+-- Versioned JSON Schemas for transaction metadata, one active version per
+-- transaction type. Version 1 of each is seeded at startup from the schema
+-- files bundled with the service.
+CREATE TABLE metadata_schemas (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    transaction_type VARCHAR(50) NOT NULL,
+    version INTEGER NOT NULL CHECK (version > 0),
+    schema JSONB NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT false,
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX idx_metadata_schemas_version ON metadata_schemas(transaction_type, version);
+CREATE UNIQUE INDEX idx_metadata_schemas_active ON metadata_schemas(transaction_type) WHERE active;
@@ -0,0 +1,10 @@
+-- Request and W3C trace IDs of the request that produced each audit entry,
+-- so admin actions can be matched to API logs. Transactions carry the same
+-- IDs in their metadata.
+ALTER TABLE audit_logs
+    ADD COLUMN request_id VARCHAR(128),
+    ADD COLUMN trace_id CHAR(32);
+
+CREATE INDEX idx_audit_logs_request_id ON audit_logs(request_id) WHERE request_id IS NOT NULL;
+CREATE INDEX idx_transactions_request_id ON transactions((metadata->>'request_id'))
+    WHERE metadata ? 'request_id';
@@ -0,0 +1,21 @@
+-- TOTP two-factor authentication. Secrets are encrypted by the service
+-- before storage; backup codes are stored as SHA-256 hashes.
+ALTER TABLE users ADD COLUMN two_factor_enabled BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE two_factor_secrets (
+    user_id UUID PRIMARY KEY REFERENCES users(id),
+    secret_ciphertext BYTEA NOT NULL,
+    confirmed_at TIMESTAMP WITH TIME ZONE,
+    last_used_step BIGINT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE two_factor_backup_codes (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id),
+    code_hash CHAR(64) NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX idx_two_factor_backup_codes_hash ON two_factor_backup_codes(user_id, code_hash);
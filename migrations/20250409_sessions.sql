@@ -0,0 +1,22 @@
+-- Refresh token sessions. Each refresh inserts the next generation of a
+-- family and marks the previous one rotated; presenting a rotated token
+-- again revokes the whole family.
+CREATE TABLE sessions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    family_id UUID NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id),
+    refresh_token_hash CHAR(64) NOT NULL,
+    device_name VARCHAR(100),
+    ip_address TEXT NOT NULL,
+    user_agent TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    rotated_at TIMESTAMP WITH TIME ZONE,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    revoked_reason VARCHAR(50)
+);
+
+CREATE UNIQUE INDEX idx_sessions_refresh_token_hash ON sessions(refresh_token_hash);
+CREATE INDEX idx_sessions_family_id ON sessions(family_id);
+CREATE INDEX idx_sessions_user_active ON sessions(user_id, created_at DESC)
+    WHERE rotated_at IS NULL AND revoked_at IS NULL;
@@ -0,0 +1,8 @@
+-- In-progress idempotency keys hold a short lease instead of blocking
+-- retries until the key expires. Each claim gets its own ID so a request
+-- whose lease lapsed cannot record a response over its successor's.
+ALTER TABLE idempotency_keys ADD COLUMN claim_id UUID NOT NULL DEFAULT uuid_generate_v4();
+ALTER TABLE idempotency_keys ADD COLUMN locked_until TIMESTAMP WITH TIME ZONE;
+
+-- Keys left in progress by earlier deploys can be retried straight away
+UPDATE idempotency_keys SET locked_until = CURRENT_TIMESTAMP WHERE response_body IS NULL;
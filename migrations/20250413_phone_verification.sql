@@ -0,0 +1,8 @@
+-- Phone verification. P2P payments and escrow claims are only routed by
+-- phone to a user whose number has been verified; transfers to an
+-- unverified number stay in escrow until it is. Existing numbers start
+-- unverified.
+ALTER TABLE users ADD COLUMN phone_verified_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_users_verified_phone ON users(phone_number)
+    WHERE phone_verified_at IS NOT NULL;
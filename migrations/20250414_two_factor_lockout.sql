@@ -0,0 +1,5 @@
+-- Failed two-factor attempts. After too many wrong TOTP or backup codes in
+-- a row the user is locked out of 2FA checks until locked_until.
+ALTER TABLE two_factor_secrets
+    ADD COLUMN failed_attempts INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN locked_until TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,15 @@
+-- Declared reserve balances, one series per currency. The table was used by
+-- the admin service before it had a migration, so it may already exist; rows
+-- from before reserves had a currency were in the USD default.
+CREATE TABLE IF NOT EXISTS reserve_balances (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    amount DECIMAL(21,3) NOT NULL,
+    proof_url TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE reserve_balances ADD COLUMN IF NOT EXISTS currency VARCHAR(3);
+UPDATE reserve_balances SET currency = 'USD' WHERE currency IS NULL;
+ALTER TABLE reserve_balances ALTER COLUMN currency SET NOT NULL;
+
+CREATE INDEX idx_reserve_balances_currency ON reserve_balances(currency, created_at DESC);
@@ -0,0 +1,28 @@
+-- Limits were only seeded for USD, and a currency without limits is
+-- refused outright. Give every supported currency the USD limits at roughly
+-- their USD value, rounded to whole units. Operations can tune them later.
+INSERT INTO kyc_limits (
+    kyc_level, currency, transaction_type, per_transaction_max, daily_amount_max,
+    monthly_amount_max, daily_count_max, monthly_count_max, max_balance
+)
+SELECT
+    l.kyc_level,
+    c.currency,
+    l.transaction_type,
+    ROUND(l.per_transaction_max * c.per_usd),
+    ROUND(l.daily_amount_max * c.per_usd),
+    ROUND(l.monthly_amount_max * c.per_usd),
+    l.daily_count_max,
+    l.monthly_count_max,
+    ROUND(l.max_balance * c.per_usd)
+FROM kyc_limits l
+CROSS JOIN (VALUES
+    ('AED', 3.7), ('BHD', 0.38), ('BIF', 2900), ('CAD', 1.4), ('CDF', 2800),
+    ('CHF', 0.9), ('CNY', 7.2), ('ETB', 130), ('EUR', 0.9), ('GBP', 0.8),
+    ('GHS', 12), ('INR', 85), ('JPY', 145), ('KES', 130), ('KWD', 0.31),
+    ('MWK', 1700), ('MZN', 64), ('NGN', 1550), ('OMR', 0.38), ('RWF', 1400),
+    ('SAR', 3.75), ('TZS', 2600), ('UGX', 3600), ('XAF', 590), ('XOF', 590),
+    ('ZAR', 18), ('ZMW', 25)
+) AS c(currency, per_usd)
+WHERE l.currency = 'USD' AND l.active
+ON CONFLICT DO NOTHING;
@@ -2,27 +2,196 @@ use crate::{
     api::{
         error::ApiError,
         middleware::auth::{require_kyc_level, AuthUser},
-        response::ApiResponse,
+        response::{ApiResponse, PageQuery, PaginatedResponse},
+    },
+    models::{
+        currency::Money,
+        metadata_schema::{MetadataSchema, MetadataSchemaError},
+        session::Session,
+        transaction::{Transaction, TransactionStatus, TransactionType},
+        user::{User, UserKycLevel},
+        wallet::{WalletStatus, WalletStatusReason},
+    },
+    services::{
+        admin::{
+            AdminError, AdminService, ReversalResult, SystemStats, TransactionFacets,
+            TransactionFilter, UserFilter, WalletStatusChange,
+        },
+        audit::{AuditActor, AuditService},
+        email::EmailService,
+        metadata_schema::MetadataSchemaRegistry,
+        token_denylist::TokenDenylist,
     },
-    models::user::UserKycLevel,
-    services::admin::{AdminService, SystemStats, UserFilter, TransactionFilter},
 };
 use axum::{
     extract::{Path, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
+use chrono::Utc;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use std::sync::Arc;
+use tracing::error;
 use uuid::Uuid;
+use validator::Validate;
 
 pub fn admin_routes() -> Router {
     Router::new()
         // User Management
         .route("/admin/users", get(get_users))
         .route("/admin/users/:id/kyc", post(update_user_kyc))
+        .route("/admin/users/:id/verify-phone", post(verify_user_phone))
+        .route("/admin/users/:id/logout-everywhere", post(logout_everywhere))
         // Transaction Management
         .route("/admin/transactions", get(get_transactions))
+        .route("/admin/transactions/search", post(search_transactions))
+        .route("/admin/transactions/export", post(export_transactions))
+        .route("/admin/transactions/:id/reverse", post(reverse_transaction))
+        .route("/admin/transactions/:id/settle", post(settle_transaction))
+        // Wallet Management
+        .route("/admin/wallets/:id/freeze", post(freeze_wallet))
+        .route("/admin/wallets/:id/unfreeze", post(unfreeze_wallet))
+        .route("/admin/wallets/:id/close", post(close_wallet))
+        // Metadata Schemas
+        .route(
+            "/admin/metadata-schemas",
+            get(list_metadata_schemas).post(create_metadata_schema),
+        )
+        .route("/admin/metadata-schemas/:id/activate", post(activate_metadata_schema))
+        // Reserve Management
+        .route("/admin/reserve", get(get_reserve_balance))
+        .route("/admin/reserve", post(update_reserve_balance))
+        // System Statistics
+        .route("/admin/stats", get(get_system_stats))
+}
+
+// User Management
+async fn get_users(
+    State(admin): State<Arc<AdminService>>,
+    auth_user: AuthUser,
+    Query(filter): Query<UserFilter>,
+    Query(page_query): Query<PageQuery>,
+) -> Result<ApiResponse<PaginatedResponse<User>>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    let page = page_query.to_request()?;
+
+    let users = admin.get_users(&filter, &page).await
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+
+    Ok(ApiResponse::success(PaginatedResponse::new(users, &page)))
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateKycRequest {
+    kyc_level: UserKycLevel,
+}
+
+async fn update_user_kyc(
+    State(admin): State<Arc<AdminService>>,
+    auth_user: AuthUser,
+    Path(user_id): Path<Uuid>,
+    Json(req): Json<UpdateKycRequest>,
+) -> Result<ApiResponse<crate::models::user::User>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    let user = admin.update_user_kyc(user_id, req.kyc_level).await
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+
+    Ok(ApiResponse::success(user))
+}
+
+async fn verify_user_phone(
+    State(admin): State<Arc<AdminService>>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<ApiResponse<User>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    let actor = AuditActor::from_request(auth_user.id, &headers);
+    let user = admin
+        .verify_user_phone(&actor, user_id)
+        .await
+        .map_err(|e| match e {
+            AdminError::UserNotFound => ApiError::NotFoundError(e.to_string()),
+            AdminError::InvalidInput(_) => ApiError::validation(e.to_string()),
+            _ => ApiError::InternalError(e.into()),
+        })?;
+
+    Ok(ApiResponse::success(user))
+}
+
+#[derive(Debug, Serialize)]
+struct LogoutEverywhereResponse {
+    revoked_sessions: i64,
+}
+
+/// Revokes all of a user's sessions and every access token issued so far,
+/// e.g. after an account takeover
+async fn logout_everywhere(
+    State(pool): State<PgPool>,
+    State(denylist): State<Arc<TokenDenylist>>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<ApiResponse<LogoutEverywhereResponse>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    User::find_by_id(&pool, user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFoundError("User not found".to_string()))?;
+
+    // The audit entry commits or rolls back with the revocation
+    let actor = AuditActor::from_request(auth_user.id, &headers);
+    let mut db_tx = pool.begin().await?;
+    let revoked_sessions = Session::revoke_all(&mut *db_tx, user_id, "logout_everywhere")
+        .await
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+    actor
+        .log_in(
+            &mut db_tx,
+            "logout_everywhere",
+            "user",
+            Some(user_id),
+            None,
+            Some(serde_json::json!({ "revoked_sessions": revoked_sessions })),
+        )
+        .await?;
+    db_tx.commit().await?;
+
+    denylist
+        .deny_user(user_id, Utc::now().timestamp_micros())
+        .await
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+
+    Ok(ApiResponse::success(LogoutEverywhereResponse { revoked_sessions }))
+}
+
+// Transaction Management
+        .route("/admin/transactions", get(get_transactions))
+        .route("/admin/transactions/search", post(search_transactions))
+        .route("/admin/transactions/export", post(export_transactions))
+        .route("/admin/transactions/:id/reverse", post(reverse_transaction))
+        .route("/admin/transactions/:id/settle", post(settle_transaction))
+        // Wallet Management
+        .route("/admin/wallets/:id/freeze", post(freeze_wallet))
+        .route("/admin/wallets/:id/unfreeze", post(unfreeze_wallet))
+        .route("/admin/wallets/:id/close", post(close_wallet))
+        // Metadata Schemas
+        .route(
+            "/admin/metadata-schemas",
+            get(list_metadata_schemas).post(create_metadata_schema),
+        )
+        .route("/admin/metadata-schemas/:id/activate", post(activate_metadata_schema))
         // Reserve Management
         .route("/admin/reserve", get(get_reserve_balance))
         .route("/admin/reserve", post(update_reserve_balance))
@@ -35,14 +204,17 @@ async fn get_users(
     State(admin): State<Arc<AdminService>>,
     auth_user: AuthUser,
     Query(filter): Query<UserFilter>,
-) -> Result<ApiResponse<Vec<crate::models::user::User>>, ApiError> {
+    Query(page_query): Query<PageQuery>,
+) -> Result<ApiResponse<PaginatedResponse<User>>, ApiError> {
     // Require admin access
     require_kyc_level(3, &auth_user)?;
 
-    let users = admin.get_users(&filter).await
+    let page = page_query.to_request()?;
+
+    let users = admin.get_users(&filter, &page).await
         .map_err(|e| ApiError::InternalError(e.into()))?;
 
-    Ok(ApiResponse::success(users))
+    Ok(ApiResponse::success(PaginatedResponse::new(users, &page)))
 }
 
 #[derive(Debug, Deserialize)]
@@ -65,38 +237,535 @@ async fn update_user_kyc(
     Ok(ApiResponse::success(user))
 }
 
+async fn verify_user_phone(
+    State(admin): State<Arc<AdminService>>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<ApiResponse<User>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    let actor = AuditActor::from_request(auth_user.id, &headers);
+    let user = admin
+        .verify_user_phone(&actor, user_id)
+        .await
+        .map_err(|e| match e {
+            AdminError::UserNotFound => ApiError::NotFoundError(e.to_string()),
+            AdminError::InvalidInput(_) => ApiError::validation(e.to_string()),
+            _ => ApiError::InternalError(e.into()),
+        })?;
+
+    Ok(ApiResponse::success(user))
+}
+
+#[derive(Debug, Serialize)]
+struct LogoutEverywhereResponse {
+    revoked_sessions: i64,
+}
+
+/// Revokes all of a user's sessions and every access token issued so far,
+/// e.g. after an account takeover
+async fn logout_everywhere(
+    State(pool): State<PgPool>,
+    State(audit): State<Arc<AuditService>>,
+    State(denylist): State<Arc<TokenDenylist>>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<ApiResponse<LogoutEverywhereResponse>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    User::find_by_id(&pool, user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFoundError("User not found".to_string()))?;
+
+    let revoked_sessions = Session::revoke_all(&pool, user_id, "logout_everywhere")
+        .await
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+    denylist
+        .deny_user(user_id, Utc::now().timestamp())
+        .await
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+
+    audit
+        .log_admin_action(
+            auth_user.id,
+            "logout_everywhere",
+            "user",
+            Some(user_id),
+            None,
+            Some(serde_json::json!({ "revoked_sessions": revoked_sessions })),
+            &headers,
+        )
+        .await
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+
+    Ok(ApiResponse::success(LogoutEverywhereResponse { revoked_sessions }))
+}
+
 // Transaction Management
 async fn get_transactions(
     State(admin): State<Arc<AdminService>>,
     auth_user: AuthUser,
     Query(filter): Query<TransactionFilter>,
-) -> Result<ApiResponse<Vec<crate::models::transaction::Transaction>>, ApiError> {
+    Query(page_query): Query<PageQuery>,
+) -> Result<ApiResponse<PaginatedResponse<Transaction>>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    let page = page_query.to_request()?;
+
+    let transactions = admin
+        .get_transactions(&filter, &page)
+        .await
+        .map_err(search_admin_error)?;
+
+    Ok(ApiResponse::success(PaginatedResponse::new(transactions, &page)))
+}
+
+/// Search with the full filter, including metadata conditions, which do not
+/// fit in a query string
+#[derive(Debug, Deserialize)]
+struct SearchTransactionsRequest {
+    #[serde(flatten)]
+    filter: TransactionFilter,
+    #[serde(flatten)]
+    page: PageQuery,
+}
+
+#[derive(Serialize)]
+struct SearchTransactionsResponse {
+    #[serde(flatten)]
+    results: PaginatedResponse<Transaction>,
+    facets: TransactionFacets,
+}
+
+async fn search_transactions(
+    State(admin): State<Arc<AdminService>>,
+    auth_user: AuthUser,
+    Json(req): Json<SearchTransactionsRequest>,
+) -> Result<ApiResponse<SearchTransactionsResponse>, ApiError> {
     // Require admin access
     require_kyc_level(3, &auth_user)?;
 
-    let transactions = admin.get_transactions(&filter).await
+    let page = req.page.to_request()?;
+
+    let transactions = admin
+        .get_transactions(&req.filter, &page)
+        .await
+        .map_err(search_admin_error)?;
+    let facets = admin
+        .get_transaction_facets(&req.filter)
+        .await
+        .map_err(search_admin_error)?;
+
+    Ok(ApiResponse::success(SearchTransactionsResponse {
+        results: PaginatedResponse::new(transactions, &page),
+        facets,
+    }))
+}
+
+async fn export_transactions(
+    State(admin): State<Arc<AdminService>>,
+    State(audit): State<Arc<AuditService>>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Json(filter): Json<TransactionFilter>,
+) -> Result<Response, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    let csv = admin
+        .export_transactions(&filter)
+        .await
+        .map_err(search_admin_error)?;
+
+    audit
+        .log_admin_action(
+            auth_user.id,
+            "export_transactions",
+            "transaction",
+            None,
+            None,
+            Some(serde_json::json!({ "filter": filter })),
+            &headers,
+        )
+        .await
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+
+    let filename = format!("transactions-{}.csv", Utc::now().format("%Y%m%d%H%M%S"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        csv,
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize, Validate)]
+struct ReverseTransactionRequest {
+    amount: Option<Decimal>,
+    #[validate(length(min = 1, max = 500))]
+    reason: String,
+}
+
+async fn reverse_transaction(
+    State(admin): State<Arc<AdminService>>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Path(transaction_id): Path<Uuid>,
+    Json(req): Json<ReverseTransactionRequest>,
+) -> Result<ApiResponse<ReversalResult>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    // Validate request
+    req.validate()?;
+
+    let actor = AuditActor::from_request(auth_user.id, &headers);
+    let result = admin
+        .reverse_transaction(&actor, transaction_id, req.amount, &req.reason)
+        .await
+        .map_err(|e| match e {
+            AdminError::TransactionNotFound => ApiError::NotFoundError(e.to_string()),
+            AdminError::TransactionError(e) => e.into(),
+            _ => ApiError::InternalError(e.into()),
+        })?;
+
+    Ok(ApiResponse::success(result))
+}
+
+/// The bank's progress on a deposit or withdrawal: `processing` once it is
+/// submitted, then `completed` or `failed`
+#[derive(Debug, Deserialize, Validate)]
+struct SettleTransactionRequest {
+    status: TransactionStatus,
+    #[validate(length(min = 1, max = 500))]
+    reason: Option<String>,
+}
+
+async fn settle_transaction(
+    State(admin): State<Arc<AdminService>>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Path(transaction_id): Path<Uuid>,
+    Json(req): Json<SettleTransactionRequest>,
+) -> Result<ApiResponse<Transaction>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    // Validate request
+    req.validate()?;
+
+    let actor = AuditActor::from_request(auth_user.id, &headers);
+    let transaction = admin
+        .settle_transaction(&actor, transaction_id, req.status, req.reason)
+        .await
+        .map_err(|e| match e {
+            AdminError::TransactionNotFound => ApiError::NotFoundError(e.to_string()),
+            AdminError::TransactionError(e) => e.into(),
+            _ => ApiError::InternalError(e.into()),
+        })?;
+
+    Ok(ApiResponse::success(transaction))
+}
+
+// Metadata Schemas
+#[derive(Debug, Deserialize)]
+struct CreateMetadataSchemaRequest {
+    transaction_type: TransactionType,
+    schema: serde_json::Value,
+    /// Enforce the new version immediately
+    #[serde(default)]
+    activate: bool,
+}
+
+async fn list_metadata_schemas(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+) -> Result<ApiResponse<Vec<MetadataSchema>>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    let schemas = MetadataSchema::find_all(&pool)
+        .await
+        .map_err(metadata_schema_error)?;
+
+    Ok(ApiResponse::success(schemas))
+}
+
+async fn create_metadata_schema(
+    State(metadata_schemas): State<Arc<MetadataSchemaRegistry>>,
+    State(audit): State<Arc<AuditService>>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Json(req): Json<CreateMetadataSchemaRequest>,
+) -> Result<ApiResponse<MetadataSchema>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    let schema = metadata_schemas
+        .create(req.transaction_type, req.schema, auth_user.id, req.activate)
+        .await
+        .map_err(metadata_schema_error)?;
+
+    audit
+        .log_admin_action(
+            auth_user.id,
+            "create_metadata_schema",
+            "metadata_schema",
+            Some(schema.id),
+            None,
+            Some(serde_json::json!({
+                "transaction_type": schema.transaction_type,
+                "version": schema.version,
+                "active": schema.active,
+            })),
+            &headers,
+        )
+        .await
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+
+    Ok(ApiResponse::success(schema))
+}
+
+async fn activate_metadata_schema(
+    State(metadata_schemas): State<Arc<MetadataSchemaRegistry>>,
+    State(audit): State<Arc<AuditService>>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Path(schema_id): Path<Uuid>,
+) -> Result<ApiResponse<MetadataSchema>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    let schema = metadata_schemas
+        .activate(schema_id)
+        .await
+        .map_err(metadata_schema_error)?;
+
+    audit
+        .log_admin_action(
+            auth_user.id,
+            "activate_metadata_schema",
+            "metadata_schema",
+            Some(schema.id),
+            None,
+            Some(serde_json::json!({
+                "transaction_type": schema.transaction_type,
+                "version": schema.version,
+            })),
+            &headers,
+        )
+        .await
         .map_err(|e| ApiError::InternalError(e.into()))?;
 
-    Ok(ApiResponse::success(transactions))
+    Ok(ApiResponse::success(schema))
+}
+
+fn metadata_schema_error(e: MetadataSchemaError) -> ApiError {
+    match e {
+        MetadataSchemaError::InvalidSchema(_) => ApiError::validation(e.to_string()),
+        MetadataSchemaError::NotFound => ApiError::NotFoundError(e.to_string()),
+        MetadataSchemaError::VersionConflict => ApiError::ConflictError(e.to_string()),
+        MetadataSchemaError::DatabaseError(_) => ApiError::InternalError(e.into()),
+    }
+}
+
+// Wallet Management
+#[derive(Debug, Deserialize, Validate)]
+struct WalletStatusRequest {
+    reason: WalletStatusReason,
+    #[validate(length(min = 1, max = 500))]
+    note: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+struct CloseWalletRequest {
+    reason: WalletStatusReason,
+    #[validate(length(min = 1, max = 500))]
+    note: Option<String>,
+    /// Where to move any remaining balance before closing
+    sweep_to_wallet_id: Option<Uuid>,
+}
+
+async fn freeze_wallet(
+    State(pool): State<PgPool>,
+    State(admin): State<Arc<AdminService>>,
+    State(email_service): State<Arc<EmailService>>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Path(wallet_id): Path<Uuid>,
+    Json(req): Json<WalletStatusRequest>,
+) -> Result<ApiResponse<WalletStatusChange>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    // Validate request
+    req.validate()?;
+
+    let actor = AuditActor::from_request(auth_user.id, &headers);
+    let change = admin
+        .freeze_wallet(&actor, wallet_id, req.reason, req.note)
+        .await
+        .map_err(wallet_admin_error)?;
+
+    notify_wallet_change(&pool, &email_service, &change).await;
+
+    Ok(ApiResponse::success(change))
+}
+
+async fn unfreeze_wallet(
+    State(pool): State<PgPool>,
+    State(admin): State<Arc<AdminService>>,
+    State(email_service): State<Arc<EmailService>>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Path(wallet_id): Path<Uuid>,
+    Json(req): Json<WalletStatusRequest>,
+) -> Result<ApiResponse<WalletStatusChange>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    // Validate request
+    req.validate()?;
+
+    let actor = AuditActor::from_request(auth_user.id, &headers);
+    let change = admin
+        .unfreeze_wallet(&actor, wallet_id, req.reason, req.note)
+        .await
+        .map_err(wallet_admin_error)?;
+
+    notify_wallet_change(&pool, &email_service, &change).await;
+
+    Ok(ApiResponse::success(change))
+}
+
+async fn close_wallet(
+    State(pool): State<PgPool>,
+    State(admin): State<Arc<AdminService>>,
+    State(email_service): State<Arc<EmailService>>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Path(wallet_id): Path<Uuid>,
+    Json(req): Json<CloseWalletRequest>,
+) -> Result<ApiResponse<WalletStatusChange>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    // Validate request
+    req.validate()?;
+
+    let actor = AuditActor::from_request(auth_user.id, &headers);
+    let change = admin
+        .close_wallet(&actor, wallet_id, req.reason, req.note, req.sweep_to_wallet_id)
+        .await
+        .map_err(wallet_admin_error)?;
+
+    notify_wallet_change(&pool, &email_service, &change).await;
+
+    Ok(ApiResponse::success(change))
+}
+
+fn search_admin_error(e: AdminError) -> ApiError {
+    match e {
+        AdminError::InvalidInput(_) => ApiError::validation(e.to_string()),
+        _ => ApiError::InternalError(e.into()),
+    }
+}
+
+fn wallet_admin_error(e: AdminError) -> ApiError {
+    match e {
+        AdminError::WalletNotFound => ApiError::NotFoundError(e.to_string()),
+        AdminError::WalletError(e) => e.into(),
+        AdminError::TransactionError(e) => e.into(),
+        _ => ApiError::InternalError(e.into()),
+    }
+}
+
+/// Tells the owner their wallet's status changed. The change has already
+/// been made, so a failed email is only logged. The admin's note is internal
+/// and is never sent.
+async fn notify_wallet_change(
+    pool: &PgPool,
+    email_service: &EmailService,
+    change: &WalletStatusChange,
+) {
+    let wallet = &change.wallet;
+
+    let owner = match User::find_by_id(pool, wallet.user_id).await {
+        Ok(Some(owner)) => owner,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Error loading owner of wallet {}: {}", wallet.id, e);
+            return;
+        }
+    };
+
+    let label = match &wallet.nickname {
+        Some(nickname) => format!("{} ({})", nickname, wallet.currency),
+        None => format!("{} wallet", wallet.currency),
+    };
+    let reason = wallet
+        .status_reason
+        .as_ref()
+        .map(|reason| reason.description())
+        .unwrap_or("Not given");
+    let (status_label, consequence) = match wallet.status {
+        WalletStatus::Frozen => (
+            "Frozen",
+            "While a wallet is frozen, no money can be sent from or received into it.",
+        ),
+        WalletStatus::Active => (
+            "Unfrozen",
+            "You can send and receive money with this wallet again.",
+        ),
+        WalletStatus::Closed => (
+            "Closed",
+            "A closed wallet can no longer send or receive money.",
+        ),
+    };
+
+    if let Err(e) = email_service
+        .send_wallet_status_changed(
+            &owner.email,
+            &owner.full_name,
+            &label,
+            status_label,
+            reason,
+            consequence,
+        )
+        .await
+    {
+        error!("Error notifying owner of wallet {}: {}", wallet.id, e);
+    }
 }
 
 // Reserve Management
 async fn get_reserve_balance(
     State(admin): State<Arc<AdminService>>,
     auth_user: AuthUser,
-) -> Result<ApiResponse<crate::models::reserve::ReserveBalance>, ApiError> {
+) -> Result<ApiResponse<Vec<crate::models::reserve::ReserveBalance>>, ApiError> {
     // Require admin access
     require_kyc_level(3, &auth_user)?;
 
-    let balance = admin.get_reserve_balance().await
+    let balances = admin.get_reserve_balances().await
         .map_err(|e| ApiError::InternalError(e.into()))?;
 
-    Ok(ApiResponse::success(balance))
+    Ok(ApiResponse::success(balances))
 }
 
 #[derive(Debug, Deserialize)]
 struct UpdateReserveRequest {
-    amount: i64,
+    amount: Decimal,
+    currency: String,
     proof_url: String,
 }
 
@@ -108,7 +777,10 @@ async fn update_reserve_balance(
     // Require admin access
     require_kyc_level(3, &auth_user)?;
 
-    let balance = admin.update_reserve_balance(req.amount, &req.proof_url).await
+    let amount = Money::new(req.amount, &req.currency)
+        .map_err(|e| ApiError::validation(e.to_string()))?;
+
+    let balance = admin.update_reserve_balance(&amount, &req.proof_url).await
         .map_err(|e| ApiError::InternalError(e.into()))?;
 
     Ok(ApiResponse::success(balance))
@@ -2,8 +2,9 @@ use crate::{
     api::{
         error::ApiError,
         middleware::auth::{require_kyc_level, AuthUser},
-        response::ApiResponse,
+        response::{ApiResponse, PageQuery, PaginatedResponse},
     },
+    models::audit::AuditLog,
     services::audit::AuditService,
 };
 use axum::{
@@ -15,6 +16,9 @@ use serde::Deserialize;
 use std::sync::Arc;
 use uuid::Uuid;
 
+/// Admins review the audit trail in longer pages than other listings
+const AUDIT_PAGE_SIZE: u32 = 50;
+
 pub fn audit_routes() -> Router {
     Router::new()
         .route("/admin/audit/logs", get(get_admin_logs))
@@ -22,27 +26,20 @@ pub fn audit_routes() -> Router {
         .route("/admin/audit/search", get(search_logs))
 }
 
-#[derive(Debug, Deserialize)]
-struct PaginationQuery {
-    limit: Option<i64>,
-    offset: Option<i64>,
-}
-
 async fn get_admin_logs(
     State(audit): State<Arc<AuditService>>,
     auth_user: AuthUser,
-    Query(pagination): Query<PaginationQuery>,
-) -> Result<ApiResponse<Vec<crate::models::audit::AuditLog>>, ApiError> {
+    Query(page_query): Query<PageQuery>,
+) -> Result<ApiResponse<PaginatedResponse<AuditLog>>, ApiError> {
     // Require admin access
     require_kyc_level(3, &auth_user)?;
 
-    let limit = pagination.limit.unwrap_or(50);
-    let offset = pagination.offset.unwrap_or(0);
+    let page = page_query.to_request_or(AUDIT_PAGE_SIZE)?;
 
-    let logs = audit.get_admin_logs(auth_user.id, limit, offset).await
+    let logs = audit.get_admin_logs(auth_user.id, &page).await
         .map_err(|e| ApiError::InternalError(e.into()))?;
 
-    Ok(ApiResponse::success(logs))
+    Ok(ApiResponse::success(PaginatedResponse::new(logs, &page)))
 }
 
 #[derive(Debug, Deserialize)]
@@ -55,40 +52,37 @@ async fn get_entity_logs(
     State(audit): State<Arc<AuditService>>,
     auth_user: AuthUser,
     Path(params): Path<EntityPathParams>,
-    Query(pagination): Query<PaginationQuery>,
-) -> Result<ApiResponse<Vec<crate::models::audit::AuditLog>>, ApiError> {
+    Query(page_query): Query<PageQuery>,
+) -> Result<ApiResponse<PaginatedResponse<AuditLog>>, ApiError> {
     // Require admin access
     require_kyc_level(3, &auth_user)?;
 
-    let limit = pagination.limit.unwrap_or(50);
-    let offset = pagination.offset.unwrap_or(0);
+    let page = page_query.to_request_or(AUDIT_PAGE_SIZE)?;
 
-    let logs = audit.get_entity_logs(&params.r#type, params.id, limit, offset).await
+    let logs = audit.get_entity_logs(&params.r#type, params.id, &page).await
         .map_err(|e| ApiError::InternalError(e.into()))?;
 
-    Ok(ApiResponse::success(logs))
+    Ok(ApiResponse::success(PaginatedResponse::new(logs, &page)))
 }
 
 #[derive(Debug, Deserialize)]
 struct SearchQuery {
     q: String,
-    #[serde(flatten)]
-    pagination: PaginationQuery,
 }
 
 async fn search_logs(
     State(audit): State<Arc<AuditService>>,
     auth_user: AuthUser,
     Query(query): Query<SearchQuery>,
-) -> Result<ApiResponse<Vec<crate::models::audit::AuditLog>>, ApiError> {
+    Query(page_query): Query<PageQuery>,
+) -> Result<ApiResponse<PaginatedResponse<AuditLog>>, ApiError> {
     // Require admin access
     require_kyc_level(3, &auth_user)?;
 
-    let limit = query.pagination.limit.unwrap_or(50);
-    let offset = query.pagination.offset.unwrap_or(0);
+    let page = page_query.to_request_or(AUDIT_PAGE_SIZE)?;
 
-    let logs = audit.search_logs(&query.q, limit, offset).await
+    let logs = audit.search_logs(&query.q, &page).await
         .map_err(|e| ApiError::InternalError(e.into()))?;
 
-    Ok(ApiResponse::success(logs))
+    Ok(ApiResponse::success(PaginatedResponse::new(logs, &page)))
 }
@@ -1,38 +1,60 @@
 use crate::{
-    api::{error::ApiError, response::ApiResponse},
-    models::user::{User, UserStatus},
-};
-use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
+    api::{
+        error::{ApiError, FieldError},
+        middleware::auth::{access_token_ttl, issue_access_token, AuthUser},
+        response::ApiResponse,
+    },
+    models::{
+        email_verification::{EmailVerificationError, EmailVerificationToken},
+        password_reset::{PasswordResetError, PasswordResetToken},
+        recipient::normalize_phone,
+        session::{ClientInfo, Session, SessionError},
+        two_factor::{TwoFactorError, TwoFactorSecret},
+        user::{User, UserStatus},
+    },
+    services::{
+        email::EmailService,
+        password::{self, PolicyViolation, Verification},
+        token_denylist::TokenDenylist,
+        totp,
+    },
+    telemetry,
 };
 use axum::{
-    extract::State,
-    routing::{get, post},
+    extract::{Path, State},
+    http::HeaderMap,
+    routing::{delete, get, post},
     Json, Router,
 };
-use jsonwebtoken::{encode, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use time::OffsetDateTime;
+use std::sync::Arc;
+use tracing::{error, warn};
+use uuid::Uuid;
 use validator::Validate;
 
 pub fn auth_routes() -> Router {
     Router::new()
         .route("/auth/register", post(register))
         .route("/auth/login", post(login))
+        .route("/auth/refresh", post(refresh))
+        .route("/auth/logout", post(logout))
+        .route("/auth/sessions", get(list_sessions))
+        .route("/auth/sessions/:id", delete(revoke_session))
         .route("/auth/verify-email", post(verify_email))
+        .route("/auth/resend-verification", post(resend_verification))
         .route("/auth/request-reset", post(request_password_reset))
         .route("/auth/reset-password", post(reset_password))
         .route("/auth/enable-2fa", post(enable_2fa))
         .route("/auth/verify-2fa", post(verify_2fa))
+        .route("/auth/disable-2fa", post(disable_2fa))
 }
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct RegisterRequest {
     #[validate(email)]
     pub email: String,
-    #[validate(length(min = 8))]
+    /// Checked against the password policy rather than here
     pub password: String,
     #[validate(length(min = 2))]
     pub full_name: String,
@@ -43,13 +65,24 @@ pub struct RegisterRequest {
 pub struct LoginRequest {
     #[validate(email)]
     pub email: String,
+    /// Matches password::MAX_LENGTH so a login can't be made to hash an
+    /// arbitrarily long input
+    #[validate(length(max = 128))]
     pub password: String,
     pub totp_code: Option<String>,
+    /// Shown in the session list, e.g. "Pixel 8"
+    #[validate(length(max = 100))]
+    pub device_name: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
+    /// Short-lived access token
     pub token: String,
+    /// Seconds until `token` expires
+    pub expires_in: i64,
+    /// Single use; exchange at /auth/refresh for a new pair
+    pub refresh_token: String,
     pub user: UserResponse,
 }
 
@@ -62,6 +95,8 @@ pub struct UserResponse {
     pub kyc_status: String,
     pub kyc_level: i32,
     pub two_factor_enabled: bool,
+    pub email_verified: bool,
+    pub phone_verified: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -75,6 +110,8 @@ impl From<User> for UserResponse {
             kyc_status: user.kyc_status.to_string(),
             kyc_level: user.kyc_level,
             two_factor_enabled: user.two_factor_enabled,
+            email_verified: user.email_verified_at.is_some(),
+            phone_verified: user.phone_verified_at.is_some(),
             created_at: user.created_at,
         }
     }
@@ -82,24 +119,30 @@ impl From<User> for UserResponse {
 
 async fn register(
     State(pool): State<PgPool>,
+    State(email_service): State<Arc<EmailService>>,
     Json(req): Json<RegisterRequest>,
 ) -> Result<ApiResponse<UserResponse>, ApiError> {
     // Validate request
-    req.validate()
-        .map_err(|e| ApiError::ValidationError(e.to_string()))?;
+    req.validate()?;
 
     // Check if user exists
     if User::find_by_email(&pool, &req.email).await?.is_some() {
-        return Err(ApiError::ValidationError("Email already registered".to_string()));
+        return Err(ApiError::validation("Email already registered".to_string()));
     }
 
+    // Phone numbers are stored in E.164 so P2P transfers can find them
+    let phone_number = req
+        .phone_number
+        .as_deref()
+        .map(normalize_phone)
+        .transpose()
+        .map_err(|e| ApiError::validation(e.to_string()))?;
+
+    password::check_policy(&req.password, &req.email)
+        .map_err(|v| password_policy_error("password", v))?;
+
     // Hash password
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(req.password.as_bytes(), &salt)
-        .map_err(|e| ApiError::InternalError(e.into()))?
-        .to_string();
+    let password_hash = hash_password(&req.password)?;
 
     // Create user
     let user = User::create(
@@ -107,39 +150,33 @@ async fn register(
         &req.email,
         &password_hash,
         &req.full_name,
-        req.phone_number.as_deref(),
+        phone_number.as_deref(),
     )
     .await?;
 
-    // Send verification email
-    // TODO: Implement email service
-    
+    // The account exists either way; a lost email can be resent
+    if let Err(e) = send_verification(&pool, &email_service, &user).await {
+        error!("Failed to send verification email to user {}: {}", user.id, e);
+    }
+
     Ok(ApiResponse::success(UserResponse::from(user)))
 }
 
 async fn login(
     State(pool): State<PgPool>,
+    headers: HeaderMap,
     Json(req): Json<LoginRequest>,
 ) -> Result<ApiResponse<AuthResponse>, ApiError> {
     // Validate request
-    req.validate()
-        .map_err(|e| ApiError::ValidationError(e.to_string()))?;
+    req.validate()?;
 
     // Find user
-    let user = User::find_by_email(&pool, &req.email)
+    let mut user = User::find_by_email(&pool, &req.email)
         .await?
         .ok_or_else(|| ApiError::AuthenticationError("Invalid credentials".to_string()))?;
 
     // Verify password
-    let parsed_hash = PasswordHash::new(&user.password_hash)
-        .map_err(|e| ApiError::InternalError(e.into()))?;
-    
-    if !Argon2::default()
-        .verify_password(req.password.as_bytes(), &parsed_hash)
-        .is_ok()
-    {
-        return Err(ApiError::AuthenticationError("Invalid credentials".to_string()));
-    }
+    let needs_rehash = verify_password(&user, &req.password)?;
 
     // Check user status
     if user.status != UserStatus::Active {
@@ -148,69 +185,311 @@ async fn login(
         ));
     }
 
-    // Verify 2FA if enabled
+    // Verify 2FA if enabled. A backup code may stand in for the TOTP code.
     if user.two_factor_enabled {
-        if let Some(totp_code) = req.totp_code {
-            if !verify_totp(&user.totp_secret.unwrap(), &totp_code) {
-                return Err(ApiError::AuthenticationError("Invalid 2FA code".to_string()));
-            }
-        } else {
+        let Some(totp_code) = req.totp_code else {
             return Err(ApiError::AuthenticationError("2FA code required".to_string()));
-        }
+        };
+        TwoFactorSecret::verify(&pool, user.id, &totp_code)
+            .await
+            .map_err(|e| match e {
+                TwoFactorError::InvalidCode => {
+                    ApiError::AuthenticationError("Invalid 2FA code".to_string())
+                }
+                e => two_factor_error(e),
+            })?;
     }
 
-    // Generate JWT
-    let claims = Claims {
-        sub: user.id,
-        exp: (OffsetDateTime::now_utc() + time::Duration::hours(24)).unix_timestamp() as usize,
-        iat: OffsetDateTime::now_utc().unix_timestamp() as usize,
-        kyc_level: user.kyc_level,
+    // Only now is the user fully authenticated. Move legacy bcrypt hashes and
+    // hashes made with older parameters onto the current ones.
+    if needs_rehash {
+        rehash_password(&pool, &mut user, &req.password).await;
+    }
+
+    // Start a session and issue its first token pair
+    let client = client_info(&headers, req.device_name);
+    let (session, refresh_token) = Session::create(&pool, user.id, &client)
+        .await
+        .map_err(session_error)?;
+    let token = issue_access_token(&user, session.family_id)?;
+
+    Ok(ApiResponse::success(AuthResponse {
+        token,
+        expires_in: access_token_ttl().num_seconds(),
+        refresh_token,
+        user: UserResponse::from(user),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+async fn refresh(
+    State(pool): State<PgPool>,
+    State(denylist): State<Arc<TokenDenylist>>,
+    headers: HeaderMap,
+    Json(req): Json<RefreshRequest>,
+) -> Result<ApiResponse<AuthResponse>, ApiError> {
+    let client = client_info(&headers, None);
+    let (session, refresh_token) = match Session::rotate(&pool, &req.refresh_token, &client).await {
+        Ok(rotated) => rotated,
+        Err(SessionError::TokenReused { family_id }) => {
+            // Whoever holds the newer token loses their access tokens too
+            warn!("Refresh token reused; revoked session {}", family_id);
+            denylist
+                .deny_session(family_id)
+                .await
+                .map_err(|e| ApiError::InternalError(e.into()))?;
+            return Err(ApiError::AuthenticationError(
+                "Refresh token was already used; please log in again".to_string(),
+            ));
+        }
+        Err(e) => return Err(session_error(e)),
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(std::env::var("JWT_SECRET").unwrap().as_bytes()),
-    )
-    .map_err(|e| ApiError::InternalError(e.into()))?;
+    // Pick up KYC level changes made since the last token was issued
+    let user = User::find_by_id(&pool, session.user_id)
+        .await?
+        .ok_or_else(|| ApiError::AuthenticationError("Invalid refresh token".to_string()))?;
+    if user.status != UserStatus::Active {
+        return Err(ApiError::AuthenticationError(
+            "Account is not active".to_string(),
+        ));
+    }
+
+    let token = issue_access_token(&user, session.family_id)?;
 
     Ok(ApiResponse::success(AuthResponse {
         token,
+        expires_in: access_token_ttl().num_seconds(),
+        refresh_token,
         user: UserResponse::from(user),
     }))
 }
 
+async fn logout(
+    State(pool): State<PgPool>,
+    State(denylist): State<Arc<TokenDenylist>>,
+    auth_user: AuthUser,
+) -> Result<ApiResponse<()>, ApiError> {
+    end_session(&pool, &denylist, &auth_user, auth_user.session_id, "logout").await?;
+
+    Ok(ApiResponse::message("Logged out successfully"))
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub device_name: Option<String>,
+    pub ip_address: String,
+    pub user_agent: String,
+    /// When the session's current refresh token was issued
+    pub last_active_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    /// Whether this is the session making the request
+    pub current: bool,
+}
+
+async fn list_sessions(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+) -> Result<ApiResponse<Vec<SessionResponse>>, ApiError> {
+    let sessions = Session::list_active(&pool, auth_user.id)
+        .await
+        .map_err(session_error)?
+        .into_iter()
+        .map(|session| SessionResponse {
+            id: session.family_id,
+            current: session.family_id == auth_user.session_id,
+            device_name: session.device_name,
+            ip_address: session.ip_address,
+            user_agent: session.user_agent,
+            last_active_at: session.created_at,
+            expires_at: session.expires_at,
+        })
+        .collect();
+
+    Ok(ApiResponse::success(sessions))
+}
+
+async fn revoke_session(
+    State(pool): State<PgPool>,
+    State(denylist): State<Arc<TokenDenylist>>,
+    auth_user: AuthUser,
+    Path(session_id): Path<Uuid>,
+) -> Result<ApiResponse<()>, ApiError> {
+    end_session(&pool, &denylist, &auth_user, session_id, "revoked_by_user").await?;
+
+    Ok(ApiResponse::message("Session revoked successfully"))
+}
+
+/// Revokes one of the caller's sessions and blocks its outstanding access
+/// tokens
+async fn end_session(
+    pool: &PgPool,
+    denylist: &TokenDenylist,
+    auth_user: &AuthUser,
+    session_id: Uuid,
+    reason: &str,
+) -> Result<(), ApiError> {
+    Session::revoke(pool, auth_user.id, session_id, reason)
+        .await
+        .map_err(session_error)?;
+    denylist
+        .deny_session(session_id)
+        .await
+        .map_err(|e| ApiError::InternalError(e.into()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
 async fn verify_email(
     State(pool): State<PgPool>,
-    Json(token): Json<String>,
+    Json(req): Json<VerifyEmailRequest>,
 ) -> Result<ApiResponse<()>, ApiError> {
-    // Verify token and update user
-    // TODO: Implement email verification logic
+    EmailVerificationToken::consume(&pool, &req.token)
+        .await
+        .map_err(email_verification_error)?;
+
+    // Access tokens pick up the change on the next refresh
     Ok(ApiResponse::message("Email verified successfully"))
 }
 
+async fn resend_verification(
+    State(pool): State<PgPool>,
+    State(email_service): State<Arc<EmailService>>,
+    auth_user: AuthUser,
+) -> Result<ApiResponse<()>, ApiError> {
+    let user = User::find_by_id(&pool, auth_user.id)
+        .await?
+        .ok_or_else(|| ApiError::NotFoundError("User not found".to_string()))?;
+
+    send_verification(&pool, &email_service, &user).await?;
+
+    Ok(ApiResponse::message("Verification email sent"))
+}
+
+/// Issues a verification token and queues the email carrying it
+async fn send_verification(
+    pool: &PgPool,
+    email_service: &EmailService,
+    user: &User,
+) -> Result<(), ApiError> {
+    let token = EmailVerificationToken::issue(pool, user.id)
+        .await
+        .map_err(email_verification_error)?;
+
+    email_service
+        .send_verification_email(&user.email, &user.full_name, &token)
+        .await
+        .map_err(|e| ApiError::InternalError(e.into()))
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RequestPasswordResetRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
 async fn request_password_reset(
     State(pool): State<PgPool>,
-    Json(email): Json<String>,
+    State(email_service): State<Arc<EmailService>>,
+    Json(req): Json<RequestPasswordResetRequest>,
 ) -> Result<ApiResponse<()>, ApiError> {
-    // Generate reset token and send email
-    // TODO: Implement password reset logic
-    Ok(ApiResponse::message("Password reset instructions sent"))
+    req.validate()?;
+
+    // The reply must not reveal whether the account exists, so the lookup
+    // and email run after responding and their failures are only logged
+    let context = telemetry::current().unwrap_or_else(telemetry::RequestContext::generate);
+    tokio::spawn(context.scope("password_reset", async move {
+        if let Err(e) = send_password_reset(&pool, &email_service, &req.email).await {
+            error!("Failed to process password reset request: {}", e);
+        }
+    }));
+
+    Ok(ApiResponse::message(
+        "If an account exists for that email, password reset instructions have been sent",
+    ))
+}
+
+async fn send_password_reset(
+    pool: &PgPool,
+    email_service: &EmailService,
+    email: &str,
+) -> anyhow::Result<()> {
+    let Some(user) = User::find_by_email(pool, email).await? else {
+        return Ok(());
+    };
+
+    match PasswordResetToken::issue(pool, user.id).await? {
+        Some(token) => {
+            email_service
+                .send_password_reset(&user.email, &user.full_name, &token)
+                .await?
+        }
+        None => warn!("Password reset rate limit reached for user {}", user.id),
+    }
+
+    Ok(())
 }
 
 async fn reset_password(
     State(pool): State<PgPool>,
+    State(email_service): State<Arc<EmailService>>,
+    State(denylist): State<Arc<TokenDenylist>>,
     Json(req): Json<ResetPasswordRequest>,
 ) -> Result<ApiResponse<()>, ApiError> {
-    // Verify token and update password
-    // TODO: Implement password reset logic
+    req.validate()?;
+
+    let user_id = PasswordResetToken::find_user_id(&pool, &req.token)
+        .await
+        .map_err(password_reset_error)?;
+    let user = User::find_by_id(&pool, user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFoundError("User not found".to_string()))?;
+    password::check_policy(&req.new_password, &user.email)
+        .map_err(|v| password_policy_error("new_password", v))?;
+
+    // Revokes every session along with the password change
+    let password_hash = hash_password(&req.new_password)?;
+    PasswordResetToken::consume(&pool, &req.token, &password_hash)
+        .await
+        .map_err(password_reset_error)?;
+
+    // Best effort: the sessions are already gone, so outstanding access
+    // tokens still run out within their short lifetime if this fails
+    if let Err(e) = denylist
+        .deny_user(user_id, chrono::Utc::now().timestamp_micros())
+        .await
+    {
+        error!("Failed to deny access tokens of user {} after password reset: {}", user_id, e);
+    }
+
+    if let Err(e) = email_service
+        .send_security_alert(
+            &user.email,
+            &user.full_name,
+            "Password Reset",
+            "Your password was reset and all devices were signed out. \
+             If this wasn't you, contact support immediately.",
+        )
+        .await
+    {
+        error!("Failed to send password reset alert to user {}: {}", user.id, e);
+    }
+
     Ok(ApiResponse::message("Password reset successfully"))
 }
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct ResetPasswordRequest {
     pub token: String,
-    #[validate(length(min = 8))]
+    /// Checked against the password policy rather than here
     pub new_password: String,
 }
 
@@ -218,29 +497,214 @@ async fn enable_2fa(
     State(pool): State<PgPool>,
     auth_user: AuthUser,
 ) -> Result<ApiResponse<TwoFactorResponse>, ApiError> {
-    // Generate TOTP secret and QR code
-    // TODO: Implement 2FA setup
-    Ok(ApiResponse::message("2FA enabled successfully"))
+    let user = User::find_by_id(&pool, auth_user.id)
+        .await?
+        .ok_or_else(|| ApiError::NotFoundError("User not found".to_string()))?;
+
+    // The secret stays pending until a code from it is confirmed
+    let secret = TwoFactorSecret::begin_enrollment(&pool, user.id)
+        .await
+        .map_err(two_factor_error)?;
+
+    let otpauth_uri = totp::otpauth_uri(&secret, &user.email);
+    let qr_code = totp::qr_png_data_uri(&otpauth_uri)
+        .map_err(|e| ApiError::InternalError(e.into()))?;
+    let qr_code_svg = totp::qr_svg(&otpauth_uri).map_err(|e| ApiError::InternalError(e.into()))?;
+
+    Ok(ApiResponse::success(TwoFactorResponse {
+        secret: totp::encode_secret(&secret),
+        otpauth_uri,
+        qr_code,
+        qr_code_svg,
+    }))
 }
 
 #[derive(Debug, Serialize)]
 pub struct TwoFactorResponse {
+    /// Base32 secret, for entering into an authenticator app by hand
     pub secret: String,
+    pub otpauth_uri: String,
+    /// PNG data URI
     pub qr_code: String,
+    pub qr_code_svg: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct VerifyTwoFactorRequest {
+    #[validate(length(min = 6, max = 6))]
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupCodesResponse {
+    /// Shown once; only hashes are kept
+    pub backup_codes: Vec<String>,
 }
 
 async fn verify_2fa(
+    State(pool): State<PgPool>,
+    State(email_service): State<Arc<EmailService>>,
+    auth_user: AuthUser,
+    Json(req): Json<VerifyTwoFactorRequest>,
+) -> Result<ApiResponse<BackupCodesResponse>, ApiError> {
+    req.validate()?;
+
+    let user = User::find_by_id(&pool, auth_user.id)
+        .await?
+        .ok_or_else(|| ApiError::NotFoundError("User not found".to_string()))?;
+
+    let backup_codes = TwoFactorSecret::confirm_enrollment(&pool, user.id, &req.code)
+        .await
+        .map_err(two_factor_error)?;
+
+    // 2FA is on either way. The codes are only ever in this response.
+    if let Err(e) = email_service
+        .send_2fa_enabled(&user.email, &user.full_name)
+        .await
+    {
+        error!("Failed to send 2FA enabled email to user {}: {}", user.id, e);
+    }
+
+    Ok(ApiResponse::success(BackupCodesResponse { backup_codes }))
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct DisableTwoFactorRequest {
+    pub password: String,
+    /// A current TOTP code or an unused backup code
+    #[validate(length(min = 1))]
+    pub code: String,
+}
+
+async fn disable_2fa(
     State(pool): State<PgPool>,
     auth_user: AuthUser,
-    Json(code): Json<String>,
+    Json(req): Json<DisableTwoFactorRequest>,
 ) -> Result<ApiResponse<()>, ApiError> {
-    // Verify TOTP code
-    // TODO: Implement 2FA verification
-    Ok(ApiResponse::message("2FA verified successfully"))
+    req.validate()?;
+
+    let user = User::find_by_id(&pool, auth_user.id)
+        .await?
+        .ok_or_else(|| ApiError::NotFoundError("User not found".to_string()))?;
+
+    // Both factors are needed, so a stolen session alone cannot turn 2FA off
+    verify_password(&user, &req.password)?;
+    TwoFactorSecret::verify(&pool, user.id, &req.code)
+        .await
+        .map_err(two_factor_error)?;
+
+    TwoFactorSecret::disable(&pool, user.id)
+        .await
+        .map_err(two_factor_error)?;
+
+    Ok(ApiResponse::message("2FA disabled successfully"))
+}
+
+fn hash_password(password: &str) -> Result<String, ApiError> {
+    password::hash(password).map_err(|e| ApiError::InternalError(e.into()))
+}
+
+/// Checks the user's password, returning whether the stored hash should be
+/// replaced with one at the current parameters
+fn verify_password(user: &User, password: &str) -> Result<bool, ApiError> {
+    match password::verify(password, &user.password_hash) {
+        Ok(Verification::Valid { needs_rehash }) => Ok(needs_rehash),
+        Ok(Verification::Invalid) => {
+            Err(ApiError::AuthenticationError("Invalid credentials".to_string()))
+        }
+        Err(e) => Err(ApiError::InternalError(e.into())),
+    }
+}
+
+/// Best effort; the old hash still works if this fails, and the next login
+/// tries again
+async fn rehash_password(pool: &PgPool, user: &mut User, password: &str) {
+    let result = match password::hash(password) {
+        Ok(password_hash) => user
+            .update_password_hash(pool, &password_hash)
+            .await
+            .map_err(anyhow::Error::from),
+        Err(e) => Err(e.into()),
+    };
+
+    if let Err(e) = result {
+        warn!("Failed to rehash password for user {}: {}", user.id, e);
+    }
+}
+
+fn password_policy_error(field: &str, violation: PolicyViolation) -> ApiError {
+    let mut params = serde_json::Map::new();
+    if matches!(violation, PolicyViolation::TooShort | PolicyViolation::TooLong) {
+        params.insert("min".to_string(), password::MIN_LENGTH.into());
+        params.insert("max".to_string(), password::MAX_LENGTH.into());
+    }
+
+    ApiError::invalid_fields(vec![FieldError {
+        field: Some(field.to_string()),
+        code: violation.code().to_string(),
+        message: Some(violation.to_string()),
+        params,
+    }])
+}
+
+fn password_reset_error(e: PasswordResetError) -> ApiError {
+    match e {
+        PasswordResetError::InvalidToken => ApiError::validation(e.to_string()),
+        PasswordResetError::SessionError(_) | PasswordResetError::DatabaseError(_) => {
+            ApiError::InternalError(e.into())
+        }
+    }
+}
+
+/// Device details recorded against a session
+fn client_info(headers: &HeaderMap, device_name: Option<String>) -> ClientInfo {
+    let ip_address = headers
+        .get("x-forwarded-for")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.split(',').next())
+        .map(str::trim)
+        .unwrap_or("unknown")
+        .to_string();
+
+    let user_agent = headers
+        .get("user-agent")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string();
+
+    ClientInfo {
+        device_name,
+        ip_address,
+        user_agent,
+    }
+}
+
+fn email_verification_error(e: EmailVerificationError) -> ApiError {
+    match e {
+        EmailVerificationError::InvalidToken => ApiError::validation(e.to_string()),
+        EmailVerificationError::AlreadyVerified => ApiError::ConflictError(e.to_string()),
+        EmailVerificationError::RateLimited => ApiError::RateLimitError,
+        EmailVerificationError::DatabaseError(_) => ApiError::InternalError(e.into()),
+    }
 }
 
-// Helper function to verify TOTP codes
-fn verify_totp(secret: &str, code: &str) -> bool {
-    // TODO: Implement TOTP verification
-    true
+fn session_error(e: SessionError) -> ApiError {
+    match e {
+        SessionError::InvalidToken | SessionError::Expired | SessionError::TokenReused { .. } => {
+            ApiError::AuthenticationError(e.to_string())
+        }
+        SessionError::NotFound => ApiError::NotFoundError(e.to_string()),
+        SessionError::DatabaseError(_) => ApiError::InternalError(e.into()),
+    }
+}
+
+fn two_factor_error(e: TwoFactorError) -> ApiError {
+    match e {
+        TwoFactorError::AlreadyEnabled
+        | TwoFactorError::NotEnabled
+        | TwoFactorError::NoPendingEnrollment => ApiError::ConflictError(e.to_string()),
+        TwoFactorError::InvalidCode => ApiError::validation(e.to_string()),
+        TwoFactorError::LockedOut => ApiError::RateLimitError,
+        e => ApiError::InternalError(e.into()),
+    }
 }
@@ -1,57 +1,393 @@
+use crate::{
+    models::{
+        currency::MoneyError,
+        fx::FxError,
+        hold::HoldError,
+        limit::LimitError,
+        pending_transfer::PendingTransferError,
+        schedule::ScheduleError,
+        transaction::TransactionError,
+        wallet::WalletError,
+    },
+    telemetry::current_request_id,
+};
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
 use thiserror::Error;
+use tracing::error;
+use validator::{ValidationErrors, ValidationErrorsKind};
 
 #[derive(Error, Debug)]
 pub enum ApiError {
     #[error("Authentication failed: {0}")]
     AuthenticationError(String),
-    
+
     #[error("Authorization failed: {0}")]
     AuthorizationError(String),
-    
-    #[error("Invalid input: {0}")]
-    ValidationError(String),
-    
+
+    #[error("KYC level {required} required. Current level: {current}")]
+    KycLevelRequired { required: i32, current: i32 },
+
+    #[error("Email address must be verified first")]
+    EmailNotVerified,
+
+    /// `fields` is empty when the failure isn't tied to particular fields
+    #[error("Invalid input: {message}")]
+    ValidationError {
+        message: String,
+        fields: Vec<FieldError>,
+    },
+
     #[error("Resource not found: {0}")]
     NotFoundError(String),
-    
+
     #[error("Insufficient funds: {0}")]
     InsufficientFundsError(String),
-    
+
+    #[error("Limit exceeded: {0}")]
+    LimitExceededError(String),
+
+    #[error("Conflict: {0}")]
+    ConflictError(String),
+
     #[error("Rate limit exceeded")]
     RateLimitError,
-    
+
     #[error("Internal server error")]
     InternalError(#[from] anyhow::Error),
 }
 
+/// Stable, machine-readable error codes. Clients branch on these rather
+/// than on messages or HTTP statuses, so existing values must not change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    AuthenticationFailed,
+    Forbidden,
+    KycLevelRequired,
+    EmailNotVerified,
+    ValidationFailed,
+    NotFound,
+    InsufficientFunds,
+    LimitExceeded,
+    Conflict,
+    RateLimited,
+    InternalError,
+}
+
+/// A validation failure tied to one request field
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    /// Dotted path to the field; absent when the whole request is invalid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// What went wrong, e.g. `length`, `range`, `required`
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Constraint values for the code, e.g. `{"min": 1, "max": 50}`
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub params: Map<String, Value>,
+}
+
+fn summarize_fields(fields: &[FieldError]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            let reason = field.message.as_deref().unwrap_or(&field.code);
+            match &field.field {
+                Some(name) => format!("{}: {}", name, reason),
+                None => reason.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl ApiError {
+    /// A validation failure described by a message alone
+    pub fn validation(message: impl Into<String>) -> Self {
+        ApiError::ValidationError {
+            message: message.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// A validation failure listing the offending fields
+    pub fn invalid_fields(fields: Vec<FieldError>) -> Self {
+        ApiError::ValidationError {
+            message: summarize_fields(&fields),
+            fields,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ApiError::AuthenticationError(_) => ErrorCode::AuthenticationFailed,
+            ApiError::AuthorizationError(_) => ErrorCode::Forbidden,
+            ApiError::KycLevelRequired { .. } => ErrorCode::KycLevelRequired,
+            ApiError::EmailNotVerified => ErrorCode::EmailNotVerified,
+            ApiError::ValidationError { .. } => ErrorCode::ValidationFailed,
+            ApiError::NotFoundError(_) => ErrorCode::NotFound,
+            ApiError::InsufficientFundsError(_) => ErrorCode::InsufficientFunds,
+            ApiError::LimitExceededError(_) => ErrorCode::LimitExceeded,
+            ApiError::ConflictError(_) => ErrorCode::Conflict,
+            ApiError::RateLimitError => ErrorCode::RateLimited,
+            ApiError::InternalError(_) => ErrorCode::InternalError,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::AuthenticationError(_) => StatusCode::UNAUTHORIZED,
+            ApiError::AuthorizationError(_)
+            | ApiError::KycLevelRequired { .. }
+            | ApiError::EmailNotVerified => StatusCode::FORBIDDEN,
+            ApiError::ValidationError { .. } => StatusCode::BAD_REQUEST,
+            ApiError::NotFoundError(_) => StatusCode::NOT_FOUND,
+            ApiError::InsufficientFundsError(_) => StatusCode::BAD_REQUEST,
+            ApiError::LimitExceededError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::ConflictError(_) => StatusCode::CONFLICT,
+            ApiError::RateLimitError => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            ApiError::AuthenticationError(_) => (StatusCode::UNAUTHORIZED, self.to_string()),
-            ApiError::AuthorizationError(_) => (StatusCode::FORBIDDEN, self.to_string()),
-            ApiError::ValidationError(_) => (StatusCode::BAD_REQUEST, self.to_string()),
-            ApiError::NotFoundError(_) => (StatusCode::NOT_FOUND, self.to_string()),
-            ApiError::InsufficientFundsError(_) => (StatusCode::BAD_REQUEST, self.to_string()),
-            ApiError::RateLimitError => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
-            ApiError::InternalError(_) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Internal server error".to_string(),
-            ),
+        let status = self.status();
+        let code = self.code();
+        let request_id = current_request_id();
+
+        let message = match &self {
+            ApiError::InternalError(e) => {
+                error!("Internal error: {:?}", e);
+                "Internal server error".to_string()
+            }
+            _ => self.to_string(),
         };
 
-        let body = Json(json!({
-            "error": {
-                "message": error_message,
-                "code": status.as_u16()
+        let mut error = json!({
+            "code": code,
+            "message": message,
+            "status": status.as_u16(),
+            "request_id": request_id,
+        });
+        match self {
+            ApiError::ValidationError { fields, .. } if !fields.is_empty() => {
+                error["fields"] = json!(fields)
+            }
+            ApiError::KycLevelRequired { required, current } => {
+                error["details"] = json!({
+                    "required_level": required,
+                    "current_level": current,
+                })
             }
-        }));
+            _ => {}
+        }
+
+        let body = Json(json!({ "error": error }));
 
         (status, body).into_response()
     }
 }
+
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields = Vec::new();
+        collect_field_errors(&errors, None, &mut fields);
+        fields.sort_by(|a, b| a.field.cmp(&b.field));
+
+        ApiError::invalid_fields(fields)
+    }
+}
+
+/// Flattens nested validator errors into dotted field paths, e.g.
+/// `recipients.0.amount`
+fn collect_field_errors(errors: &ValidationErrors, prefix: Option<&str>, out: &mut Vec<FieldError>) {
+    for (name, kind) in errors.errors() {
+        // Struct-level checks are reported against the struct itself
+        let path = match (*name, prefix) {
+            ("__all__", prefix) => prefix.map(str::to_string),
+            (name, Some(prefix)) => Some(format!("{}.{}", prefix, name)),
+            (name, None) => Some(name.to_string()),
+        };
+
+        match kind {
+            ValidationErrorsKind::Field(field_errors) => {
+                for error in field_errors {
+                    let params = error
+                        .params
+                        .iter()
+                        .filter(|(key, _)| key.as_ref() != "value")
+                        .map(|(key, value)| (key.to_string(), value.clone()))
+                        .collect();
+
+                    out.push(FieldError {
+                        field: path.clone(),
+                        code: error.code.to_string(),
+                        message: error.message.as_ref().map(|message| message.to_string()),
+                        params,
+                    });
+                }
+            }
+            ValidationErrorsKind::Struct(nested) => {
+                collect_field_errors(nested, path.as_deref(), out);
+            }
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    let item_path = match &path {
+                        Some(path) => format!("{}.{}", path, index),
+                        None => index.to_string(),
+                    };
+                    collect_field_errors(nested, Some(&item_path), out);
+                }
+            }
+        }
+    }
+}
+
+// Model errors that reach the API. Anything that is not the client's fault
+// is reported as an internal error.
+impl From<WalletError> for ApiError {
+    fn from(e: WalletError) -> Self {
+        match e {
+            WalletError::InsufficientFunds { .. } => ApiError::InsufficientFundsError(e.to_string()),
+            WalletError::UnsupportedCurrency(_) | WalletError::InvalidAmount(_) => {
+                ApiError::validation(e.to_string())
+            }
+            WalletError::InactiveWallet
+            | WalletError::NonZeroBalance { .. }
+            | WalletError::AlreadyClosed
+            | WalletError::UnexpectedStatus(_) => ApiError::ConflictError(e.to_string()),
+            WalletError::DatabaseError(_) => ApiError::InternalError(e.into()),
+        }
+    }
+}
+
+impl From<HoldError> for ApiError {
+    fn from(e: HoldError) -> Self {
+        match e {
+            HoldError::NotFound => ApiError::NotFoundError(e.to_string()),
+            HoldError::NotActive => ApiError::ConflictError(e.to_string()),
+            HoldError::InvalidAmount(_) => ApiError::validation(e.to_string()),
+            HoldError::WalletError(e) => e.into(),
+            HoldError::LedgerError(_) | HoldError::DatabaseError(_) => {
+                ApiError::InternalError(e.into())
+            }
+        }
+    }
+}
+
+impl From<LimitError> for ApiError {
+    fn from(e: LimitError) -> Self {
+        match e {
+            LimitError::Exceeded { .. } | LimitError::NotConfigured { .. } => {
+                ApiError::LimitExceededError(e.to_string())
+            }
+            LimitError::DatabaseError(_) => ApiError::InternalError(e.into()),
+        }
+    }
+}
+
+impl From<MoneyError> for ApiError {
+    fn from(e: MoneyError) -> Self {
+        ApiError::validation(e.to_string())
+    }
+}
+
+impl From<FxError> for ApiError {
+    fn from(e: FxError) -> Self {
+        match e {
+            FxError::QuoteNotFound => ApiError::NotFoundError(e.to_string()),
+            FxError::QuoteExpired | FxError::QuoteUsed => ApiError::ConflictError(e.to_string()),
+            FxError::RateNotFound(..) | FxError::InvalidPair(_) | FxError::InvalidAmount(_) => {
+                ApiError::validation(e.to_string())
+            }
+            FxError::DatabaseError(_) => ApiError::InternalError(e.into()),
+        }
+    }
+}
+
+impl From<TransactionError> for ApiError {
+    fn from(e: TransactionError) -> Self {
+        match e {
+            TransactionError::InvalidTransaction(_) => ApiError::validation(e.to_string()),
+            TransactionError::WalletError(e) => e.into(),
+            TransactionError::HoldError(e) => e.into(),
+            TransactionError::LimitError(e) => e.into(),
+            TransactionError::MoneyError(e) => e.into(),
+            TransactionError::FxError(e) => e.into(),
+            TransactionError::InvalidStatusTransition { .. } => {
+                ApiError::ConflictError(e.to_string())
+            }
+            TransactionError::NotFound => ApiError::NotFoundError(e.to_string()),
+            TransactionError::LedgerError(_) | TransactionError::DatabaseError(_) => {
+                ApiError::InternalError(e.into())
+            }
+        }
+    }
+}
+
+impl From<PendingTransferError> for ApiError {
+    fn from(e: PendingTransferError) -> Self {
+        match e {
+            PendingTransferError::NotFound => ApiError::NotFoundError(e.to_string()),
+            PendingTransferError::NotClaimable => ApiError::ConflictError(e.to_string()),
+            PendingTransferError::NotRecipient => ApiError::AuthorizationError(e.to_string()),
+            PendingTransferError::MoneyError(e) => e.into(),
+            PendingTransferError::TransactionError(e) => e.into(),
+            PendingTransferError::DatabaseError(_) => ApiError::InternalError(e.into()),
+        }
+    }
+}
+
+impl From<ScheduleError> for ApiError {
+    fn from(e: ScheduleError) -> Self {
+        match e {
+            ScheduleError::InvalidSchedule(_) => ApiError::validation(e.to_string()),
+            ScheduleError::NotFound => ApiError::NotFoundError(e.to_string()),
+            ScheduleError::InvalidStatus(_) => ApiError::ConflictError(e.to_string()),
+            ScheduleError::MoneyError(e) => e.into(),
+            ScheduleError::DatabaseError(_) => ApiError::InternalError(e.into()),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        ApiError::InternalError(e.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use validator::Validate;
+
+    #[derive(Validate)]
+    struct Nickname {
+        #[validate(length(min = 1, max = 50))]
+        nickname: String,
+    }
+
+    #[test]
+    fn test_validation_errors_keep_field_and_params() {
+        let errors = Nickname { nickname: String::new() }.validate().unwrap_err();
+
+        let ApiError::ValidationError { fields, .. } = ApiError::from(errors) else {
+            panic!("expected field errors");
+        };
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].field.as_deref(), Some("nickname"));
+        assert_eq!(fields[0].code, "length");
+        assert_eq!(fields[0].params.get("max"), Some(&json!(50)));
+        assert!(!fields[0].params.contains_key("value"));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{
+    api::{
+        error::ApiError,
+        middleware::auth::{require_kyc_level, require_verified_email, AuthUser},
+        response::ApiResponse,
+        transaction::TransactionResponse,
+    },
+    models::{
+        currency::{validate_amount, validate_currency_code},
+        fx::{FxQuote, FxRate},
+        transaction::Transaction,
+        wallet::Wallet,
+    },
+};
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+pub fn fx_routes() -> Router {
+    Router::new()
+        .route("/fx/rates/:from/:to", get(get_rate))
+        .route("/fx/quotes", post(create_quote))
+        .route("/fx/conversions", post(create_conversion))
+        .route("/admin/fx/rates", post(publish_rate))
+}
+
+// Request/Response types
+#[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "validate_quote_amount"))]
+pub struct CreateQuoteRequest {
+    #[validate(custom = "validate_currency_code")]
+    pub from_currency: String,
+    #[validate(custom = "validate_currency_code")]
+    pub to_currency: String,
+    pub from_amount: Decimal,
+}
+
+fn validate_quote_amount(req: &CreateQuoteRequest) -> Result<(), validator::ValidationError> {
+    validate_amount(req.from_amount, &req.from_currency)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateConversionRequest {
+    pub quote_id: Uuid,
+    pub debit_wallet_id: Uuid,
+    pub credit_wallet_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PublishRateRequest {
+    #[validate(custom = "validate_currency_code")]
+    pub base_currency: String,
+    #[validate(custom = "validate_currency_code")]
+    pub quote_currency: String,
+    #[validate(range(min = 0.0000000001))]
+    pub rate: Decimal,
+    pub effective_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RateResponse {
+    pub from_currency: String,
+    pub to_currency: String,
+    pub mid_rate: Decimal,
+}
+
+// Handlers
+async fn get_rate(
+    State(pool): State<PgPool>,
+    _auth_user: AuthUser,
+    Path((from, to)): Path<(String, String)>,
+) -> Result<ApiResponse<RateResponse>, ApiError> {
+    let mid_rate = FxRate::current(&pool, &from, &to).await?;
+
+    Ok(ApiResponse::success(RateResponse {
+        from_currency: from,
+        to_currency: to,
+        mid_rate,
+    }))
+}
+
+async fn create_quote(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Json(req): Json<CreateQuoteRequest>,
+) -> Result<ApiResponse<FxQuote>, ApiError> {
+    require_kyc_level(1, &auth_user)?;
+
+    // Validate request
+    req.validate()?;
+
+    let quote = FxQuote::create(
+        &pool,
+        auth_user.id,
+        &req.from_currency,
+        &req.to_currency,
+        req.from_amount,
+    )
+    .await?;
+
+    Ok(ApiResponse::success(quote))
+}
+
+async fn create_conversion(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Json(req): Json<CreateConversionRequest>,
+) -> Result<ApiResponse<TransactionResponse>, ApiError> {
+    require_kyc_level(1, &auth_user)?;
+    require_verified_email(&auth_user)?;
+
+    // Verify ownership of both wallets
+    for wallet_id in [req.debit_wallet_id, req.credit_wallet_id] {
+        let wallet = Wallet::find(&pool, wallet_id).await?;
+        if wallet.user_id != auth_user.id {
+            return Err(ApiError::AuthorizationError(
+                "Not authorized to access this wallet".to_string(),
+            ));
+        }
+    }
+
+    let transaction = Transaction::convert(
+        &pool,
+        req.quote_id,
+        req.debit_wallet_id,
+        req.credit_wallet_id,
+    )
+    .await?;
+
+    Ok(ApiResponse::success(TransactionResponse::from(transaction)))
+}
+
+async fn publish_rate(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Json(req): Json<PublishRateRequest>,
+) -> Result<ApiResponse<FxRate>, ApiError> {
+    // Require admin access
+    require_kyc_level(3, &auth_user)?;
+
+    // Validate request
+    req.validate()?;
+
+    let rate = FxRate::create(
+        &pool,
+        &req.base_currency,
+        &req.quote_currency,
+        req.rate,
+        req.effective_at.unwrap_or_else(chrono::Utc::now),
+    )
+    .await?;
+
+    Ok(ApiResponse::success(rate))
+}
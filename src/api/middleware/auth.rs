@@ -1,37 +1,84 @@
-use crate::api::error::ApiError;
+use crate::{
+    api::error::ApiError,
+    models::user::User,
+    services::token_denylist::TokenDenylist,
+};
 use axum::{
     async_trait,
-    extract::{FromRequestParts, TypedHeader},
+    extract::{FromRef, FromRequestParts, TypedHeader},
     headers::{authorization::Bearer, Authorization},
     http::request::Parts,
     RequestPartsExt,
 };
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::error;
 use uuid::Uuid;
 
+const DEFAULT_ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: Uuid,        // User ID
     pub exp: usize,       // Expiration time
     pub iat: usize,       // Issued at
     pub kyc_level: i32,   // KYC level for authorization
+    pub sid: Uuid,        // Session the token was issued to
+    #[serde(default)]
+    pub email_verified: bool, // Whether money movement is allowed
+    #[serde(default)]
+    pub iat_micros: i64,  // Issue time precise enough to order against revocations
 }
 
 #[derive(Debug, Clone)]
 pub struct AuthUser {
     pub id: Uuid,
     pub kyc_level: i32,
+    pub session_id: Uuid,
+    pub email_verified: bool,
+}
+
+/// How long an access token is valid, from ACCESS_TOKEN_TTL_MINUTES. Kept
+/// short; clients renew with their refresh token.
+pub fn access_token_ttl() -> chrono::Duration {
+    let minutes = std::env::var("ACCESS_TOKEN_TTL_MINUTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_ACCESS_TOKEN_TTL_MINUTES);
+    chrono::Duration::minutes(minutes)
+}
+
+pub fn issue_access_token(user: &User, session_id: Uuid) -> Result<String, ApiError> {
+    let now = Utc::now();
+    let claims = Claims {
+        sub: user.id,
+        exp: (now + access_token_ttl()).timestamp() as usize,
+        iat: now.timestamp() as usize,
+        iat_micros: now.timestamp_micros(),
+        kyc_level: user.kyc_level,
+        sid: session_id,
+        email_verified: user.email_verified_at.is_some(),
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(std::env::var("JWT_SECRET").unwrap().as_bytes()),
+    )
+    .map_err(|e| ApiError::InternalError(e.into()))
 }
 
 #[async_trait]
 impl<S> FromRequestParts<S> for AuthUser
 where
     S: Send + Sync,
+    Arc<TokenDenylist>: FromRef<S>,
 {
     type Rejection = ApiError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         // Extract the token from the Authorization header
         let TypedHeader(Authorization(bearer)) = parts
             .extract::<TypedHeader<Authorization<Bearer>>>()
@@ -45,10 +92,27 @@ where
             &Validation::default(),
         )
         .map_err(|_| ApiError::AuthenticationError("Invalid token".to_string()))?;
+        let claims = token_data.claims;
+
+        // A valid signature is not enough once the session or user has been
+        // logged out. Fail closed if the denylist cannot be checked.
+        let denylist = Arc::<TokenDenylist>::from_ref(state);
+        let denied = denylist
+            .is_denied(claims.sub, claims.sid, claims.iat_micros)
+            .await
+            .map_err(|e| {
+                error!("Token denylist unavailable: {}", e);
+                ApiError::InternalError(e.into())
+            })?;
+        if denied {
+            return Err(ApiError::AuthenticationError("Token has been revoked".to_string()));
+        }
 
         Ok(AuthUser {
-            id: token_data.claims.sub,
-            kyc_level: token_data.claims.kyc_level,
+            id: claims.sub,
+            kyc_level: claims.kyc_level,
+            session_id: claims.sid,
+            email_verified: claims.email_verified,
         })
     }
 }
@@ -98,11 +162,21 @@ pub async fn rate_limit(
 // KYC level authorization middleware
 pub fn require_kyc_level(required_level: i32, user: &AuthUser) -> Result<(), ApiError> {
     if user.kyc_level < required_level {
-        Err(ApiError::AuthorizationError(format!(
-            "KYC level {} required. Current level: {}",
-            required_level, user.kyc_level
-        )))
+        Err(ApiError::KycLevelRequired {
+            required: required_level,
+            current: user.kyc_level,
+        })
     } else {
         Ok(())
     }
 }
+
+// Money can only move once the user has proved they own their email address.
+// Tokens issued before verification carry the old state until refreshed.
+pub fn require_verified_email(user: &AuthUser) -> Result<(), ApiError> {
+    if user.email_verified {
+        Ok(())
+    } else {
+        Err(ApiError::EmailNotVerified)
+    }
+}
@@ -0,0 +1,59 @@
+use crate::telemetry::{RequestContext, TraceContext};
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+pub const TRACEPARENT_HEADER: HeaderName = HeaderName::from_static("traceparent");
+
+// Tags each request with an ID, reusing the caller's `x-request-id` when it
+// is sane, and continues the caller's W3C trace. Both are made current for
+// the handler and returned on the response.
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let request_id = headers
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_request_id(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let trace = TraceContext::from_traceparent(
+        headers
+            .get(&TRACEPARENT_HEADER)
+            .and_then(|value| value.to_str().ok()),
+    );
+    let traceparent = trace.traceparent();
+
+    // Handlers that read headers directly see the ID that is in use
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        request.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    let context = RequestContext {
+        request_id: request_id.clone(),
+        trace,
+    };
+    let mut response = context.scope("http", next.run(request)).await;
+
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&traceparent) {
+        headers.insert(TRACEPARENT_HEADER, value);
+    }
+
+    response
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
@@ -4,3 +4,7 @@ pub mod transaction;
 pub mod middleware;
 pub mod error;
 pub mod response;
+pub mod fx;
+pub mod schedule;
+pub mod p2p;
+pub mod state;
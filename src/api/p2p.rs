@@ -0,0 +1,341 @@
+use crate::{
+    api::{
+        error::ApiError,
+        middleware::auth::{rate_limit, require_kyc_level, require_verified_email, AuthUser},
+        response::ApiResponse,
+    },
+    models::{
+        currency::{validate_amount, validate_currency_code, Money},
+        pending_transfer::PendingTransfer,
+        recipient::{mask_name, Recipient, RecipientType},
+        transaction::{Transaction, TransactionType},
+        user::User,
+        wallet::Wallet,
+    },
+    services::email::EmailService,
+};
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::error;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Recipient lookups allowed per user in each window
+const LOOKUP_LIMIT: u32 = 20;
+const LOOKUP_WINDOW_SECS: u32 = 3600;
+
+pub fn p2p_routes() -> Router {
+    Router::new()
+        .route("/p2p/lookup", post(lookup_recipient))
+        .route("/p2p/send", post(send_p2p))
+        .route("/p2p/pending", get(list_pending))
+        .route("/p2p/claims", get(list_claims))
+        .route("/p2p/claims/:id/claim", post(claim_transfer))
+}
+
+// Request/Response types
+#[derive(Debug, Deserialize, Validate)]
+pub struct LookupRecipientRequest {
+    #[validate(length(min = 3, max = 255))]
+    pub recipient: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LookupRecipientResponse {
+    pub recipient_type: RecipientType,
+    pub recipient: String,
+    pub registered: bool,
+    pub masked_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "validate_send_amount"))]
+pub struct SendP2pRequest {
+    pub debit_wallet_id: Uuid,
+    #[validate(length(min = 3, max = 255))]
+    pub recipient: String,
+    pub amount: Decimal,
+    #[validate(custom = "validate_currency_code")]
+    pub currency: String,
+    #[validate(length(max = 255))]
+    pub note: Option<String>,
+}
+
+fn validate_send_amount(req: &SendP2pRequest) -> Result<(), validator::ValidationError> {
+    validate_amount(req.amount, &req.currency)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum P2pTransferStatus {
+    /// Paid straight into the recipient's wallet
+    Completed,
+    /// Held until the recipient claims it
+    Pending,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SendP2pResponse {
+    pub status: P2pTransferStatus,
+    pub transaction_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    pub fee_amount: Decimal,
+    pub recipient: String,
+    pub masked_name: Option<String>,
+    pub pending_transfer_id: Option<Uuid>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClaimTransferRequest {
+    /// Wallet to pay into; defaults to the caller's wallet in the currency,
+    /// which is opened if they do not have one
+    pub wallet_id: Option<Uuid>,
+}
+
+// Handlers
+async fn lookup_recipient(
+    State(pool): State<PgPool>,
+    State(redis): State<redis::Client>,
+    auth_user: AuthUser,
+    Json(req): Json<LookupRecipientRequest>,
+) -> Result<ApiResponse<LookupRecipientResponse>, ApiError> {
+    require_kyc_level(1, &auth_user)?;
+
+    // The answer says whether an account exists, so it can't be used to
+    // sweep through phone numbers or emails
+    rate_limit(&redis, auth_user.id, LOOKUP_LIMIT, LOOKUP_WINDOW_SECS).await?;
+
+    // Validate request
+    req.validate()?;
+
+    let recipient = Recipient::parse(&req.recipient)
+        .map_err(|e| ApiError::validation(e.to_string()))?;
+    let user = find_recipient(&pool, &recipient).await?;
+
+    Ok(ApiResponse::success(LookupRecipientResponse {
+        recipient_type: recipient.recipient_type,
+        recipient: recipient.value,
+        registered: user.is_some(),
+        masked_name: user.map(|user| mask_name(&user.full_name)),
+    }))
+}
+
+async fn send_p2p(
+    State(pool): State<PgPool>,
+    State(email_service): State<Arc<EmailService>>,
+    auth_user: AuthUser,
+    Json(req): Json<SendP2pRequest>,
+) -> Result<ApiResponse<SendP2pResponse>, ApiError> {
+    require_kyc_level(1, &auth_user)?;
+    require_verified_email(&auth_user)?;
+
+    // Validate request
+    req.validate()?;
+
+    let recipient = Recipient::parse(&req.recipient)
+        .map_err(|e| ApiError::validation(e.to_string()))?;
+
+    // Verify ownership of the source wallet
+    let debit_wallet = Wallet::find(&pool, req.debit_wallet_id).await?;
+    if debit_wallet.user_id != auth_user.id {
+        return Err(ApiError::AuthorizationError(
+            "Not authorized to access this wallet".to_string(),
+        ));
+    }
+
+    if debit_wallet.currency != req.currency {
+        return Err(ApiError::validation(format!(
+            "Wallet holds {}, not {}",
+            debit_wallet.currency, req.currency
+        )));
+    }
+
+    let amount = Money::new(req.amount, &req.currency)
+        .map_err(|e| ApiError::validation(e.to_string()))?;
+
+    let recipient_user = find_recipient(&pool, &recipient).await?;
+    if let Some(user) = &recipient_user {
+        if user.id == auth_user.id {
+            return Err(ApiError::validation(
+                "Cannot send money to yourself".to_string(),
+            ));
+        }
+
+        // Registered recipients are paid directly when they hold the currency
+        if let Some(wallet) = Wallet::find_default(&pool, user.id, &amount.currency).await? {
+            let transaction = Transaction::create(
+                &pool,
+                Some(debit_wallet.id),
+                Some(wallet.id),
+                amount.amount,
+                amount.currency.clone(),
+                TransactionType::Transfer,
+                None,
+                Some(serde_json::json!({
+                    "p2p_recipient_type": recipient.recipient_type,
+                    "p2p_recipient": recipient.value,
+                    "note": req.note,
+                })),
+            )
+            .await?;
+
+            return Ok(ApiResponse::success(SendP2pResponse {
+                status: P2pTransferStatus::Completed,
+                transaction_id: transaction.id,
+                amount: transaction.amount,
+                currency: transaction.currency,
+                fee_amount: transaction.fee_amount,
+                recipient: recipient.value,
+                masked_name: Some(mask_name(&user.full_name)),
+                pending_transfer_id: None,
+                expires_at: None,
+            }));
+        }
+    }
+
+    // Everyone else gets a claimable transfer
+    let pending = PendingTransfer::send(
+        &pool,
+        auth_user.id,
+        debit_wallet.id,
+        &recipient,
+        &amount,
+        req.note.clone(),
+    )
+    .await?;
+    let escrow = Transaction::find(&pool, pending.transaction_id).await?;
+
+    if recipient_user.is_none() && recipient.recipient_type == RecipientType::Email {
+        invite_recipient(&pool, &email_service, auth_user.id, &pending).await;
+    }
+
+    Ok(ApiResponse::success(SendP2pResponse {
+        status: P2pTransferStatus::Pending,
+        transaction_id: escrow.id,
+        amount: escrow.amount,
+        currency: escrow.currency,
+        fee_amount: escrow.fee_amount,
+        recipient: recipient.value,
+        masked_name: recipient_user.map(|user| mask_name(&user.full_name)),
+        pending_transfer_id: Some(pending.id),
+        expires_at: Some(pending.expires_at),
+    }))
+}
+
+async fn list_pending(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+) -> Result<ApiResponse<Vec<PendingTransfer>>, ApiError> {
+    let pending = PendingTransfer::find_by_sender(&pool, auth_user.id).await?;
+
+    Ok(ApiResponse::success(pending))
+}
+
+async fn list_claims(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+) -> Result<ApiResponse<Vec<PendingTransfer>>, ApiError> {
+    let user = find_user(&pool, &auth_user).await?;
+    let claims = PendingTransfer::find_claimable(&pool, &user).await?;
+
+    Ok(ApiResponse::success(claims))
+}
+
+async fn claim_transfer(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(pending_id): Path<Uuid>,
+    Json(req): Json<ClaimTransferRequest>,
+) -> Result<ApiResponse<PendingTransfer>, ApiError> {
+    require_kyc_level(1, &auth_user)?;
+    require_verified_email(&auth_user)?;
+
+    let user = find_user(&pool, &auth_user).await?;
+    let pending = PendingTransfer::find_by_id(&pool, pending_id)
+        .await?
+        .filter(|pending| pending.is_for(&user))
+        .ok_or_else(|| ApiError::NotFoundError("Pending transfer not found".to_string()))?;
+
+    let wallet = match req.wallet_id {
+        Some(wallet_id) => {
+            let wallet = Wallet::find(&pool, wallet_id).await?;
+            if wallet.user_id != auth_user.id {
+                return Err(ApiError::AuthorizationError(
+                    "Not authorized to access this wallet".to_string(),
+                ));
+            }
+            wallet
+        }
+        None => match Wallet::find_default(&pool, user.id, &pending.currency).await? {
+            Some(wallet) => wallet,
+            None => Wallet::create(&pool, user.id, &pending.currency, None).await?,
+        },
+    };
+
+    if wallet.currency != pending.currency {
+        return Err(ApiError::validation(format!(
+            "Wallet holds {}, not {}",
+            wallet.currency, pending.currency
+        )));
+    }
+
+    let (pending, _payout) = PendingTransfer::claim(&pool, pending.id, &user, wallet.id).await?;
+
+    Ok(ApiResponse::success(pending))
+}
+
+/// Finds the registered user a recipient refers to. Phone numbers only
+/// match once their owner has verified them.
+async fn find_recipient(pool: &PgPool, recipient: &Recipient) -> Result<Option<User>, ApiError> {
+    let user = match recipient.recipient_type {
+        RecipientType::Email => User::find_by_email(pool, &recipient.value).await?,
+        RecipientType::Phone => User::find_by_phone(pool, &recipient.value).await?,
+    };
+
+    Ok(user)
+}
+
+async fn find_user(pool: &PgPool, auth_user: &AuthUser) -> Result<User, ApiError> {
+    User::find_by_id(pool, auth_user.id)
+        .await?
+        .ok_or_else(|| ApiError::NotFoundError("User not found".to_string()))
+}
+
+/// Emails an unregistered recipient that money is waiting for them
+async fn invite_recipient(
+    pool: &PgPool,
+    email_service: &EmailService,
+    sender_id: Uuid,
+    pending: &PendingTransfer,
+) {
+    let sender_name = match User::find_by_id(pool, sender_id).await {
+        Ok(Some(sender)) => sender.full_name,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Error loading sender of pending transfer {}: {}", pending.id, e);
+            return;
+        }
+    };
+
+    if let Err(e) = email_service
+        .send_p2p_claim_invite(
+            &pending.recipient,
+            &sender_name,
+            &pending.money().to_string(),
+            pending.note.as_deref(),
+            &pending.expires_at.format("%Y-%m-%d %H:%M UTC").to_string(),
+        )
+        .await
+    {
+        error!("Error inviting recipient of pending transfer {}: {}", pending.id, e);
+    }
+}
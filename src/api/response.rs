@@ -1,5 +1,9 @@
+use crate::{
+    api::error::ApiError,
+    models::pagination::{Page, PageMode, PageRequest, DEFAULT_PAGE_SIZE},
+};
 use axum::{http::StatusCode, response::IntoResponse, Json};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 #[derive(Serialize)]
@@ -62,6 +66,30 @@ where
     }
 }
 
+// Pagination query parameters, shared by every listing
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+    /// Page number; switches the listing to page mode
+    pub page: Option<u32>,
+    #[serde(alias = "limit")]
+    pub per_page: Option<u32>,
+}
+
+impl PageQuery {
+    pub fn to_request(&self) -> Result<PageRequest, ApiError> {
+        self.to_request_or(DEFAULT_PAGE_SIZE)
+    }
+
+    /// As `to_request`, for listings with their own default page size
+    pub fn to_request_or(&self, default_per_page: u32) -> Result<PageRequest, ApiError> {
+        let per_page = self.per_page.unwrap_or(default_per_page);
+        PageRequest::new(self.cursor.as_deref(), self.page, Some(per_page))
+            .map_err(|e| ApiError::validation(e.to_string()))
+    }
+}
+
 // Pagination response wrapper
 #[derive(Serialize)]
 pub struct PaginatedResponse<T>
@@ -69,23 +97,39 @@ where
     T: Serialize,
 {
     pub items: Vec<T>,
-    pub total: u64,
-    pub page: u32,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
     pub per_page: u32,
-    pub total_pages: u32,
+    // Page mode only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_pages: Option<u32>,
 }
 
 impl<T> PaginatedResponse<T>
 where
     T: Serialize,
 {
-    pub fn new(items: Vec<T>, total: u64, page: u32, per_page: u32) -> Self {
-        let total_pages = ((total as f64) / (per_page as f64)).ceil() as u32;
+    pub fn new(page: Page<T>, request: &PageRequest) -> Self {
+        let per_page = request.limit;
+        let total = page.total.map(|total| total.max(0) as u64);
+        let total_pages =
+            total.map(|total| ((total as f64) / (per_page as f64)).ceil() as u32);
+        let page_number = match request.mode {
+            PageMode::Number(page) => Some(page),
+            PageMode::Cursor(_) => None,
+        };
+
         Self {
-            items,
-            total,
-            page,
+            items: page.items,
+            next_cursor: page.next_cursor,
+            has_more: page.has_more,
             per_page,
+            total,
+            page: page_number,
             total_pages,
         }
     }
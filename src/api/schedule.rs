@@ -0,0 +1,195 @@
+use crate::{
+    api::{
+        error::ApiError,
+        middleware::auth::{require_kyc_level, require_verified_email, AuthUser},
+        response::ApiResponse,
+    },
+    models::{
+        currency::{validate_amount, validate_currency_code, Money},
+        schedule::{ScheduleFrequency, ScheduleSpec, ScheduledTransfer, ScheduledTransferRun},
+        wallet::Wallet,
+    },
+};
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+pub fn schedule_routes() -> Router {
+    Router::new()
+        .route("/schedules", get(list_schedules).post(create_schedule))
+        .route("/schedules/:id", get(get_schedule))
+        .route("/schedules/:id/runs", get(list_runs))
+        .route("/schedules/:id/pause", post(pause_schedule))
+        .route("/schedules/:id/resume", post(resume_schedule))
+        .route("/schedules/:id/cancel", post(cancel_schedule))
+}
+
+// Request/Response types
+#[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "validate_schedule_amount"))]
+pub struct CreateScheduleRequest {
+    pub debit_wallet_id: Uuid,
+    pub credit_wallet_id: Uuid,
+    pub amount: Decimal,
+    #[validate(custom = "validate_currency_code")]
+    pub currency: String,
+    pub frequency: ScheduleFrequency,
+    #[validate(range(min = 1, max = 366))]
+    pub interval_count: Option<i32>,
+    #[validate(length(min = 1, max = 255))]
+    pub cron_expression: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    #[validate(length(max = 255))]
+    pub description: Option<String>,
+}
+
+fn validate_schedule_amount(req: &CreateScheduleRequest) -> Result<(), validator::ValidationError> {
+    validate_amount(req.amount, &req.currency)
+}
+
+// Handlers
+async fn create_schedule(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Json(req): Json<CreateScheduleRequest>,
+) -> Result<ApiResponse<ScheduledTransfer>, ApiError> {
+    require_kyc_level(1, &auth_user)?;
+    require_verified_email(&auth_user)?;
+
+    // Validate request
+    req.validate()?;
+
+    // Verify ownership of the source wallet
+    let debit_wallet = Wallet::find(&pool, req.debit_wallet_id).await?;
+    if debit_wallet.user_id != auth_user.id {
+        return Err(ApiError::AuthorizationError(
+            "Not authorized to access this wallet".to_string(),
+        ));
+    }
+
+    if debit_wallet.currency != req.currency {
+        return Err(ApiError::validation(format!(
+            "Wallet holds {}, not {}",
+            debit_wallet.currency, req.currency
+        )));
+    }
+
+    // The destination may belong to anyone but must exist
+    Wallet::find(&pool, req.credit_wallet_id).await?;
+
+    let amount = Money::new(req.amount, &req.currency)
+        .map_err(|e| ApiError::validation(e.to_string()))?;
+
+    let spec = ScheduleSpec {
+        frequency: req.frequency,
+        interval_count: req.interval_count.unwrap_or(1),
+        cron_expression: req.cron_expression,
+        starts_at: req.starts_at.unwrap_or_else(Utc::now),
+        ends_at: req.ends_at,
+    };
+
+    let schedule = ScheduledTransfer::create(
+        &pool,
+        auth_user.id,
+        req.debit_wallet_id,
+        req.credit_wallet_id,
+        &amount,
+        spec,
+        req.description,
+    )
+    .await?;
+
+    Ok(ApiResponse::success(schedule))
+}
+
+async fn list_schedules(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+) -> Result<ApiResponse<Vec<ScheduledTransfer>>, ApiError> {
+    let schedules = ScheduledTransfer::find_by_user(&pool, auth_user.id).await?;
+
+    Ok(ApiResponse::success(schedules))
+}
+
+async fn get_schedule(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<ApiResponse<ScheduledTransfer>, ApiError> {
+    let schedule = find_owned_schedule(&pool, &auth_user, schedule_id).await?;
+
+    Ok(ApiResponse::success(schedule))
+}
+
+async fn list_runs(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<ApiResponse<Vec<ScheduledTransferRun>>, ApiError> {
+    let schedule = find_owned_schedule(&pool, &auth_user, schedule_id).await?;
+    let runs = schedule.runs(&pool).await?;
+
+    Ok(ApiResponse::success(runs))
+}
+
+async fn pause_schedule(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<ApiResponse<ScheduledTransfer>, ApiError> {
+    let mut schedule = find_owned_schedule(&pool, &auth_user, schedule_id).await?;
+    schedule.pause(&pool).await?;
+
+    Ok(ApiResponse::success(schedule))
+}
+
+async fn resume_schedule(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<ApiResponse<ScheduledTransfer>, ApiError> {
+    let mut schedule = find_owned_schedule(&pool, &auth_user, schedule_id).await?;
+    schedule.resume(&pool).await?;
+
+    Ok(ApiResponse::success(schedule))
+}
+
+async fn cancel_schedule(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<ApiResponse<ScheduledTransfer>, ApiError> {
+    let mut schedule = find_owned_schedule(&pool, &auth_user, schedule_id).await?;
+    schedule.cancel(&pool).await?;
+
+    Ok(ApiResponse::success(schedule))
+}
+
+/// Loads a schedule the caller owns
+async fn find_owned_schedule(
+    pool: &PgPool,
+    auth_user: &AuthUser,
+    schedule_id: Uuid,
+) -> Result<ScheduledTransfer, ApiError> {
+    let schedule = ScheduledTransfer::find_by_id(pool, schedule_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFoundError("Scheduled transfer not found".to_string()))?;
+
+    // Verify ownership
+    if schedule.user_id != auth_user.id {
+        return Err(ApiError::AuthorizationError(
+            "Not authorized to access this scheduled transfer".to_string(),
+        ));
+    }
+
+    Ok(schedule)
+}
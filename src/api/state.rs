@@ -0,0 +1,46 @@
+use crate::services::{
+    email::EmailService, metadata_schema::MetadataSchemaRegistry, token_denylist::TokenDenylist,
+};
+use axum::extract::FromRef;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Everything handlers extract with `State`, built once at startup
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: PgPool,
+    pub redis: redis::Client,
+    pub email_service: Arc<EmailService>,
+    pub token_denylist: Arc<TokenDenylist>,
+    pub metadata_schemas: Arc<MetadataSchemaRegistry>,
+}
+
+impl FromRef<AppState> for PgPool {
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
+
+impl FromRef<AppState> for redis::Client {
+    fn from_ref(state: &AppState) -> Self {
+        state.redis.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<EmailService> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.email_service)
+    }
+}
+
+impl FromRef<AppState> for Arc<TokenDenylist> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.token_denylist)
+    }
+}
+
+impl FromRef<AppState> for Arc<MetadataSchemaRegistry> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.metadata_schemas)
+    }
+}
@@ -1,37 +1,48 @@
 use crate::{
     api::{
-        error::ApiError,
-        middleware::auth::{require_kyc_level, AuthUser},
-        response::{ApiResponse, PaginatedResponse},
+        error::{ApiError, FieldError},
+        middleware::auth::{require_kyc_level, require_verified_email, AuthUser},
+        response::{ApiResponse, PageQuery, PaginatedResponse},
     },
     models::{
+        currency::{validate_amount, validate_currency_code, Money},
+        fee::FeeQuote,
+        idempotency::{IdempotencyClaim, IdempotencyKey},
         transaction::{Transaction, TransactionStatus, TransactionType},
         wallet::Wallet,
     },
+    services::metadata_schema::MetadataSchemaRegistry,
 };
 use axum::{
     extract::{Path, Query, State},
+    http::HeaderMap,
     routing::{get, post},
     Json, Router,
 };
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
 use uuid::Uuid;
 use validator::Validate;
 
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
 pub fn transaction_routes() -> Router {
     Router::new()
         .route("/transactions", get(list_transactions).post(create_transaction))
+        .route("/transactions/quote", post(quote_transaction))
         .route("/transactions/:id", get(get_transaction))
+        .route("/transactions/:id/reversals", get(get_reversal_chain))
 }
 
 // Request/Response types
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "validate_create_amount"))]
 pub struct CreateTransactionRequest {
     pub transaction_type: TransactionType,
-    #[validate(range(min = 0.01))]
     pub amount: Decimal,
+    #[validate(custom = "validate_currency_code")]
     pub currency: String,
     pub debit_wallet_id: Option<Uuid>,
     pub credit_wallet_id: Option<Uuid>,
@@ -39,10 +50,25 @@ pub struct CreateTransactionRequest {
     pub metadata: Option<serde_json::Value>,
 }
 
+#[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "validate_quote_amount"))]
+pub struct QuoteTransactionRequest {
+    pub transaction_type: TransactionType,
+    pub amount: Decimal,
+    #[validate(custom = "validate_currency_code")]
+    pub currency: String,
+}
+
+fn validate_create_amount(req: &CreateTransactionRequest) -> Result<(), validator::ValidationError> {
+    validate_amount(req.amount, &req.currency)
+}
+
+fn validate_quote_amount(req: &QuoteTransactionRequest) -> Result<(), validator::ValidationError> {
+    validate_amount(req.amount, &req.currency)
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ListTransactionsQuery {
-    pub page: Option<u32>,
-    pub per_page: Option<u32>,
     pub wallet_id: Option<Uuid>,
     pub transaction_type: Option<TransactionType>,
     pub status: Option<TransactionStatus>,
@@ -50,7 +76,7 @@ pub struct ListTransactionsQuery {
     pub end_date: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TransactionResponse {
     pub id: Uuid,
     pub transaction_type: TransactionType,
@@ -61,6 +87,9 @@ pub struct TransactionResponse {
     pub credit_wallet_id: Option<Uuid>,
     pub reference_id: Option<String>,
     pub metadata: Option<serde_json::Value>,
+    pub fee_amount: Decimal,
+    pub fee_breakdown: Option<serde_json::Value>,
+    pub parent_transaction_id: Option<Uuid>,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -76,6 +105,9 @@ impl From<Transaction> for TransactionResponse {
             credit_wallet_id: tx.credit_wallet_id,
             reference_id: tx.reference_id,
             metadata: tx.metadata,
+            fee_amount: tx.fee_amount,
+            fee_breakdown: tx.fee_breakdown,
+            parent_transaction_id: tx.parent_transaction_id,
             created_at: tx.created_at,
         }
     }
@@ -84,40 +116,112 @@ impl From<Transaction> for TransactionResponse {
 // Handlers
 async fn create_transaction(
     State(pool): State<PgPool>,
+    State(metadata_schemas): State<Arc<MetadataSchemaRegistry>>,
     auth_user: AuthUser,
+    headers: HeaderMap,
     Json(req): Json<CreateTransactionRequest>,
 ) -> Result<ApiResponse<TransactionResponse>, ApiError> {
-    // Validate KYC level based on transaction type and amount
-    let required_kyc_level = match req.transaction_type {
-        TransactionType::Transfer if req.amount > Decimal::new(1000, 0) => 2,
-        TransactionType::Withdrawal if req.amount > Decimal::new(500, 0) => 2,
-        _ => 1,
-    };
-    require_kyc_level(required_kyc_level, &auth_user)?;
+    // Amount caps for the user's KYC tier are enforced when the transaction
+    // is created
+    require_kyc_level(1, &auth_user)?;
+    require_verified_email(&auth_user)?;
 
     // Validate request
-    req.validate()
-        .map_err(|e| ApiError::ValidationError(e.to_string()))?;
+    req.validate()?;
+
+    // Metadata must match the active schema for the transaction type
+    metadata_schemas
+        .validate(&req.transaction_type, req.metadata.as_ref())
+        .map_err(|violations| {
+            ApiError::invalid_fields(
+                violations
+                    .into_iter()
+                    .map(|violation| FieldError {
+                        field: Some(violation.field),
+                        code: violation.code.to_string(),
+                        message: Some(violation.message),
+                        params: Default::default(),
+                    })
+                    .collect(),
+            )
+        })?;
+
+    // Replay or reject retried requests carrying an idempotency key
+    let idempotency_key = idempotency_key(&headers)?;
+    let mut claim = None;
+    if let Some(key) = &idempotency_key {
+        let fingerprint = IdempotencyKey::fingerprint(&req)
+            .map_err(|e| ApiError::InternalError(e.into()))?;
+
+        match IdempotencyKey::claim(&pool, auth_user.id, key, &fingerprint).await? {
+            IdempotencyClaim::Claimed(claim_id) => claim = Some((key.as_str(), claim_id)),
+            IdempotencyClaim::Replay(response) => {
+                let response = serde_json::from_value(response)
+                    .map_err(|e| ApiError::InternalError(e.into()))?;
+                return Ok(ApiResponse::success(response));
+            }
+            IdempotencyClaim::InProgress => {
+                return Err(ApiError::ConflictError(
+                    "A request with this idempotency key is still being processed".to_string(),
+                ));
+            }
+            IdempotencyClaim::Mismatch => {
+                return Err(ApiError::ConflictError(
+                    "Idempotency key was already used with a different request".to_string(),
+                ));
+            }
+        }
+    }
+
+    let result = process_transaction(&pool, &auth_user, req, claim).await;
+
+    // Nothing was committed, so the client may retry with the same key
+    if let (Err(_), Some((key, claim_id))) = (&result, claim) {
+        IdempotencyKey::release(&pool, auth_user.id, key, claim_id).await?;
+    }
 
+    Ok(ApiResponse::success(result?))
+}
+
+/// Reads the optional Idempotency-Key header
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>, ApiError> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+
+    let key = value
+        .to_str()
+        .map_err(|_| ApiError::validation("Invalid Idempotency-Key header".to_string()))?
+        .trim();
+
+    if key.is_empty() || key.len() > 255 {
+        return Err(ApiError::validation(
+            "Idempotency-Key must be between 1 and 255 characters".to_string(),
+        ));
+    }
+
+    Ok(Some(key.to_string()))
+}
+
+/// Creates the transaction and, for a request holding an idempotency key
+/// claim, records its response in the same database transaction
+async fn process_transaction(
+    pool: &PgPool,
+    auth_user: &AuthUser,
+    req: CreateTransactionRequest,
+    claim: Option<(&str, Uuid)>,
+) -> Result<TransactionResponse, ApiError> {
     // Verify wallet ownership and get wallets
     let (debit_wallet, credit_wallet) = match req.transaction_type {
         TransactionType::Transfer => {
             let debit_wallet = match req.debit_wallet_id {
-                Some(id) => {
-                    let wallet = Wallet::find(&pool, id).await?;
-                    if wallet.user_id != auth_user.id {
-                        return Err(ApiError::AuthorizationError(
-                            "Not authorized to access debit wallet".to_string(),
-                        ));
-                    }
-                    Some(wallet)
-                }
+                Some(id) => Some(find_owned_wallet(pool, auth_user, id, "debit").await?),
                 None => None,
             };
 
             let credit_wallet = match req.credit_wallet_id {
                 Some(id) => {
-                    let wallet = Wallet::find(&pool, id).await?;
+                    let wallet = Wallet::find(pool, id).await?;
                     Some(wallet)
                 }
                 None => None,
@@ -125,35 +229,106 @@ async fn create_transaction(
 
             (debit_wallet, credit_wallet)
         }
-        TransactionType::Deposit => (None, Some(Wallet::find(&pool, req.credit_wallet_id.unwrap()).await?)),
-        TransactionType::Withdrawal => (Some(Wallet::find(&pool, req.debit_wallet_id.unwrap()).await?), None),
+        TransactionType::Deposit => {
+            // Deposits without a wallet go to the default one in the currency
+            let credit_wallet = match req.credit_wallet_id {
+                Some(id) => find_owned_wallet(pool, auth_user, id, "credit").await?,
+                None => Wallet::find_default(pool, auth_user.id, &req.currency)
+                    .await?
+                    .ok_or_else(|| {
+                        ApiError::validation(format!("No default {} wallet", req.currency))
+                    })?,
+            };
+
+            (None, Some(credit_wallet))
+        }
+        TransactionType::Withdrawal => {
+            let id = req
+                .debit_wallet_id
+                .ok_or_else(|| ApiError::validation("debit_wallet_id is required"))?;
+
+            (Some(find_owned_wallet(pool, auth_user, id, "debit").await?), None)
+        }
         _ => (None, None),
     };
 
+    let money = Money::new(req.amount, &req.currency)?;
+    let mut db_tx = pool.begin().await?;
+
     // Create transaction
-    let transaction = Transaction::create(
-        &pool,
+    let transaction = Transaction::create_in(
+        &mut db_tx,
+        debit_wallet.map(|wallet| wallet.id),
+        credit_wallet.map(|wallet| wallet.id),
+        &money,
         req.transaction_type,
-        req.amount,
-        &req.currency,
-        debit_wallet.as_ref(),
-        credit_wallet.as_ref(),
         req.reference_id,
         req.metadata,
+        None,
     )
     .await?;
+    let response = TransactionResponse::from(transaction);
 
-    Ok(ApiResponse::success(TransactionResponse::from(transaction)))
+    if let Some((key, claim_id)) = claim {
+        let body = serde_json::to_value(&response).map_err(|e| ApiError::InternalError(e.into()))?;
+        if !IdempotencyKey::complete_in(&mut db_tx, auth_user.id, key, claim_id, &body).await? {
+            // A retry took the key over; dropping db_tx rolls this attempt back
+            return Err(ApiError::ConflictError(
+                "Idempotency key lease expired before the request finished; retry it".to_string(),
+            ));
+        }
+    }
+
+    db_tx.commit().await?;
+
+    Ok(response)
+}
+
+/// Loads a wallet the caller owns; `role` names it in the error, e.g. "debit"
+async fn find_owned_wallet(
+    pool: &PgPool,
+    auth_user: &AuthUser,
+    wallet_id: Uuid,
+    role: &str,
+) -> Result<Wallet, ApiError> {
+    let wallet = Wallet::find(pool, wallet_id).await?;
+    if wallet.user_id != auth_user.id {
+        return Err(ApiError::AuthorizationError(format!(
+            "Not authorized to access {} wallet",
+            role
+        )));
+    }
+
+    Ok(wallet)
+}
+
+async fn quote_transaction(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Json(req): Json<QuoteTransactionRequest>,
+) -> Result<ApiResponse<FeeQuote>, ApiError> {
+    // Validate request
+    req.validate()?;
+
+    let quote = FeeQuote::calculate(
+        &pool,
+        req.transaction_type,
+        &req.currency,
+        auth_user.kyc_level,
+        req.amount,
+    )
+    .await?;
+
+    Ok(ApiResponse::success(quote))
 }
 
 async fn list_transactions(
     State(pool): State<PgPool>,
     auth_user: AuthUser,
     Query(query): Query<ListTransactionsQuery>,
+    Query(page_query): Query<PageQuery>,
 ) -> Result<ApiResponse<PaginatedResponse<TransactionResponse>>, ApiError> {
-    let page = query.page.unwrap_or(1);
-    let per_page = query.per_page.unwrap_or(10).min(100);
-    let offset = ((page - 1) * per_page) as i64;
+    let page = page_query.to_request()?;
 
     // If wallet_id is specified, verify ownership
     if let Some(wallet_id) = query.wallet_id {
@@ -165,7 +340,7 @@ async fn list_transactions(
         }
     }
 
-    let (transactions, total) = Transaction::list(
+    let transactions = Transaction::list(
         &pool,
         auth_user.id,
         query.wallet_id,
@@ -173,17 +348,11 @@ async fn list_transactions(
         query.status,
         query.start_date,
         query.end_date,
-        per_page as i64,
-        offset,
+        &page,
     )
     .await?;
 
-    let response = PaginatedResponse::new(
-        transactions.into_iter().map(TransactionResponse::from).collect(),
-        total as u64,
-        page,
-        per_page,
-    );
+    let response = PaginatedResponse::new(transactions.map(TransactionResponse::from), &page);
 
     Ok(ApiResponse::success(response))
 }
@@ -207,3 +376,27 @@ async fn get_transaction(
 
     Ok(ApiResponse::success(TransactionResponse::from(transaction)))
 }
+
+async fn get_reversal_chain(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(transaction_id): Path<Uuid>,
+) -> Result<ApiResponse<Vec<TransactionResponse>>, ApiError> {
+    let transaction = Transaction::find(&pool, transaction_id).await?;
+
+    // Verify ownership of either debit or credit wallet
+    if let Some(wallet_id) = transaction.debit_wallet_id.or(transaction.credit_wallet_id) {
+        let wallet = Wallet::find(&pool, wallet_id).await?;
+        if wallet.user_id != auth_user.id {
+            return Err(ApiError::AuthorizationError(
+                "Not authorized to access this transaction".to_string(),
+            ));
+        }
+    }
+
+    let chain = transaction.reversal_chain(&pool).await?;
+
+    Ok(ApiResponse::success(
+        chain.into_iter().map(TransactionResponse::from).collect(),
+    ))
+}
@@ -1,16 +1,27 @@
 use crate::{
     api::{
         error::ApiError,
-        middleware::auth::{require_kyc_level, AuthUser},
-        response::{ApiResponse, PaginatedResponse},
+        middleware::auth::{require_kyc_level, require_verified_email, AuthUser},
+        response::{ApiResponse, PageQuery, PaginatedResponse},
+    },
+    models::{
+        currency::validate_currency_code,
+        hold::{HoldError, WalletHold},
+        limit::{KycLimit, LimitStatus},
+        statement::{Statement, StatementError},
+        user::User,
+        wallet::{Wallet, WalletStatus, WalletStatusReason},
     },
-    models::wallet::{Wallet, WalletStatus},
 };
+use crate::services::statement::{render_csv, render_pdf};
 use axum::{
     extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
+use chrono::{Duration, NaiveDate, NaiveTime};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
@@ -20,31 +31,86 @@ use validator::Validate;
 pub fn wallet_routes() -> Router {
     Router::new()
         .route("/wallets", get(list_wallets).post(create_wallet))
-        .route("/wallets/:id", get(get_wallet))
+        .route("/wallets/:id", get(get_wallet).patch(rename_wallet))
+        .route("/wallets/:id/default", post(set_default_wallet))
+        .route("/wallets/:id/close", post(close_wallet))
         .route("/wallets/:id/balance", get(get_balance))
+        .route("/wallets/:id/limits", get(get_limits))
+        .route("/wallets/:id/statement", get(get_statement))
+        .route("/wallets/:id/holds", get(list_holds).post(place_hold))
+        .route("/wallets/:id/holds/:hold_id/capture", post(capture_hold))
+        .route("/wallets/:id/holds/:hold_id/release", post(release_hold))
 }
 
 // Request/Response types
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateWalletRequest {
-    #[validate(length(min = 1))]
+    #[validate(custom = "validate_currency_code")]
     pub currency: String,
+    #[validate(length(min = 1, max = 50))]
+    pub nickname: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RenameWalletRequest {
+    /// The new nickname, or null to clear it
+    #[validate(length(min = 1, max = 50))]
+    pub nickname: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ListWalletsQuery {
-    pub page: Option<u32>,
-    pub per_page: Option<u32>,
     pub currency: Option<String>,
     pub status: Option<WalletStatus>,
 }
 
+#[derive(Debug, Deserialize, Validate)]
+pub struct PlaceHoldRequest {
+    #[validate(range(min = 0.01))]
+    pub amount: Decimal,
+    pub reference_id: Option<String>,
+    #[validate(range(min = 60, max = 2592000))]
+    pub expires_in_secs: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CaptureHoldRequest {
+    pub amount: Option<Decimal>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatementQuery {
+    pub from: NaiveDate,
+    /// Last day of the statement, inclusive
+    pub to: NaiveDate,
+    #[serde(default)]
+    pub format: StatementFormat,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementFormat {
+    Csv,
+    #[default]
+    Pdf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HoldPathParams {
+    pub id: Uuid,
+    pub hold_id: Uuid,
+}
+
 #[derive(Debug, Serialize)]
 pub struct WalletResponse {
     pub id: Uuid,
     pub currency: String,
     pub balance: Decimal,
+    pub available_balance: Decimal,
+    pub held_balance: Decimal,
     pub status: WalletStatus,
+    pub nickname: Option<String>,
+    pub is_default: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -54,7 +120,11 @@ impl From<Wallet> for WalletResponse {
             id: wallet.id,
             currency: wallet.currency,
             balance: wallet.balance,
+            available_balance: wallet.available_balance,
+            held_balance: wallet.held_balance,
             status: wallet.status,
+            nickname: wallet.nickname,
+            is_default: wallet.is_default,
             created_at: wallet.created_at,
         }
     }
@@ -70,11 +140,11 @@ async fn create_wallet(
     require_kyc_level(1, &auth_user)?;
 
     // Validate request
-    req.validate()
-        .map_err(|e| ApiError::ValidationError(e.to_string()))?;
+    req.validate()?;
 
     // Create wallet
-    let wallet = Wallet::create(&pool, auth_user.id, &req.currency).await?;
+    let wallet = Wallet::create(&pool, auth_user.id, &req.currency, clean_nickname(req.nickname))
+        .await?;
 
     Ok(ApiResponse::success(WalletResponse::from(wallet)))
 }
@@ -83,27 +153,20 @@ async fn list_wallets(
     State(pool): State<PgPool>,
     auth_user: AuthUser,
     Query(query): Query<ListWalletsQuery>,
+    Query(page_query): Query<PageQuery>,
 ) -> Result<ApiResponse<PaginatedResponse<WalletResponse>>, ApiError> {
-    let page = query.page.unwrap_or(1);
-    let per_page = query.per_page.unwrap_or(10).min(100);
-    let offset = ((page - 1) * per_page) as i64;
+    let page = page_query.to_request()?;
 
-    let (wallets, total) = Wallet::list(
+    let wallets = Wallet::list(
         &pool,
         auth_user.id,
         query.currency.as_deref(),
         query.status,
-        per_page as i64,
-        offset,
+        &page,
     )
     .await?;
 
-    let response = PaginatedResponse::new(
-        wallets.into_iter().map(WalletResponse::from).collect(),
-        total as u64,
-        page,
-        per_page,
-    );
+    let response = PaginatedResponse::new(wallets.map(WalletResponse::from), &page);
 
     Ok(ApiResponse::success(response))
 }
@@ -130,6 +193,140 @@ async fn get_balance(
     auth_user: AuthUser,
     Path(wallet_id): Path<Uuid>,
 ) -> Result<ApiResponse<Decimal>, ApiError> {
+    let wallet = find_owned_wallet(&pool, &auth_user, wallet_id).await?;
+
+    Ok(ApiResponse::success(wallet.balance))
+}
+
+async fn get_limits(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(wallet_id): Path<Uuid>,
+) -> Result<ApiResponse<LimitStatus>, ApiError> {
+    let wallet = find_owned_wallet(&pool, &auth_user, wallet_id).await?;
+
+    // Limits follow the stored KYC level, which may be newer than the token's
+    let user = User::find_by_id(&pool, auth_user.id)
+        .await?
+        .ok_or_else(|| ApiError::NotFoundError("User not found".to_string()))?;
+
+    let status = KycLimit::status(&pool, user.id, user.kyc_level, &wallet.currency).await?;
+
+    Ok(ApiResponse::success(status))
+}
+
+async fn get_statement(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(wallet_id): Path<Uuid>,
+    Query(query): Query<StatementQuery>,
+) -> Result<Response, ApiError> {
+    let wallet = find_owned_wallet(&pool, &auth_user, wallet_id).await?;
+    let user = User::find_by_id(&pool, auth_user.id)
+        .await?
+        .ok_or_else(|| ApiError::NotFoundError("User not found".to_string()))?;
+
+    // Whole UTC days, with the end day included
+    let period_start = query.from.and_time(NaiveTime::MIN).and_utc();
+    let period_end = query
+        .to
+        .and_time(NaiveTime::MIN)
+        .and_utc()
+        .checked_add_signed(Duration::days(1))
+        .ok_or_else(|| ApiError::validation("Statement end date is out of range".to_string()))?;
+
+    let statement = Statement::generate(&pool, &wallet, &user.full_name, period_start, period_end)
+        .await
+        .map_err(statement_error)?;
+
+    let (content_type, extension, body) = match query.format {
+        StatementFormat::Csv => ("text/csv; charset=utf-8", "csv", render_csv(&statement)),
+        StatementFormat::Pdf => ("application/pdf", "pdf", render_pdf(&statement)),
+    };
+    let body = body.map_err(statement_error)?;
+    let filename = format!(
+        "statement-{}-{}-{}.{}",
+        wallet.currency.to_lowercase(),
+        query.from,
+        query.to,
+        extension
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+async fn rename_wallet(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(wallet_id): Path<Uuid>,
+    Json(req): Json<RenameWalletRequest>,
+) -> Result<ApiResponse<WalletResponse>, ApiError> {
+    // Validate request
+    req.validate()?;
+
+    let mut wallet = find_owned_wallet(&pool, &auth_user, wallet_id).await?;
+    wallet.rename(&pool, clean_nickname(req.nickname)).await?;
+
+    Ok(ApiResponse::success(WalletResponse::from(wallet)))
+}
+
+async fn set_default_wallet(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(wallet_id): Path<Uuid>,
+) -> Result<ApiResponse<WalletResponse>, ApiError> {
+    let mut wallet = find_owned_wallet(&pool, &auth_user, wallet_id).await?;
+    wallet.set_default(&pool).await?;
+
+    Ok(ApiResponse::success(WalletResponse::from(wallet)))
+}
+
+async fn close_wallet(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(wallet_id): Path<Uuid>,
+) -> Result<ApiResponse<WalletResponse>, ApiError> {
+    let mut wallet = find_owned_wallet(&pool, &auth_user, wallet_id).await?;
+    wallet
+        .close(&pool, WalletStatusReason::CustomerRequest, None)
+        .await?;
+
+    Ok(ApiResponse::success(WalletResponse::from(wallet)))
+}
+
+async fn list_holds(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(wallet_id): Path<Uuid>,
+) -> Result<ApiResponse<Vec<WalletHold>>, ApiError> {
+    find_owned_wallet(&pool, &auth_user, wallet_id).await?;
+
+    let holds = WalletHold::find_by_wallet(&pool, wallet_id).await?;
+
+    Ok(ApiResponse::success(holds))
+}
+
+async fn place_hold(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(wallet_id): Path<Uuid>,
+    Json(req): Json<PlaceHoldRequest>,
+) -> Result<ApiResponse<WalletHold>, ApiError> {
+    require_verified_email(&auth_user)?;
+
+    // Validate request
+    req.validate()?;
+
     let wallet = Wallet::find(&pool, wallet_id).await?;
 
     // Verify ownership
@@ -139,5 +336,101 @@ async fn get_balance(
         ));
     }
 
-    Ok(ApiResponse::success(wallet.balance))
+    let expires_at = req
+        .expires_in_secs
+        .map(|secs| chrono::Utc::now() + chrono::Duration::seconds(secs));
+
+    let hold = WalletHold::authorize(&pool, wallet_id, req.amount, req.reference_id, expires_at).await?;
+
+    Ok(ApiResponse::success(hold))
+}
+
+async fn capture_hold(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(params): Path<HoldPathParams>,
+    Json(req): Json<CaptureHoldRequest>,
+) -> Result<ApiResponse<WalletHold>, ApiError> {
+    // Capturing moves the held money to the Suspense account, so owners
+    // may only release their holds
+    require_kyc_level(3, &auth_user)?;
+    find_standalone_hold(&pool, &params).await?;
+
+    let hold = WalletHold::capture(&pool, params.hold_id, req.amount).await?;
+
+    Ok(ApiResponse::success(hold))
+}
+
+async fn release_hold(
+    State(pool): State<PgPool>,
+    auth_user: AuthUser,
+    Path(params): Path<HoldPathParams>,
+) -> Result<ApiResponse<WalletHold>, ApiError> {
+    find_owned_hold(&pool, &auth_user, &params).await?;
+
+    let hold = WalletHold::release(&pool, params.hold_id).await?;
+
+    Ok(ApiResponse::success(hold))
+}
+
+fn statement_error(e: StatementError) -> ApiError {
+    match e {
+        StatementError::InvalidPeriod(_) => ApiError::validation(e.to_string()),
+        e => ApiError::InternalError(e.into()),
+    }
+}
+
+/// Trims a nickname; one that is only whitespace means no nickname
+fn clean_nickname(nickname: Option<String>) -> Option<String> {
+    nickname
+        .map(|nickname| nickname.trim().to_string())
+        .filter(|nickname| !nickname.is_empty())
+}
+
+/// Loads a wallet the caller owns
+async fn find_owned_wallet(
+    pool: &PgPool,
+    auth_user: &AuthUser,
+    wallet_id: Uuid,
+) -> Result<Wallet, ApiError> {
+    let wallet = Wallet::find(pool, wallet_id).await?;
+
+    // Verify ownership
+    if wallet.user_id != auth_user.id {
+        return Err(ApiError::AuthorizationError(
+            "Not authorized to access this wallet".to_string(),
+        ));
+    }
+
+    Ok(wallet)
+}
+
+/// Loads a standalone hold on a wallet the caller owns
+async fn find_owned_hold(
+    pool: &PgPool,
+    auth_user: &AuthUser,
+    params: &HoldPathParams,
+) -> Result<WalletHold, ApiError> {
+    find_owned_wallet(pool, auth_user, params.id).await?;
+    find_standalone_hold(pool, params).await
+}
+
+/// Loads a standalone hold on the wallet in the path. Holds placed by
+/// transactions follow the transaction lifecycle and cannot be settled here.
+async fn find_standalone_hold(
+    pool: &PgPool,
+    params: &HoldPathParams,
+) -> Result<WalletHold, ApiError> {
+    let hold = WalletHold::find_by_id(pool, params.hold_id)
+        .await?
+        .filter(|hold| hold.wallet_id == params.id)
+        .ok_or(HoldError::NotFound)?;
+
+    if hold.transaction_id.is_some() {
+        return Err(ApiError::validation(
+            "Transaction holds are settled by the transaction".to_string(),
+        ));
+    }
+
+    Ok(hold)
 }
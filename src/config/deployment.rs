@@ -1,3 +1,5 @@
+use crate::models::currency::Money;
+use rust_decimal::Decimal;
 use std::env;
 
 /// Represents the deployment mode of the application
@@ -74,7 +76,7 @@ pub struct DemoConfig {
     pub failure_rate: f64,
     
     /// Maximum allowed transaction amount in demo mode
-    pub max_transaction_amount: f64,
+    pub max_transaction_amount: Money,
 }
 
 impl Default for DemoConfig {
@@ -82,7 +84,7 @@ impl Default for DemoConfig {
         Self {
             api_delay: 2000,
             failure_rate: 0.1,
-            max_transaction_amount: 10000.0,
+            max_transaction_amount: Money::from_stored(Decimal::new(10000, 0), "USD"),
         }
     }
 }
@@ -90,6 +92,8 @@ impl Default for DemoConfig {
 /// Get demo mode configuration
 /// Only relevant when running in demo mode
 pub fn get_demo_config() -> DemoConfig {
+    let currency = env::var("DEMO_CURRENCY").unwrap_or_else(|_| "USD".to_string());
+
     DemoConfig {
         api_delay: env::var("DEMO_API_DELAY")
             .ok()
@@ -103,8 +107,8 @@ pub fn get_demo_config() -> DemoConfig {
             
         max_transaction_amount: env::var("DEMO_MAX_TRANSACTION")
             .ok()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(10000.0),
+            .and_then(|v| Money::parse(&v, &currency).ok())
+            .unwrap_or_else(|| DemoConfig::default().max_transaction_amount),
     }
 }
 
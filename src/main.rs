@@ -5,13 +5,15 @@ mod db;
 mod handlers;
 mod models;
 mod services;
+mod telemetry;
 mod utils;
 
 use axum::{
+    middleware,
     routing::{get, post},
     Router,
 };
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc};
 use tower_http::cors::CorsLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -28,12 +30,62 @@ async fn main() {
     // Load environment variables
     dotenv::dotenv().ok();
 
+    // Refuse to start with Argon2 settings every login would fail on
+    if let Err(e) = services::password::init() {
+        tracing::error!("Invalid password hashing configuration: {}", e);
+        std::process::exit(1);
+    }
+
     // Initialize the database connection pool
     let db_pool = db::init_db_pool().await;
     
     // Initialize Redis connection
     let redis_client = services::cache::init_redis().await;
 
+    // Emails are sent from a Redis-backed queue
+    let redis_manager = redis::aio::ConnectionManager::new(redis_client.clone())
+        .await
+        .expect("Failed to connect to Redis");
+    let email_queue = Arc::new(services::queue::EmailQueue::new(redis_manager.clone()));
+    let email_service = Arc::new(
+        services::email::EmailService::new(Arc::clone(&email_queue))
+            .await
+            .expect("Failed to set up email"),
+    );
+    email_queue.start(Arc::clone(&email_service)).await;
+
+    // Revoked access tokens are refused until they would have expired
+    let token_denylist = Arc::new(services::token_denylist::TokenDenylist::new(
+        redis_manager,
+        api::middleware::auth::access_token_ttl().num_seconds() as u64,
+    ));
+
+    // Refuse to start if a stored metadata schema no longer compiles
+    let metadata_schemas =
+        services::metadata_schema::MetadataSchemaRegistry::load(db_pool.clone()).await;
+    let metadata_schemas = match metadata_schemas {
+        Ok(registry) => Arc::new(registry),
+        Err(e) => {
+            tracing::error!("Failed to load metadata schemas: {}", e);
+            std::process::exit(1);
+        }
+    };
+    metadata_schemas.start().await;
+
+    // Background jobs
+    services::idempotency::IdempotencyKeyPurgeService::new(db_pool.clone())
+        .start()
+        .await;
+    services::scheduler::TransferScheduler::new(db_pool.clone(), Arc::clone(&email_service))
+        .start()
+        .await;
+    services::p2p::PendingTransferExpiryService::new(db_pool.clone())
+        .start()
+        .await;
+    services::holds::HoldExpiryService::new(db_pool.clone())
+        .start()
+        .await;
+
     // Build our application with a route
     let app = Router::new()
         .route("/health", get(health_check))
@@ -43,7 +95,14 @@ async fn main() {
         .route("/api/v1/wallet/deposit", post(handlers::wallet::deposit))
         .route("/api/v1/wallet/withdraw", post(handlers::wallet::withdraw))
         .layer(CorsLayer::permissive()) // Configure CORS for development
-        .with_state(db_pool);
+        .layer(middleware::from_fn(api::middleware::request_id::request_id))
+        .with_state(api::state::AppState {
+            pool: db_pool,
+            redis: redis_client,
+            email_service,
+            token_denylist,
+            metadata_schemas,
+        });
 
     // Run the server
     let port = std::env::var("PORT")
@@ -1,7 +1,8 @@
+use crate::models::pagination::{Cursor, Keyset, Page, PageRequest};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, QueryBuilder};
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
@@ -15,12 +16,15 @@ pub struct AuditLog {
     pub new_value: Option<Value>,
     pub ip_address: String,
     pub user_agent: String,
+    /// Request that performed the action, when it came through the API
+    pub request_id: Option<String>,
+    pub trace_id: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
 impl AuditLog {
-    pub async fn create(
-        pool: &PgPool,
+    pub async fn create<'e, E: sqlx::PgExecutor<'e>>(
+        executor: E,
         admin_id: Uuid,
         action: &str,
         entity_type: &str,
@@ -29,14 +33,17 @@ impl AuditLog {
         new_value: Option<Value>,
         ip_address: &str,
         user_agent: &str,
+        request_id: Option<&str>,
+        trace_id: Option<&str>,
     ) -> Result<Self, sqlx::Error> {
         let log = sqlx::query_as::<_, Self>(
             r#"
             INSERT INTO audit_logs (
                 admin_id, action, entity_type, entity_id,
-                old_value, new_value, ip_address, user_agent
+                old_value, new_value, ip_address, user_agent,
+                request_id, trace_id
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
             RETURNING *
             "#,
         )
@@ -48,7 +55,9 @@ impl AuditLog {
         .bind(new_value)
         .bind(ip_address)
         .bind(user_agent)
-        .fetch_one(pool)
+        .bind(request_id)
+        .bind(trace_id)
+        .fetch_one(executor)
         .await?;
 
         Ok(log)
@@ -57,75 +66,76 @@ impl AuditLog {
     pub async fn list_by_admin(
         pool: &PgPool,
         admin_id: Uuid,
-        limit: i64,
-        offset: i64,
-    ) -> Result<Vec<Self>, sqlx::Error> {
-        let logs = sqlx::query_as::<_, Self>(
-            r#"
-            SELECT * FROM audit_logs
-            WHERE admin_id = $1
-            ORDER BY created_at DESC
-            LIMIT $2 OFFSET $3
-            "#,
-        )
-        .bind(admin_id)
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(pool)
-        .await?;
-
-        Ok(logs)
+        page: &PageRequest,
+    ) -> Result<Page<Self>, sqlx::Error> {
+        Self::list(pool, page, |query| {
+            query.push(" WHERE admin_id = ");
+            query.push_bind(admin_id);
+        })
+        .await
     }
 
     pub async fn list_by_entity(
         pool: &PgPool,
         entity_type: &str,
         entity_id: Uuid,
-        limit: i64,
-        offset: i64,
-    ) -> Result<Vec<Self>, sqlx::Error> {
-        let logs = sqlx::query_as::<_, Self>(
-            r#"
-            SELECT * FROM audit_logs
-            WHERE entity_type = $1 AND entity_id = $2
-            ORDER BY created_at DESC
-            LIMIT $3 OFFSET $4
-            "#,
-        )
-        .bind(entity_type)
-        .bind(entity_id)
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(pool)
-        .await?;
-
-        Ok(logs)
+        page: &PageRequest,
+    ) -> Result<Page<Self>, sqlx::Error> {
+        Self::list(pool, page, |query| {
+            query.push(" WHERE entity_type = ");
+            query.push_bind(entity_type.to_string());
+            query.push(" AND entity_id = ");
+            query.push_bind(entity_id);
+        })
+        .await
     }
 
     pub async fn search(
         pool: &PgPool,
         query: &str,
-        limit: i64,
-        offset: i64,
-    ) -> Result<Vec<Self>, sqlx::Error> {
-        let logs = sqlx::query_as::<_, Self>(
-            r#"
-            SELECT * FROM audit_logs
-            WHERE 
-                action ILIKE $1 OR
-                entity_type ILIKE $1 OR
-                ip_address ILIKE $1 OR
-                user_agent ILIKE $1
-            ORDER BY created_at DESC
-            LIMIT $2 OFFSET $3
-            "#,
-        )
-        .bind(format!("%{}%", query))
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(pool)
-        .await?;
+        page: &PageRequest,
+    ) -> Result<Page<Self>, sqlx::Error> {
+        let pattern = format!("%{}%", query);
+        let search = query.to_string();
+        Self::list(pool, page, |query| {
+            query.push(" WHERE (action ILIKE ");
+            query.push_bind(pattern.clone());
+            query.push(" OR entity_type ILIKE ");
+            query.push_bind(pattern.clone());
+            query.push(" OR ip_address ILIKE ");
+            query.push_bind(pattern.clone());
+            query.push(" OR user_agent ILIKE ");
+            query.push_bind(pattern.clone());
+            query.push(" OR request_id = ");
+            query.push_bind(search.clone());
+            query.push(")");
+        })
+        .await
+    }
+
+    /// Runs a listing query, newest first. `push_filters` opens the WHERE
+    /// clause and is applied again for the count in page mode.
+    async fn list(
+        pool: &PgPool,
+        page: &PageRequest,
+        push_filters: impl Fn(&mut QueryBuilder<'_, Postgres>),
+    ) -> Result<Page<Self>, sqlx::Error> {
+        let mut query = QueryBuilder::new("SELECT * FROM audit_logs");
+        push_filters(&mut query);
+        page.push_to(&mut query, "audit_logs");
+        let rows = query.build_query_as::<Self>().fetch_all(pool).await?;
+
+        let total = page.count(pool, "audit_logs", push_filters).await?;
+
+        Ok(Page::from_rows(rows, page, total))
+    }
+}
 
-        Ok(logs)
+impl Keyset for AuditLog {
+    fn cursor(&self) -> Cursor {
+        Cursor {
+            created_at: self.created_at,
+            id: self.id,
+        }
     }
 }
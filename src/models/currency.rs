@@ -0,0 +1,260 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+use validator::ValidationError;
+
+/// An ISO 4217 currency and the number of decimal places it is quoted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Currency {
+    pub code: &'static str,
+    pub numeric: u16,
+    pub minor_units: u32,
+    pub name: &'static str,
+}
+
+/// Currencies the wallet accepts
+pub const CURRENCIES: &[Currency] = &[
+    Currency { code: "AED", numeric: 784, minor_units: 2, name: "UAE Dirham" },
+    Currency { code: "BHD", numeric: 48, minor_units: 3, name: "Bahraini Dinar" },
+    Currency { code: "BIF", numeric: 108, minor_units: 0, name: "Burundi Franc" },
+    Currency { code: "CAD", numeric: 124, minor_units: 2, name: "Canadian Dollar" },
+    Currency { code: "CDF", numeric: 976, minor_units: 2, name: "Congolese Franc" },
+    Currency { code: "CHF", numeric: 756, minor_units: 2, name: "Swiss Franc" },
+    Currency { code: "CNY", numeric: 156, minor_units: 2, name: "Yuan Renminbi" },
+    Currency { code: "ETB", numeric: 230, minor_units: 2, name: "Ethiopian Birr" },
+    Currency { code: "EUR", numeric: 978, minor_units: 2, name: "Euro" },
+    Currency { code: "GBP", numeric: 826, minor_units: 2, name: "Pound Sterling" },
+    Currency { code: "GHS", numeric: 936, minor_units: 2, name: "Ghana Cedi" },
+    Currency { code: "INR", numeric: 356, minor_units: 2, name: "Indian Rupee" },
+    Currency { code: "JPY", numeric: 392, minor_units: 0, name: "Yen" },
+    Currency { code: "KES", numeric: 404, minor_units: 2, name: "Kenyan Shilling" },
+    Currency { code: "KWD", numeric: 414, minor_units: 3, name: "Kuwaiti Dinar" },
+    Currency { code: "MWK", numeric: 454, minor_units: 2, name: "Malawi Kwacha" },
+    Currency { code: "MZN", numeric: 943, minor_units: 2, name: "Mozambique Metical" },
+    Currency { code: "NGN", numeric: 566, minor_units: 2, name: "Naira" },
+    Currency { code: "OMR", numeric: 512, minor_units: 3, name: "Rial Omani" },
+    Currency { code: "RWF", numeric: 646, minor_units: 0, name: "Rwanda Franc" },
+    Currency { code: "SAR", numeric: 682, minor_units: 2, name: "Saudi Riyal" },
+    Currency { code: "TZS", numeric: 834, minor_units: 2, name: "Tanzanian Shilling" },
+    Currency { code: "UGX", numeric: 800, minor_units: 0, name: "Uganda Shilling" },
+    Currency { code: "USD", numeric: 840, minor_units: 2, name: "US Dollar" },
+    Currency { code: "XAF", numeric: 950, minor_units: 0, name: "CFA Franc BEAC" },
+    Currency { code: "XOF", numeric: 952, minor_units: 0, name: "CFA Franc BCEAO" },
+    Currency { code: "ZAR", numeric: 710, minor_units: 2, name: "Rand" },
+    Currency { code: "ZMW", numeric: 967, minor_units: 2, name: "Zambian Kwacha" },
+];
+
+impl Currency {
+    /// Looks up a currency by its three-letter code
+    pub fn from_code(code: &str) -> Option<&'static Currency> {
+        CURRENCIES.iter().find(|c| c.code == code)
+    }
+
+    /// Rounds an amount to this currency's minor unit
+    pub fn round(&self, amount: Decimal, strategy: RoundingStrategy) -> Decimal {
+        amount.round_dp_with_strategy(self.minor_units, strategy)
+    }
+}
+
+/// Rounds a computed amount (a fee, an FX conversion) to the minor unit of
+/// `currency`. Codes missing from the registry fall back to two decimals so
+/// rows written before it existed still price.
+pub fn round_to_minor_units(amount: Decimal, currency: &str, strategy: RoundingStrategy) -> Decimal {
+    match Currency::from_code(currency) {
+        Some(registered) => registered.round(amount, strategy),
+        None => amount.round_dp_with_strategy(2, strategy),
+    }
+}
+
+/// Validator hook for request fields holding a currency code
+pub fn validate_currency_code(code: &str) -> Result<(), ValidationError> {
+    match Currency::from_code(code) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new("unsupported_currency")),
+    }
+}
+
+/// Validator hook for request amounts: the currency must be registered and
+/// the amount positive and no finer than its minor unit
+pub fn validate_amount(amount: Decimal, currency: &str) -> Result<(), ValidationError> {
+    if amount <= Decimal::ZERO {
+        return Err(ValidationError::new("amount_not_positive"));
+    }
+
+    match Money::new(amount, currency) {
+        Ok(_) => Ok(()),
+        Err(MoneyError::UnsupportedCurrency(_)) => Err(ValidationError::new("unsupported_currency")),
+        Err(_) => Err(ValidationError::new("amount_too_precise")),
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MoneyError {
+    #[error("Unsupported currency: {0}")]
+    UnsupportedCurrency(String),
+    #[error("{currency} amounts allow at most {minor_units} decimal places")]
+    TooPrecise { currency: String, minor_units: u32 },
+    #[error("Currency mismatch: {0} and {1}")]
+    CurrencyMismatch(String, String),
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+}
+
+/// An amount in a specific currency, never finer than the currency's minor unit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedMoney")]
+pub struct Money {
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+#[derive(Deserialize)]
+struct UncheckedMoney {
+    amount: Decimal,
+    currency: String,
+}
+
+impl TryFrom<UncheckedMoney> for Money {
+    type Error = MoneyError;
+
+    fn try_from(raw: UncheckedMoney) -> Result<Self, Self::Error> {
+        Money::new(raw.amount, &raw.currency)
+    }
+}
+
+impl Money {
+    /// Creates an amount, rejecting unknown currencies and sub-minor-unit
+    /// precision
+    pub fn new(amount: Decimal, currency: &str) -> Result<Self, MoneyError> {
+        let registered = Currency::from_code(currency)
+            .ok_or_else(|| MoneyError::UnsupportedCurrency(currency.to_string()))?;
+
+        if amount.normalize().scale() > registered.minor_units {
+            return Err(MoneyError::TooPrecise {
+                currency: registered.code.to_string(),
+                minor_units: registered.minor_units,
+            });
+        }
+
+        Ok(Self {
+            amount,
+            currency: registered.code.to_string(),
+        })
+    }
+
+    /// Parses a decimal string such as "1500.50"
+    pub fn parse(amount: &str, currency: &str) -> Result<Self, MoneyError> {
+        let amount = amount
+            .trim()
+            .parse::<Decimal>()
+            .map_err(|_| MoneyError::InvalidAmount(amount.to_string()))?;
+        Self::new(amount, currency)
+    }
+
+    pub fn zero(currency: &str) -> Result<Self, MoneyError> {
+        Self::new(Decimal::ZERO, currency)
+    }
+
+    /// Rounds a computed amount half away from zero and wraps it
+    pub fn rounded(amount: Decimal, currency: &str) -> Result<Self, MoneyError> {
+        let registered = Currency::from_code(currency)
+            .ok_or_else(|| MoneyError::UnsupportedCurrency(currency.to_string()))?;
+        Self::new(registered.round(amount, RoundingStrategy::MidpointAwayFromZero), currency)
+    }
+
+    /// Wraps an amount read back from the database, where the column type
+    /// already bounds precision and the currency was checked on the way in
+    pub fn from_stored(amount: Decimal, currency: &str) -> Self {
+        Self {
+            amount,
+            currency: currency.to_string(),
+        }
+    }
+
+    pub fn minor_units(&self) -> u32 {
+        Currency::from_code(&self.currency)
+            .map(|c| c.minor_units)
+            .unwrap_or(2)
+    }
+
+    /// The amount alone, written to the currency's minor units
+    pub fn format_amount(&self) -> String {
+        let mut amount = self.amount;
+        amount.rescale(self.minor_units());
+        amount.to_string()
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.amount > Decimal::ZERO
+    }
+
+    pub fn checked_add(&self, other: &Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        Ok(Money {
+            amount: self.amount + other.amount,
+            currency: self.currency.clone(),
+        })
+    }
+
+    pub fn checked_sub(&self, other: &Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        Ok(Money {
+            amount: self.amount - other.amount,
+            currency: self.currency.clone(),
+        })
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<(), MoneyError> {
+        if self.currency != other.currency {
+            return Err(MoneyError::CurrencyMismatch(
+                self.currency.clone(),
+                other.currency.clone(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.format_amount(), self.currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minor_unit_precision() {
+        assert!(Money::parse("10.50", "USD").is_ok());
+        assert!(Money::parse("10.5000", "USD").is_ok());
+        assert!(Money::parse("1.125", "KWD").is_ok());
+        assert_eq!(
+            Money::parse("10.505", "USD"),
+            Err(MoneyError::TooPrecise {
+                currency: "USD".to_string(),
+                minor_units: 2
+            })
+        );
+        assert!(Money::parse("1500.5", "UGX").is_err());
+        assert!(Money::parse("10", "XYZ").is_err());
+    }
+
+    #[test]
+    fn test_arithmetic_requires_same_currency() {
+        let usd = Money::parse("1.00", "USD").unwrap();
+        let kes = Money::parse("1.00", "KES").unwrap();
+        assert_eq!(usd.checked_add(&usd).unwrap().to_string(), "2.00 USD");
+        assert!(usd.checked_sub(&kes).is_err());
+    }
+
+    #[test]
+    fn test_rounding_follows_minor_units() {
+        let amount = Decimal::new(123_456, 3);
+        assert_eq!(Money::rounded(amount, "USD").unwrap().amount, Decimal::new(12_346, 2));
+        assert_eq!(Money::rounded(amount, "KWD").unwrap().amount, Decimal::new(123_456, 3));
+        assert_eq!(Money::rounded(amount, "UGX").unwrap().amount, Decimal::new(123, 0));
+    }
+}
@@ -0,0 +1,254 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use thiserror::Error;
+use uuid::Uuid;
+
+const DEFAULT_TTL_HOURS: i64 = 24;
+/// At most this many verification emails per user per hour
+pub const MAX_SENDS_PER_HOUR: i64 = 3;
+/// Minimum gap between two verification emails to the same user
+pub const MIN_RESEND_INTERVAL_SECS: i64 = 60;
+
+/// A single-use token proving the holder can read the user's inbox. Only
+/// the token's hash is stored.
+#[derive(Debug, sqlx::FromRow)]
+pub struct EmailVerificationToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Error)]
+pub enum EmailVerificationError {
+    #[error("Invalid or expired verification token")]
+    InvalidToken,
+    #[error("Email is already verified")]
+    AlreadyVerified,
+    #[error("Too many verification emails; try again later")]
+    RateLimited,
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl EmailVerificationToken {
+    /// How long a token is valid, from EMAIL_VERIFICATION_TTL_HOURS
+    pub fn ttl() -> Duration {
+        let hours = std::env::var("EMAIL_VERIFICATION_TTL_HOURS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_TTL_HOURS);
+        Duration::hours(hours)
+    }
+
+    /// Issues a token for the user, returning the plain token for the
+    /// email. Refused while the user is over the send limits.
+    pub async fn issue(pool: &PgPool, user_id: Uuid) -> Result<String, EmailVerificationError> {
+        let mut db_tx = pool.begin().await?;
+
+        // Serializes concurrent resends for the user
+        let verified = sqlx::query_scalar!(
+            r#"
+            SELECT email_verified_at IS NOT NULL AS "verified!" FROM users
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+            user_id
+        )
+        .fetch_one(&mut *db_tx)
+        .await?;
+        if verified {
+            return Err(EmailVerificationError::AlreadyVerified);
+        }
+
+        let recent = sqlx::query!(
+            r#"
+            SELECT COUNT(*) AS "count!", MAX(created_at) AS latest
+            FROM email_verification_tokens
+            WHERE user_id = $1 AND created_at > CURRENT_TIMESTAMP - INTERVAL '1 hour'
+            "#,
+            user_id
+        )
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        let too_soon = recent
+            .latest
+            .is_some_and(|latest| Utc::now() - latest < Duration::seconds(MIN_RESEND_INTERVAL_SECS));
+        if recent.count >= MAX_SENDS_PER_HOUR || too_soon {
+            return Err(EmailVerificationError::RateLimited);
+        }
+
+        let token = generate_token();
+        sqlx::query!(
+            r#"
+            INSERT INTO email_verification_tokens (user_id, token_hash, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            user_id,
+            hash_token(&token),
+            Utc::now() + Self::ttl()
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+
+        Ok(token)
+    }
+
+    /// Uses up a token and marks the user's email verified, returning the
+    /// user's id
+    pub async fn consume(pool: &PgPool, token: &str) -> Result<Uuid, EmailVerificationError> {
+        let mut db_tx = pool.begin().await?;
+
+        let user_id = sqlx::query_scalar!(
+            r#"
+            UPDATE email_verification_tokens SET used_at = CURRENT_TIMESTAMP
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+            RETURNING user_id
+            "#,
+            hash_token(token)
+        )
+        .fetch_optional(&mut *db_tx)
+        .await?
+        .ok_or(EmailVerificationError::InvalidToken)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET email_verified_at = COALESCE(email_verified_at, CURRENT_TIMESTAMP),
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        // Any other outstanding tokens have nothing left to verify
+        sqlx::query!(
+            r#"
+            UPDATE email_verification_tokens SET used_at = CURRENT_TIMESTAMP
+            WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+
+        Ok(user_id)
+    }
+}
+
+/// 256 random bits, URL safe so it can go in a link
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.trim().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{testing::create_user, user::User};
+
+    async fn user_id(pool: &PgPool) -> Uuid {
+        create_user(pool, "verify@example.com").await.id
+    }
+
+    /// Moves the user's tokens back in time, as if issued `secs` ago
+    async fn backdate(pool: &PgPool, user_id: Uuid, secs: f64) {
+        sqlx::query!(
+            r#"
+            UPDATE email_verification_tokens
+            SET created_at = created_at - make_interval(secs => $2)
+            WHERE user_id = $1
+            "#,
+            user_id,
+            secs
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_resend_is_rate_limited(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+
+        EmailVerificationToken::issue(&pool, user_id).await.unwrap();
+        assert!(matches!(
+            EmailVerificationToken::issue(&pool, user_id).await,
+            Err(EmailVerificationError::RateLimited)
+        ));
+
+        // Spaced out, the hourly cap still applies
+        for _ in 1..MAX_SENDS_PER_HOUR {
+            backdate(&pool, user_id, MIN_RESEND_INTERVAL_SECS as f64).await;
+            EmailVerificationToken::issue(&pool, user_id).await.unwrap();
+        }
+        backdate(&pool, user_id, MIN_RESEND_INTERVAL_SECS as f64).await;
+        assert!(matches!(
+            EmailVerificationToken::issue(&pool, user_id).await,
+            Err(EmailVerificationError::RateLimited)
+        ));
+
+        backdate(&pool, user_id, 3600.0).await;
+        EmailVerificationToken::issue(&pool, user_id).await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_token_is_single_use(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+        let token = EmailVerificationToken::issue(&pool, user_id).await.unwrap();
+
+        assert_eq!(EmailVerificationToken::consume(&pool, &token).await.unwrap(), user_id);
+        let user = User::find_by_id(&pool, user_id).await.unwrap().unwrap();
+        assert!(user.email_verified_at.is_some());
+
+        assert!(matches!(
+            EmailVerificationToken::consume(&pool, &token).await,
+            Err(EmailVerificationError::InvalidToken)
+        ));
+        assert!(matches!(
+            EmailVerificationToken::issue(&pool, user_id).await,
+            Err(EmailVerificationError::AlreadyVerified)
+        ));
+    }
+
+    #[sqlx::test]
+    async fn test_expired_token_is_refused(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+        let token = EmailVerificationToken::issue(&pool, user_id).await.unwrap();
+
+        sqlx::query!(
+            r#"
+            UPDATE email_verification_tokens SET expires_at = CURRENT_TIMESTAMP - INTERVAL '1 minute'
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            EmailVerificationToken::consume(&pool, &token).await,
+            Err(EmailVerificationError::InvalidToken)
+        ));
+        let user = User::find_by_id(&pool, user_id).await.unwrap().unwrap();
+        assert!(user.email_verified_at.is_none());
+    }
+}
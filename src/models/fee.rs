@@ -0,0 +1,203 @@
+use crate::models::{
+    currency::{round_to_minor_units, Money},
+    transaction::TransactionType,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct FeeSchedule {
+    pub id: Uuid,
+    pub transaction_type: TransactionType,
+    pub currency: String,
+    pub kyc_level: Option<i32>,
+    pub min_amount: Decimal,
+    pub max_amount: Option<Decimal>,
+    pub flat_fee: Decimal,
+    pub percentage: Decimal,
+    pub min_fee: Option<Decimal>,
+    pub max_fee: Option<Decimal>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// How a fee was arrived at, stored on the transaction and shown to clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeBreakdown {
+    pub schedule_id: Option<Uuid>,
+    pub flat_fee: Decimal,
+    pub percentage: Decimal,
+    pub percentage_fee: Decimal,
+    pub total: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeQuote {
+    pub transaction_type: TransactionType,
+    pub amount: Decimal,
+    pub currency: String,
+    pub fee: FeeBreakdown,
+    /// What leaves the payer's wallet, or for deposits what is credited
+    pub total: Decimal,
+}
+
+impl FeeBreakdown {
+    pub fn none() -> Self {
+        Self {
+            schedule_id: None,
+            flat_fee: Decimal::ZERO,
+            percentage: Decimal::ZERO,
+            percentage_fee: Decimal::ZERO,
+            total: Decimal::ZERO,
+        }
+    }
+}
+
+impl FeeSchedule {
+    /// Finds the rule for a transaction. A rule for the payer's exact KYC
+    /// level wins over a catch-all rule, and the narrowest band wins after that.
+    pub async fn find_applicable<'e, E: sqlx::PgExecutor<'e>>(
+        executor: E,
+        transaction_type: &TransactionType,
+        currency: &str,
+        kyc_level: i32,
+        amount: Decimal,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            FeeSchedule,
+            r#"
+            SELECT * FROM fee_schedules
+            WHERE active
+              AND transaction_type = $1
+              AND currency = $2
+              AND (kyc_level IS NULL OR kyc_level = $3)
+              AND min_amount <= $4
+              AND (max_amount IS NULL OR $4 < max_amount)
+            ORDER BY kyc_level IS NULL, min_amount DESC
+            LIMIT 1
+            "#,
+            transaction_type.clone() as TransactionType,
+            currency,
+            kyc_level,
+            amount,
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Applies the rule to an amount
+    pub fn calculate(&self, amount: Decimal) -> FeeBreakdown {
+        let percentage_fee = round_to_minor_units(
+            amount * self.percentage / Decimal::ONE_HUNDRED,
+            &self.currency,
+            RoundingStrategy::MidpointAwayFromZero,
+        );
+
+        let mut total = self.flat_fee + percentage_fee;
+        if let Some(min_fee) = self.min_fee {
+            total = total.max(min_fee);
+        }
+        if let Some(max_fee) = self.max_fee {
+            total = total.min(max_fee);
+        }
+
+        FeeBreakdown {
+            schedule_id: Some(self.id),
+            flat_fee: self.flat_fee,
+            percentage: self.percentage,
+            percentage_fee,
+            total,
+        }
+    }
+}
+
+impl FeeQuote {
+    /// A quote that charges nothing
+    pub fn free(transaction_type: TransactionType, amount: &Money) -> Self {
+        Self {
+            transaction_type,
+            amount: amount.amount,
+            currency: amount.currency.clone(),
+            fee: FeeBreakdown::none(),
+            total: amount.amount,
+        }
+    }
+
+    /// Prices a transaction against the fee schedule. Fees and refunds are
+    /// never charged a fee themselves.
+    pub async fn calculate<'e, E: sqlx::PgExecutor<'e>>(
+        executor: E,
+        transaction_type: TransactionType,
+        currency: &str,
+        kyc_level: i32,
+        amount: Decimal,
+    ) -> Result<Self, sqlx::Error> {
+        let fee = match transaction_type {
+            TransactionType::Fee | TransactionType::Refund => FeeBreakdown::none(),
+            _ => FeeSchedule::find_applicable(executor, &transaction_type, currency, kyc_level, amount)
+                .await?
+                .map(|schedule| schedule.calculate(amount))
+                .unwrap_or_else(FeeBreakdown::none),
+        };
+
+        let total = match transaction_type {
+            TransactionType::Deposit => amount - fee.total,
+            _ => amount + fee.total,
+        };
+
+        Ok(Self {
+            transaction_type,
+            amount,
+            currency: currency.to_string(),
+            fee,
+            total,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(flat: i64, percentage: Decimal, min: Option<i64>, max: Option<i64>) -> FeeSchedule {
+        FeeSchedule {
+            id: Uuid::new_v4(),
+            transaction_type: TransactionType::Transfer,
+            currency: "USD".to_string(),
+            kyc_level: None,
+            min_amount: Decimal::ZERO,
+            max_amount: None,
+            flat_fee: Decimal::new(flat, 0),
+            percentage,
+            min_fee: min.map(|v| Decimal::new(v, 0)),
+            max_fee: max.map(|v| Decimal::new(v, 0)),
+            active: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_flat_plus_percentage() {
+        let fee = schedule(1, Decimal::new(15, 1), None, None).calculate(Decimal::new(200, 0));
+        assert_eq!(fee.percentage_fee, Decimal::new(3, 0));
+        assert_eq!(fee.total, Decimal::new(4, 0));
+    }
+
+    #[test]
+    fn test_fee_caps() {
+        let capped = schedule(0, Decimal::ONE, Some(2), Some(10));
+        assert_eq!(capped.calculate(Decimal::new(50, 0)).total, Decimal::new(2, 0));
+        assert_eq!(capped.calculate(Decimal::new(5000, 0)).total, Decimal::new(10, 0));
+    }
+
+    #[test]
+    fn test_percentage_rounds_to_minor_unit() {
+        let mut ugx = schedule(0, Decimal::new(15, 1), None, None);
+        ugx.currency = "UGX".to_string();
+        assert_eq!(ugx.calculate(Decimal::new(1_010, 0)).total, Decimal::new(15, 0));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::models::currency::{round_to_minor_units, Currency, Money};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use thiserror::Error;
+use uuid::Uuid;
+
+const DEFAULT_SPREAD_PERCENT: &str = "1.0";
+const DEFAULT_QUOTE_TTL_SECS: i64 = 60;
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct FxRate {
+    pub id: Uuid,
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub rate: Decimal,
+    pub effective_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct FxQuote {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub from_currency: String,
+    pub to_currency: String,
+    pub mid_rate: Decimal,
+    pub rate: Decimal,
+    pub from_amount: Decimal,
+    pub to_amount: Decimal,
+    pub expires_at: DateTime<Utc>,
+    pub transaction_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Error)]
+pub enum FxError {
+    #[error("No rate available for {0}/{1}")]
+    RateNotFound(String, String),
+    #[error("Invalid currency pair: {0}")]
+    InvalidPair(String),
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+    #[error("Quote not found")]
+    QuoteNotFound,
+    #[error("Quote has expired")]
+    QuoteExpired,
+    #[error("Quote has already been used")]
+    QuoteUsed,
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl FxRate {
+    /// Publishes a mid-market rate
+    pub async fn create(
+        pool: &PgPool,
+        base_currency: &str,
+        quote_currency: &str,
+        rate: Decimal,
+        effective_at: DateTime<Utc>,
+    ) -> Result<Self, FxError> {
+        Self::check_pair(base_currency, quote_currency)?;
+
+        let rate = sqlx::query_as!(
+            FxRate,
+            r#"
+            INSERT INTO fx_rates (base_currency, quote_currency, rate, effective_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+            base_currency,
+            quote_currency,
+            rate,
+            effective_at,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(rate)
+    }
+
+    /// Gets the mid rate in force for converting `from` into `to`, using the
+    /// inverse of the opposite pair when only that one is published
+    pub async fn current(pool: &PgPool, from: &str, to: &str) -> Result<Decimal, FxError> {
+        let rate = sqlx::query_as!(
+            FxRate,
+            r#"
+            SELECT * FROM fx_rates
+            WHERE ((base_currency = $1 AND quote_currency = $2)
+                OR (base_currency = $2 AND quote_currency = $1))
+              AND effective_at <= CURRENT_TIMESTAMP
+            ORDER BY effective_at DESC
+            LIMIT 1
+            "#,
+            from,
+            to,
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| FxError::RateNotFound(from.to_string(), to.to_string()))?;
+
+        if rate.base_currency == from {
+            Ok(rate.rate)
+        } else {
+            Ok((Decimal::ONE / rate.rate).round_dp(10))
+        }
+    }
+
+    /// Both sides must be distinct registered currencies
+    fn check_pair(from: &str, to: &str) -> Result<(), FxError> {
+        if from == to || Currency::from_code(from).is_none() || Currency::from_code(to).is_none() {
+            return Err(FxError::InvalidPair(format!("{}/{}", from, to)));
+        }
+        Ok(())
+    }
+}
+
+impl FxQuote {
+    /// Spread taken from the mid rate, from FX_SPREAD_PERCENT
+    fn spread_percent() -> Decimal {
+        std::env::var("FX_SPREAD_PERCENT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(|| DEFAULT_SPREAD_PERCENT.parse().unwrap())
+    }
+
+    /// How long a quote's rate is locked, from FX_QUOTE_TTL_SECS
+    fn ttl() -> Duration {
+        let secs = std::env::var("FX_QUOTE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_QUOTE_TTL_SECS);
+        Duration::seconds(secs)
+    }
+
+    /// Prices a conversion and locks the rate until the quote expires
+    pub async fn create(
+        pool: &PgPool,
+        user_id: Uuid,
+        from_currency: &str,
+        to_currency: &str,
+        from_amount: Decimal,
+    ) -> Result<Self, FxError> {
+        FxRate::check_pair(from_currency, to_currency)?;
+        let from_money = Money::new(from_amount, from_currency)
+            .map_err(|e| FxError::InvalidAmount(e.to_string()))?;
+
+        let mid_rate = FxRate::current(pool, from_currency, to_currency).await?;
+        let rate = (mid_rate * (Decimal::ONE - Self::spread_percent() / Decimal::ONE_HUNDRED)).round_dp(10);
+        let to_amount = round_to_minor_units(from_money.amount * rate, to_currency, RoundingStrategy::ToZero);
+
+        let quote = sqlx::query_as!(
+            FxQuote,
+            r#"
+            INSERT INTO fx_quotes (
+                user_id, from_currency, to_currency, mid_rate, rate,
+                from_amount, to_amount, expires_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING *
+            "#,
+            user_id,
+            from_currency,
+            to_currency,
+            mid_rate,
+            rate,
+            from_money.amount,
+            to_amount,
+            Utc::now() + Self::ttl(),
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(quote)
+    }
+
+    /// Retrieves a quote by its ID
+    pub async fn find_by_id(
+        db_tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+    ) -> Result<Option<Self>, FxError> {
+        let quote = sqlx::query_as!(
+            FxQuote,
+            r#"
+            SELECT * FROM fx_quotes WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?;
+
+        Ok(quote)
+    }
+
+    /// Locks an unexpired, unused quote for a conversion
+    pub async fn lock_for_use(
+        db_tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+    ) -> Result<Self, FxError> {
+        let quote = sqlx::query_as!(
+            FxQuote,
+            r#"
+            SELECT * FROM fx_quotes WHERE id = $1
+            FOR UPDATE
+            "#,
+            id
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?
+        .ok_or(FxError::QuoteNotFound)?;
+
+        if quote.transaction_id.is_some() {
+            return Err(FxError::QuoteUsed);
+        }
+        if quote.expires_at <= Utc::now() {
+            return Err(FxError::QuoteExpired);
+        }
+
+        Ok(quote)
+    }
+
+    /// Links the quote to the conversion that used it
+    pub async fn mark_used(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        transaction_id: Uuid,
+    ) -> Result<(), FxError> {
+        sqlx::query!(
+            r#"
+            UPDATE fx_quotes SET transaction_id = $1 WHERE id = $2
+            "#,
+            transaction_id,
+            self.id
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        self.transaction_id = Some(transaction_id);
+
+        Ok(())
+    }
+
+    /// What the converted amount would have been at the mid rate; the
+    /// difference to `to_amount` is the spread the platform earns
+    pub fn mid_to_amount(&self) -> Decimal {
+        round_to_minor_units(self.from_amount * self.mid_rate, &self.to_currency, RoundingStrategy::ToZero)
+    }
+}
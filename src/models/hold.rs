@@ -0,0 +1,386 @@
+use crate::models::{
+    currency::Money,
+    ledger::{JournalEntry, LedgerAccount, LedgerAccountType, LedgerError, PostingLeg},
+    wallet::{Wallet, WalletError},
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct WalletHold {
+    pub id: Uuid,
+    pub wallet_id: Uuid,
+    pub transaction_id: Option<Uuid>,
+    pub amount: Decimal,
+    pub captured_amount: Decimal,
+    pub status: HoldStatus,
+    pub reference_id: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+pub enum HoldStatus {
+    Active,
+    Captured,
+    Released,
+    Expired,
+}
+
+#[derive(Debug, Error)]
+pub enum HoldError {
+    #[error("Hold not found")]
+    NotFound,
+    #[error("Hold is not active")]
+    NotActive,
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+    #[error("Wallet error: {0}")]
+    WalletError(#[from] WalletError),
+    #[error("Ledger error: {0}")]
+    LedgerError(#[from] LedgerError),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl WalletHold {
+    /// Reserves part of a wallet's available balance. The caller must hold the
+    /// wallet row lock.
+    pub async fn place(
+        db_tx: &mut Transaction<'_, Postgres>,
+        wallet: &Wallet,
+        amount: Decimal,
+        transaction_id: Option<Uuid>,
+        reference_id: Option<String>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<Self, HoldError> {
+        wallet.can_debit(amount)?;
+
+        let reserved = sqlx::query!(
+            r#"
+            UPDATE wallets
+            SET held_balance = held_balance + $1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2 AND status = 'active' AND balance - held_balance >= $1
+            "#,
+            amount,
+            wallet.id
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        if reserved.rows_affected() != 1 {
+            return Err(WalletError::InsufficientFunds {
+                required: wallet.money(amount),
+                available: wallet.available(),
+            }
+            .into());
+        }
+
+        let hold = sqlx::query_as!(
+            WalletHold,
+            r#"
+            INSERT INTO wallet_holds (wallet_id, transaction_id, amount, reference_id, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+            wallet.id,
+            transaction_id,
+            amount,
+            reference_id,
+            expires_at,
+        )
+        .fetch_one(&mut **db_tx)
+        .await?;
+
+        Ok(hold)
+    }
+
+    /// Places a hold in its own database transaction
+    pub async fn authorize(
+        pool: &PgPool,
+        wallet_id: Uuid,
+        amount: Decimal,
+        reference_id: Option<String>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<Self, HoldError> {
+        let mut db_tx = pool.begin().await?;
+
+        let wallet = Wallet::find_by_id_for_update(&mut db_tx, wallet_id)
+            .await?
+            .ok_or(HoldError::NotFound)?;
+        let hold = Self::place(&mut db_tx, &wallet, amount, None, reference_id, expires_at).await?;
+
+        db_tx.commit().await?;
+
+        Ok(hold)
+    }
+
+    /// Retrieves a hold by its ID
+    pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, HoldError> {
+        let hold = sqlx::query_as!(
+            WalletHold,
+            r#"
+            SELECT * FROM wallet_holds WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(hold)
+    }
+
+    /// Gets the active hold placed for a transaction, locking it
+    pub async fn find_active_for_transaction(
+        db_tx: &mut Transaction<'_, Postgres>,
+        transaction_id: Uuid,
+    ) -> Result<Option<Self>, HoldError> {
+        let hold = sqlx::query_as!(
+            WalletHold,
+            r#"
+            SELECT * FROM wallet_holds
+            WHERE transaction_id = $1 AND status = 'active'
+            FOR UPDATE
+            "#,
+            transaction_id
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?;
+
+        Ok(hold)
+    }
+
+    /// Gets all holds on a wallet, newest first
+    pub async fn find_by_wallet(pool: &PgPool, wallet_id: Uuid) -> Result<Vec<Self>, HoldError> {
+        let holds = sqlx::query_as!(
+            WalletHold,
+            r#"
+            SELECT * FROM wallet_holds
+            WHERE wallet_id = $1
+            ORDER BY created_at DESC
+            "#,
+            wallet_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(holds)
+    }
+
+    /// Captures all or part of the hold, debiting the wallet by the captured
+    /// amount and releasing the remainder. The caller must hold the wallet row
+    /// lock. Returns the captured amount.
+    pub async fn capture_in(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        amount: Option<Decimal>,
+    ) -> Result<Decimal, HoldError> {
+        if self.status != HoldStatus::Active {
+            return Err(HoldError::NotActive);
+        }
+
+        let captured = amount.unwrap_or(self.amount);
+        if captured <= Decimal::ZERO || captured > self.amount {
+            return Err(HoldError::InvalidAmount(format!(
+                "Capture must be between 0 and {}",
+                self.amount
+            )));
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE wallets
+            SET balance = balance - $1,
+                held_balance = held_balance - $2,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $3
+            "#,
+            captured,
+            self.amount,
+            self.wallet_id
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE wallet_holds
+            SET status = 'captured', captured_amount = $1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2
+            "#,
+            captured,
+            self.id
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        self.status = HoldStatus::Captured;
+        self.captured_amount = captured;
+
+        Ok(captured)
+    }
+
+    /// Returns the held funds to the available balance. The caller must hold
+    /// the wallet row lock.
+    pub async fn release_in(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        status: HoldStatus,
+    ) -> Result<(), HoldError> {
+        if self.status != HoldStatus::Active {
+            return Err(HoldError::NotActive);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE wallets
+            SET held_balance = held_balance - $1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2
+            "#,
+            self.amount,
+            self.wallet_id
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE wallet_holds
+            SET status = $1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2
+            "#,
+            status.clone() as HoldStatus,
+            self.id
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        self.status = status;
+
+        Ok(())
+    }
+
+    /// Captures a standalone hold. The captured funds move to the suspense
+    /// account until the card or bank settlement is booked.
+    pub async fn capture(
+        pool: &PgPool,
+        id: Uuid,
+        amount: Option<Decimal>,
+    ) -> Result<Self, HoldError> {
+        let mut db_tx = pool.begin().await?;
+        let mut hold = Self::lock_with_wallet(&mut db_tx, id).await?;
+        let wallet = Wallet::find_by_id_for_update(&mut db_tx, hold.wallet_id)
+            .await?
+            .ok_or(HoldError::NotFound)?;
+
+        if let Some(amount) = amount {
+            Money::new(amount, &wallet.currency).map_err(|e| HoldError::InvalidAmount(e.to_string()))?;
+        }
+
+        let captured = hold.capture_in(&mut db_tx, amount).await?;
+
+        let from = LedgerAccount::for_wallet(&mut db_tx, wallet.id, &wallet.currency).await?;
+        let to = LedgerAccount::system(&mut db_tx, LedgerAccountType::Suspense, &wallet.currency).await?;
+        JournalEntry::post(
+            &mut db_tx,
+            None,
+            &format!("hold:capture:{}", hold.id),
+            &[
+                PostingLeg {
+                    account: from.id,
+                    amount: -captured,
+                    currency: wallet.currency.clone(),
+                },
+                PostingLeg {
+                    account: to.id,
+                    amount: captured,
+                    currency: wallet.currency.clone(),
+                },
+            ],
+        )
+        .await?;
+
+        db_tx.commit().await?;
+
+        Ok(hold)
+    }
+
+    /// Releases a standalone hold in full
+    pub async fn release(pool: &PgPool, id: Uuid) -> Result<Self, HoldError> {
+        let mut db_tx = pool.begin().await?;
+        let mut hold = Self::lock_with_wallet(&mut db_tx, id).await?;
+
+        hold.release_in(&mut db_tx, HoldStatus::Released).await?;
+
+        db_tx.commit().await?;
+
+        Ok(hold)
+    }
+
+    /// Releases every active hold whose expiry has passed
+    pub async fn expire_stale(pool: &PgPool) -> Result<u64, HoldError> {
+        let expired_ids = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM wallet_holds
+            WHERE status = 'active' AND expires_at < CURRENT_TIMESTAMP
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut expired = 0;
+        for id in expired_ids {
+            let mut db_tx = pool.begin().await?;
+            let mut hold = Self::lock_with_wallet(&mut db_tx, id).await?;
+
+            // Captured or released since the scan
+            if hold.status != HoldStatus::Active {
+                continue;
+            }
+
+            hold.release_in(&mut db_tx, HoldStatus::Expired).await?;
+            db_tx.commit().await?;
+            expired += 1;
+        }
+
+        Ok(expired)
+    }
+
+    /// Locks the hold's wallet and then the hold, matching the order used by
+    /// transaction processing
+    async fn lock_with_wallet(
+        db_tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+    ) -> Result<Self, HoldError> {
+        let wallet_id = sqlx::query_scalar!(
+            r#"
+            SELECT wallet_id FROM wallet_holds WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?
+        .ok_or(HoldError::NotFound)?;
+
+        Wallet::find_by_id_for_update(db_tx, wallet_id).await?;
+
+        let hold = sqlx::query_as!(
+            WalletHold,
+            r#"
+            SELECT * FROM wallet_holds WHERE id = $1
+            FOR UPDATE
+            "#,
+            id
+        )
+        .fetch_one(&mut **db_tx)
+        .await?;
+
+        Ok(hold)
+    }
+}
@@ -0,0 +1,192 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+const DEFAULT_TTL_HOURS: i64 = 24;
+const DEFAULT_LEASE_SECS: i64 = 60;
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct IdempotencyKey {
+    pub user_id: Uuid,
+    pub key: String,
+    pub request_hash: String,
+    pub response_body: Option<Value>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Identifies the request currently holding the key
+    pub claim_id: Uuid,
+    /// While unanswered, retries are refused until then
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+/// Outcome of trying to claim an idempotency key for a request
+#[derive(Debug)]
+pub enum IdempotencyClaim {
+    /// The key is new, expired or its lease lapsed; the caller should
+    /// process the request and record the outcome under this claim ID
+    Claimed(Uuid),
+    /// The same request already completed; this is its stored response
+    Replay(Value),
+    /// The same request is still being processed
+    InProgress,
+    /// The key was already used with a different request body
+    Mismatch,
+}
+
+impl IdempotencyKey {
+    /// How long stored keys are honoured, from IDEMPOTENCY_KEY_TTL_HOURS
+    pub fn ttl() -> Duration {
+        let hours = std::env::var("IDEMPOTENCY_KEY_TTL_HOURS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_TTL_HOURS);
+        Duration::hours(hours)
+    }
+
+    /// How long a request may hold a key before a retry can take it over,
+    /// from IDEMPOTENCY_KEY_LEASE_SECS
+    pub fn lease() -> Duration {
+        let secs = std::env::var("IDEMPOTENCY_KEY_LEASE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_LEASE_SECS);
+        Duration::seconds(secs)
+    }
+
+    /// Hashes a request body so replays can be told apart from key reuse
+    pub fn fingerprint<T: Serialize>(request: &T) -> Result<String, serde_json::Error> {
+        let body = serde_json::to_vec(request)?;
+        Ok(hex::encode(Sha256::digest(&body)))
+    }
+
+    /// Claims a key for a request. An expired key, or one whose request died
+    /// without answering before its lease ran out, is taken over as if new.
+    pub async fn claim(
+        pool: &PgPool,
+        user_id: Uuid,
+        key: &str,
+        request_hash: &str,
+    ) -> Result<IdempotencyClaim, sqlx::Error> {
+        let now = Utc::now();
+
+        // A lapsed lease is only taken over by the same request; a different
+        // body is still a mismatch
+        let claimed = sqlx::query_as!(
+            IdempotencyKey,
+            r#"
+            INSERT INTO idempotency_keys (user_id, key, request_hash, expires_at, claim_id, locked_until)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (user_id, key) DO UPDATE
+            SET request_hash = EXCLUDED.request_hash,
+                response_body = NULL,
+                created_at = CURRENT_TIMESTAMP,
+                expires_at = EXCLUDED.expires_at,
+                claim_id = EXCLUDED.claim_id,
+                locked_until = EXCLUDED.locked_until
+            WHERE idempotency_keys.expires_at < CURRENT_TIMESTAMP
+               OR (idempotency_keys.response_body IS NULL
+                   AND idempotency_keys.locked_until < CURRENT_TIMESTAMP
+                   AND idempotency_keys.request_hash = EXCLUDED.request_hash)
+            RETURNING *
+            "#,
+            user_id,
+            key,
+            request_hash,
+            now + Self::ttl(),
+            Uuid::new_v4(),
+            now + Self::lease(),
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        if let Some(claimed) = claimed {
+            return Ok(IdempotencyClaim::Claimed(claimed.claim_id));
+        }
+
+        let existing = sqlx::query_as!(
+            IdempotencyKey,
+            r#"
+            SELECT * FROM idempotency_keys WHERE user_id = $1 AND key = $2
+            "#,
+            user_id,
+            key,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        if existing.request_hash != request_hash {
+            return Ok(IdempotencyClaim::Mismatch);
+        }
+
+        Ok(match existing.response_body {
+            Some(response) => IdempotencyClaim::Replay(response),
+            None => IdempotencyClaim::InProgress,
+        })
+    }
+
+    /// Stores the response of a successfully processed request inside the
+    /// database transaction that made its changes, so one never commits
+    /// without the other. Returns false if the claim was taken over after its
+    /// lease lapsed, in which case the caller must roll back.
+    pub async fn complete_in(
+        db_tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        key: &str,
+        claim_id: Uuid,
+        response: &Value,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE idempotency_keys
+            SET response_body = $4, locked_until = NULL
+            WHERE user_id = $1 AND key = $2 AND claim_id = $3 AND response_body IS NULL
+            "#,
+            user_id,
+            key,
+            claim_id,
+            response,
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Frees a key whose request failed so the client can retry it
+    pub async fn release(
+        pool: &PgPool,
+        user_id: Uuid,
+        key: &str,
+        claim_id: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            DELETE FROM idempotency_keys
+            WHERE user_id = $1 AND key = $2 AND claim_id = $3 AND response_body IS NULL
+            "#,
+            user_id,
+            key,
+            claim_id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes expired keys
+    pub async fn purge_expired(pool: &PgPool) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM idempotency_keys WHERE expires_at < CURRENT_TIMESTAMP
+            "#
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
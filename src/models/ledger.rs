@@ -0,0 +1,329 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LedgerAccount {
+    pub id: Uuid,
+    pub account_type: LedgerAccountType,
+    pub wallet_id: Option<Uuid>,
+    pub currency: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+pub enum LedgerAccountType {
+    Wallet,
+    Reserve,
+    FeeIncome,
+    Suspense,
+    FxPosition,
+    FxSpread,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct JournalEntry {
+    pub id: Uuid,
+    pub transaction_id: Option<Uuid>,
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Posting {
+    pub id: Uuid,
+    pub journal_entry_id: Uuid,
+    pub ledger_account_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One side of a journal entry before it is written
+#[derive(Debug, Clone)]
+pub struct PostingLeg {
+    pub account: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+/// A wallet whose stored balance disagrees with the sum of its postings
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LedgerDiscrepancy {
+    pub wallet_id: Uuid,
+    pub wallet_balance: Decimal,
+    pub ledger_balance: Decimal,
+}
+
+#[derive(Debug, Error)]
+pub enum LedgerError {
+    #[error("Unbalanced journal entry: {currency} legs sum to {sum}")]
+    Unbalanced { currency: String, sum: Decimal },
+    #[error("Journal entry needs at least two legs")]
+    TooFewLegs,
+    #[error("Invalid posting: {0}")]
+    InvalidPosting(String),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl LedgerAccount {
+    /// Gets or creates the ledger account backing a wallet
+    pub async fn for_wallet(
+        db_tx: &mut Transaction<'_, Postgres>,
+        wallet_id: Uuid,
+        currency: &str,
+    ) -> Result<Self, LedgerError> {
+        let account = sqlx::query_as!(
+            LedgerAccount,
+            r#"
+            INSERT INTO ledger_accounts (account_type, wallet_id, currency)
+            VALUES ('wallet', $1, $2)
+            ON CONFLICT (wallet_id) DO UPDATE SET wallet_id = EXCLUDED.wallet_id
+            RETURNING *
+            "#,
+            wallet_id,
+            currency,
+        )
+        .fetch_one(&mut **db_tx)
+        .await?;
+
+        Ok(account)
+    }
+
+    /// Gets or creates the platform-level account of a type for a currency
+    pub async fn system(
+        db_tx: &mut Transaction<'_, Postgres>,
+        account_type: LedgerAccountType,
+        currency: &str,
+    ) -> Result<Self, LedgerError> {
+        if account_type == LedgerAccountType::Wallet {
+            return Err(LedgerError::InvalidPosting(
+                "Wallet accounts must be resolved by wallet".to_string(),
+            ));
+        }
+
+        let account = sqlx::query_as!(
+            LedgerAccount,
+            r#"
+            INSERT INTO ledger_accounts (account_type, currency)
+            VALUES ($1, $2)
+            ON CONFLICT (account_type, currency) WHERE wallet_id IS NULL
+            DO UPDATE SET currency = EXCLUDED.currency
+            RETURNING *
+            "#,
+            account_type as LedgerAccountType,
+            currency,
+        )
+        .fetch_one(&mut **db_tx)
+        .await?;
+
+        Ok(account)
+    }
+
+    /// Sums all postings for an account
+    pub async fn balance(pool: &PgPool, account_id: Uuid) -> Result<Decimal, LedgerError> {
+        let balance = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(amount), 0) as "balance!"
+            FROM postings
+            WHERE ledger_account_id = $1
+            "#,
+            account_id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(balance)
+    }
+
+    /// Derives a wallet's balance from its postings
+    pub async fn wallet_balance(pool: &PgPool, wallet_id: Uuid) -> Result<Decimal, LedgerError> {
+        let balance = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(p.amount), 0) as "balance!"
+            FROM postings p
+            JOIN ledger_accounts a ON a.id = p.ledger_account_id
+            WHERE a.wallet_id = $1
+            "#,
+            wallet_id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(balance)
+    }
+
+    /// Lists wallets whose stored balance does not match their postings
+    pub async fn wallet_discrepancies(pool: &PgPool) -> Result<Vec<LedgerDiscrepancy>, LedgerError> {
+        let discrepancies = sqlx::query_as!(
+            LedgerDiscrepancy,
+            r#"
+            SELECT
+                w.id as wallet_id,
+                w.balance as wallet_balance,
+                COALESCE(SUM(p.amount), 0) as "ledger_balance!"
+            FROM wallets w
+            LEFT JOIN ledger_accounts a ON a.wallet_id = w.id
+            LEFT JOIN postings p ON p.ledger_account_id = a.id
+            GROUP BY w.id, w.balance
+            HAVING w.balance <> COALESCE(SUM(p.amount), 0)
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(discrepancies)
+    }
+}
+
+impl JournalEntry {
+    /// Writes a balanced journal entry and its postings
+    pub async fn post(
+        db_tx: &mut Transaction<'_, Postgres>,
+        transaction_id: Option<Uuid>,
+        description: &str,
+        legs: &[PostingLeg],
+    ) -> Result<Self, LedgerError> {
+        Self::validate_legs(legs)?;
+
+        let entry = sqlx::query_as!(
+            JournalEntry,
+            r#"
+            INSERT INTO journal_entries (transaction_id, description)
+            VALUES ($1, $2)
+            RETURNING *
+            "#,
+            transaction_id,
+            description,
+        )
+        .fetch_one(&mut **db_tx)
+        .await?;
+
+        for leg in legs {
+            sqlx::query!(
+                r#"
+                INSERT INTO postings (journal_entry_id, ledger_account_id, amount, currency)
+                VALUES ($1, $2, $3, $4)
+                "#,
+                entry.id,
+                leg.account,
+                leg.amount,
+                leg.currency,
+            )
+            .execute(&mut **db_tx)
+            .await?;
+        }
+
+        Ok(entry)
+    }
+
+    /// Gets the postings belonging to a journal entry
+    pub async fn postings(&self, pool: &PgPool) -> Result<Vec<Posting>, LedgerError> {
+        let postings = sqlx::query_as!(
+            Posting,
+            r#"
+            SELECT * FROM postings WHERE journal_entry_id = $1
+            ORDER BY created_at ASC
+            "#,
+            self.id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(postings)
+    }
+
+    /// Checks that the legs of an entry sum to zero in every currency
+    fn validate_legs(legs: &[PostingLeg]) -> Result<(), LedgerError> {
+        if legs.len() < 2 {
+            return Err(LedgerError::TooFewLegs);
+        }
+
+        let mut sums: HashMap<&str, Decimal> = HashMap::new();
+        for leg in legs {
+            if leg.amount == Decimal::ZERO {
+                return Err(LedgerError::InvalidPosting(
+                    "Posting amount cannot be zero".to_string(),
+                ));
+            }
+            *sums.entry(leg.currency.as_str()).or_insert(Decimal::ZERO) += leg.amount;
+        }
+
+        match sums.into_iter().find(|(_, sum)| *sum != Decimal::ZERO) {
+            Some((currency, sum)) => Err(LedgerError::Unbalanced {
+                currency: currency.to_string(),
+                sum,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leg(amount: i64, currency: &str) -> PostingLeg {
+        PostingLeg {
+            account: Uuid::new_v4(),
+            amount: Decimal::new(amount, 2),
+            currency: currency.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_balanced_legs() {
+        let legs = [leg(-1000, "USD"), leg(990, "USD"), leg(10, "USD")];
+        assert!(JournalEntry::validate_legs(&legs).is_ok());
+
+        // Each currency balances on its own, as in an FX conversion
+        let legs = [leg(-1000, "USD"), leg(1000, "USD"), leg(-250, "KES"), leg(250, "KES")];
+        assert!(JournalEntry::validate_legs(&legs).is_ok());
+    }
+
+    #[test]
+    fn test_too_few_legs() {
+        assert!(matches!(
+            JournalEntry::validate_legs(&[]),
+            Err(LedgerError::TooFewLegs)
+        ));
+        assert!(matches!(
+            JournalEntry::validate_legs(&[leg(1000, "USD")]),
+            Err(LedgerError::TooFewLegs)
+        ));
+    }
+
+    #[test]
+    fn test_zero_leg() {
+        let legs = [leg(-1000, "USD"), leg(1000, "USD"), leg(0, "USD")];
+        assert!(matches!(
+            JournalEntry::validate_legs(&legs),
+            Err(LedgerError::InvalidPosting(_))
+        ));
+    }
+
+    #[test]
+    fn test_unbalanced_legs() {
+        let legs = [leg(-1000, "USD"), leg(900, "USD")];
+        match JournalEntry::validate_legs(&legs) {
+            Err(LedgerError::Unbalanced { currency, sum }) => {
+                assert_eq!(currency, "USD");
+                assert_eq!(sum, Decimal::new(-100, 2));
+            }
+            other => panic!("expected Unbalanced, got {:?}", other),
+        }
+
+        // Balancing across currencies is not balancing
+        let legs = [leg(-1000, "USD"), leg(1000, "KES")];
+        assert!(matches!(
+            JournalEntry::validate_legs(&legs),
+            Err(LedgerError::Unbalanced { .. })
+        ));
+    }
+}
@@ -0,0 +1,455 @@
+use crate::models::{currency::Money, transaction::TransactionType};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveTime, TimeZone, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct KycLimit {
+    pub id: Uuid,
+    pub kyc_level: i32,
+    pub currency: String,
+    /// None caps all limited transaction types together
+    pub transaction_type: Option<TransactionType>,
+    pub per_transaction_max: Option<Decimal>,
+    pub daily_amount_max: Option<Decimal>,
+    pub monthly_amount_max: Option<Decimal>,
+    pub daily_count_max: Option<i32>,
+    pub monthly_count_max: Option<i32>,
+    pub max_balance: Option<Decimal>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum LimitKind {
+    PerTransaction,
+    DailyAmount,
+    MonthlyAmount,
+    DailyCount,
+    MonthlyCount,
+    MaxBalance,
+}
+
+impl LimitKind {
+    fn description(&self) -> &'static str {
+        match self {
+            LimitKind::PerTransaction => "Per-transaction",
+            LimitKind::DailyAmount => "Daily amount",
+            LimitKind::MonthlyAmount => "Monthly amount",
+            LimitKind::DailyCount => "Daily transaction count",
+            LimitKind::MonthlyCount => "Monthly transaction count",
+            LimitKind::MaxBalance => "Maximum balance",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LimitError {
+    #[error("{} limit of {limit} exceeded; {remaining} remaining", kind.description())]
+    Exceeded {
+        kind: LimitKind,
+        limit: String,
+        remaining: String,
+    },
+    #[error("{currency} is not available at KYC level {kyc_level}")]
+    NotConfigured { kyc_level: i32, currency: String },
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// What a user has already moved in the current day and month
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LimitUsage {
+    pub daily_amount: Decimal,
+    pub daily_count: i64,
+    pub monthly_amount: Decimal,
+    pub monthly_count: i64,
+}
+
+/// Usage against one window of a limit
+#[derive(Debug, Serialize)]
+pub struct WindowHeadroom {
+    pub amount_max: Option<Decimal>,
+    pub amount_used: Decimal,
+    pub amount_remaining: Option<Decimal>,
+    pub count_max: Option<i32>,
+    pub count_used: i64,
+    pub count_remaining: Option<i64>,
+    pub resets_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LimitHeadroom {
+    pub transaction_type: Option<TransactionType>,
+    pub per_transaction_max: Option<Decimal>,
+    pub daily: WindowHeadroom,
+    pub monthly: WindowHeadroom,
+}
+
+/// Every limit that applies to a user in a currency, with what is left
+#[derive(Debug, Serialize)]
+pub struct LimitStatus {
+    pub kyc_level: i32,
+    pub currency: String,
+    pub limits: Vec<LimitHeadroom>,
+    /// Total across the user's open wallets in the currency
+    pub balance: Decimal,
+    pub max_balance: Option<Decimal>,
+    pub balance_remaining: Option<Decimal>,
+}
+
+/// Transaction types that count towards limits. Fees, refunds and payouts
+/// of money already sent are never limited.
+pub fn is_limited(transaction_type: &TransactionType) -> bool {
+    matches!(transaction_type, TransactionType::Transfer | TransactionType::Withdrawal)
+}
+
+/// Start of the current UTC day and month
+fn window_starts(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let today = now.date_naive();
+    let day_start = Utc.from_utc_datetime(&today.and_time(NaiveTime::MIN));
+    let month_start = Utc.from_utc_datetime(
+        &today
+            .with_day(1)
+            .expect("every month has a first day")
+            .and_time(NaiveTime::MIN),
+    );
+
+    (day_start, month_start)
+}
+
+impl KycLimit {
+    /// Limits for a KYC level and currency; with a transaction type, only
+    /// that type's row and the overall row. A level with no rows of its own
+    /// for the currency gets those of the highest level below it.
+    pub async fn find_applicable<'e, E: sqlx::PgExecutor<'e>>(
+        executor: E,
+        kyc_level: i32,
+        currency: &str,
+        transaction_type: Option<&TransactionType>,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            KycLimit,
+            r#"
+            SELECT * FROM kyc_limits
+            WHERE active
+              AND currency = $2
+              AND kyc_level = (
+                  SELECT MAX(kyc_level) FROM kyc_limits
+                  WHERE active AND currency = $2 AND kyc_level <= $1
+              )
+              AND ($3::VARCHAR IS NULL OR transaction_type IS NULL OR transaction_type = $3)
+            ORDER BY transaction_type NULLS FIRST
+            "#,
+            kyc_level,
+            currency,
+            transaction_type.cloned() as Option<TransactionType>,
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Sums the user's limited outgoing transactions this month, and this
+    /// day, optionally for one type only. Failed transactions do not count.
+    pub async fn usage<'e, E: sqlx::PgExecutor<'e>>(
+        executor: E,
+        user_id: Uuid,
+        currency: &str,
+        transaction_type: Option<&TransactionType>,
+        now: DateTime<Utc>,
+    ) -> Result<LimitUsage, sqlx::Error> {
+        let (day_start, month_start) = window_starts(now);
+
+        let usage = sqlx::query_as!(
+            LimitUsage,
+            r#"
+            SELECT
+                COALESCE(SUM(t.amount) FILTER (WHERE t.created_at >= $4), 0) AS "daily_amount!",
+                COUNT(*) FILTER (WHERE t.created_at >= $4) AS "daily_count!",
+                COALESCE(SUM(t.amount), 0) AS "monthly_amount!",
+                COUNT(*) AS "monthly_count!"
+            FROM transactions t
+            JOIN wallets w ON w.id = t.debit_wallet_id
+            WHERE w.user_id = $1
+              AND t.currency = $2
+              AND t.transaction_type IN ('transfer', 'withdrawal')
+              AND ($3::VARCHAR IS NULL OR t.transaction_type = $3)
+              AND t.parent_transaction_id IS NULL
+              AND t.status <> 'failed'
+              AND t.created_at >= $5
+            "#,
+            user_id,
+            currency,
+            transaction_type.cloned() as Option<TransactionType>,
+            day_start,
+            month_start
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(usage)
+    }
+
+    /// Checks that the user can send `amount` more. The caller must hold the
+    /// user's row lock (`Wallet::lock_owner`) so concurrent transactions are
+    /// checked one after another. A currency with no limits at or below the
+    /// KYC level may not be sent at all, so a missing row never means
+    /// "unlimited".
+    pub async fn enforce_outgoing(
+        db_tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        kyc_level: i32,
+        transaction_type: &TransactionType,
+        amount: &Money,
+    ) -> Result<(), LimitError> {
+        if !is_limited(transaction_type) {
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let limits =
+            Self::find_applicable(&mut **db_tx, kyc_level, &amount.currency, Some(transaction_type)).await?;
+        if limits.is_empty() {
+            return Err(LimitError::NotConfigured {
+                kyc_level,
+                currency: amount.currency.clone(),
+            });
+        }
+
+        for limit in limits {
+            let usage = Self::usage(
+                &mut **db_tx,
+                user_id,
+                &amount.currency,
+                limit.transaction_type.as_ref(),
+                now,
+            )
+            .await?;
+            limit.check(amount.amount, &usage)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that receiving `amount` keeps the user under their maximum
+    /// balance. The caller must hold the user's row lock. As with outgoing
+    /// limits, a currency with no limits at or below the KYC level is refused.
+    pub async fn enforce_balance(
+        db_tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        kyc_level: i32,
+        amount: &Money,
+    ) -> Result<(), LimitError> {
+        let limits = Self::find_applicable(&mut **db_tx, kyc_level, &amount.currency, None).await?;
+        if limits.is_empty() {
+            return Err(LimitError::NotConfigured {
+                kyc_level,
+                currency: amount.currency.clone(),
+            });
+        }
+
+        let Some(max_balance) = limits.iter().find_map(|limit| limit.max_balance) else {
+            return Ok(());
+        };
+
+        let balance = Self::balance(&mut **db_tx, user_id, &amount.currency).await?;
+        if balance + amount.amount > max_balance {
+            return Err(LimitError::Exceeded {
+                kind: LimitKind::MaxBalance,
+                limit: Money::from_stored(max_balance, &amount.currency).to_string(),
+                remaining: Money::from_stored(
+                    (max_balance - balance).max(Decimal::ZERO),
+                    &amount.currency,
+                )
+                .to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Reports usage and headroom for every limit on the user in a currency
+    pub async fn status(
+        pool: &sqlx::PgPool,
+        user_id: Uuid,
+        kyc_level: i32,
+        currency: &str,
+    ) -> Result<LimitStatus, LimitError> {
+        let now = Utc::now();
+        let (day_start, month_start) = window_starts(now);
+        let day_reset = day_start + Duration::days(1);
+        let month_reset = month_start
+            .checked_add_months(Months::new(1))
+            .expect("next month is representable");
+
+        let limits = Self::find_applicable(pool, kyc_level, currency, None).await?;
+
+        let mut headroom = Vec::with_capacity(limits.len());
+        for limit in &limits {
+            let usage = Self::usage(pool, user_id, currency, limit.transaction_type.as_ref(), now).await?;
+            headroom.push(LimitHeadroom {
+                transaction_type: limit.transaction_type.clone(),
+                per_transaction_max: limit.per_transaction_max,
+                daily: WindowHeadroom {
+                    amount_max: limit.daily_amount_max,
+                    amount_used: usage.daily_amount,
+                    amount_remaining: limit
+                        .daily_amount_max
+                        .map(|max| (max - usage.daily_amount).max(Decimal::ZERO)),
+                    count_max: limit.daily_count_max,
+                    count_used: usage.daily_count,
+                    count_remaining: limit
+                        .daily_count_max
+                        .map(|max| (max as i64 - usage.daily_count).max(0)),
+                    resets_at: day_reset,
+                },
+                monthly: WindowHeadroom {
+                    amount_max: limit.monthly_amount_max,
+                    amount_used: usage.monthly_amount,
+                    amount_remaining: limit
+                        .monthly_amount_max
+                        .map(|max| (max - usage.monthly_amount).max(Decimal::ZERO)),
+                    count_max: limit.monthly_count_max,
+                    count_used: usage.monthly_count,
+                    count_remaining: limit
+                        .monthly_count_max
+                        .map(|max| (max as i64 - usage.monthly_count).max(0)),
+                    resets_at: month_reset,
+                },
+            });
+        }
+
+        let balance = Self::balance(pool, user_id, currency).await?;
+        let max_balance = limits.iter().find_map(|limit| limit.max_balance);
+
+        Ok(LimitStatus {
+            kyc_level,
+            currency: currency.to_string(),
+            limits: headroom,
+            balance,
+            max_balance,
+            balance_remaining: max_balance.map(|max| (max - balance).max(Decimal::ZERO)),
+        })
+    }
+
+    /// Checks one more transaction of `amount` against this limit's caps
+    pub fn check(&self, amount: Decimal, usage: &LimitUsage) -> Result<(), LimitError> {
+        let money = |value: Decimal| Money::from_stored(value, &self.currency).to_string();
+        let amount_cap = |kind, max: Option<Decimal>, used: Decimal| match max {
+            Some(max) if used + amount > max => Err(LimitError::Exceeded {
+                kind,
+                limit: money(max),
+                remaining: money((max - used).max(Decimal::ZERO)),
+            }),
+            _ => Ok(()),
+        };
+        let count_cap = |kind, max: Option<i32>, used: i64| match max {
+            Some(max) if used + 1 > max as i64 => Err(LimitError::Exceeded {
+                kind,
+                limit: format!("{} transactions", max),
+                remaining: format!("{} transactions", (max as i64 - used).max(0)),
+            }),
+            _ => Ok(()),
+        };
+
+        amount_cap(LimitKind::PerTransaction, self.per_transaction_max, Decimal::ZERO)?;
+        amount_cap(LimitKind::DailyAmount, self.daily_amount_max, usage.daily_amount)?;
+        amount_cap(LimitKind::MonthlyAmount, self.monthly_amount_max, usage.monthly_amount)?;
+        count_cap(LimitKind::DailyCount, self.daily_count_max, usage.daily_count)?;
+        count_cap(LimitKind::MonthlyCount, self.monthly_count_max, usage.monthly_count)?;
+
+        Ok(())
+    }
+
+    /// Total balance across the user's open wallets in a currency
+    async fn balance<'e, E: sqlx::PgExecutor<'e>>(
+        executor: E,
+        user_id: Uuid,
+        currency: &str,
+    ) -> Result<Decimal, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(balance), 0) AS "balance!"
+            FROM wallets
+            WHERE user_id = $1 AND currency = $2 AND status <> 'closed'
+            "#,
+            user_id,
+            currency
+        )
+        .fetch_one(executor)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit() -> KycLimit {
+        KycLimit {
+            id: Uuid::new_v4(),
+            kyc_level: 1,
+            currency: "USD".to_string(),
+            transaction_type: None,
+            per_transaction_max: Some(Decimal::new(1000, 0)),
+            daily_amount_max: Some(Decimal::new(2000, 0)),
+            monthly_amount_max: Some(Decimal::new(10000, 0)),
+            daily_count_max: Some(3),
+            monthly_count_max: None,
+            max_balance: None,
+            active: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn exceeded_kind(result: Result<(), LimitError>) -> Option<LimitKind> {
+        match result {
+            Err(LimitError::Exceeded { kind, .. }) => Some(kind),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_amount_caps() {
+        let limit = limit();
+        let mut usage = LimitUsage::default();
+        assert!(limit.check(Decimal::new(1000, 0), &usage).is_ok());
+        assert_eq!(
+            exceeded_kind(limit.check(Decimal::new(1001, 0), &usage)),
+            Some(LimitKind::PerTransaction)
+        );
+
+        usage.daily_amount = Decimal::new(1500, 0);
+        assert!(limit.check(Decimal::new(500, 0), &usage).is_ok());
+        assert_eq!(
+            exceeded_kind(limit.check(Decimal::new(50001, 2), &usage)),
+            Some(LimitKind::DailyAmount)
+        );
+    }
+
+    #[test]
+    fn test_count_caps() {
+        let limit = limit();
+        let usage = LimitUsage {
+            daily_count: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            exceeded_kind(limit.check(Decimal::ONE, &usage)),
+            Some(LimitKind::DailyCount)
+        );
+    }
+
+    #[test]
+    fn test_window_starts() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 14, 15, 9, 26).unwrap();
+        let (day, month) = window_starts(now);
+        assert_eq!(day, Utc.with_ymd_and_hms(2025, 3, 14, 0, 0, 0).unwrap());
+        assert_eq!(month, Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap());
+    }
+}
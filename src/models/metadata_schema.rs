@@ -0,0 +1,241 @@
+use crate::models::transaction::TransactionType;
+use chrono::{DateTime, Utc};
+use jsonschema::{Draft, JSONSchema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Transaction types that carry a metadata schema
+pub const SCHEMA_TRANSACTION_TYPES: [TransactionType; 6] = [
+    TransactionType::Deposit,
+    TransactionType::Withdrawal,
+    TransactionType::Transfer,
+    TransactionType::Fee,
+    TransactionType::Refund,
+    TransactionType::Conversion,
+];
+
+/// One version of the JSON Schema that a transaction type's metadata must
+/// match. Only the active version is enforced.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MetadataSchema {
+    pub id: Uuid,
+    pub transaction_type: TransactionType,
+    pub version: i32,
+    pub schema: Value,
+    pub active: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Error)]
+pub enum MetadataSchemaError {
+    #[error("Invalid metadata schema: {0}")]
+    InvalidSchema(String),
+    #[error("Metadata schema not found")]
+    NotFound,
+    #[error("Another version of this schema was created at the same time")]
+    VersionConflict,
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// The schema file shipped with the service, used as version 1
+pub fn bundled_schema(transaction_type: &TransactionType) -> &'static str {
+    match transaction_type {
+        TransactionType::Deposit => include_str!("../schemas/metadata/deposit.json"),
+        TransactionType::Withdrawal => include_str!("../schemas/metadata/withdrawal.json"),
+        TransactionType::Transfer => include_str!("../schemas/metadata/transfer.json"),
+        TransactionType::Fee => include_str!("../schemas/metadata/fee.json"),
+        TransactionType::Refund => include_str!("../schemas/metadata/refund.json"),
+        TransactionType::Conversion => include_str!("../schemas/metadata/conversion.json"),
+    }
+}
+
+/// Compiles a schema as Draft 7. Remote `$ref`s are not resolved.
+pub fn compile(schema: &Value) -> Result<JSONSchema, MetadataSchemaError> {
+    JSONSchema::options()
+        .with_draft(Draft::Draft7)
+        .compile(schema)
+        .map_err(|e| MetadataSchemaError::InvalidSchema(e.to_string()))
+}
+
+impl MetadataSchema {
+    /// Stores the bundled schema as the active version 1 of every
+    /// transaction type that has no versions yet
+    pub async fn seed_bundled(pool: &PgPool) -> Result<u64, MetadataSchemaError> {
+        let mut seeded = 0;
+        for transaction_type in SCHEMA_TRANSACTION_TYPES {
+            let schema: Value = serde_json::from_str(bundled_schema(&transaction_type))
+                .map_err(|e| MetadataSchemaError::InvalidSchema(e.to_string()))?;
+            compile(&schema)?;
+
+            let result = sqlx::query!(
+                r#"
+                INSERT INTO metadata_schemas (transaction_type, version, schema, active)
+                SELECT $1, 1, $2, true
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM metadata_schemas WHERE transaction_type = $1
+                )
+                ON CONFLICT DO NOTHING
+                "#,
+                transaction_type as TransactionType,
+                schema
+            )
+            .execute(pool)
+            .await?;
+            seeded += result.rows_affected();
+        }
+
+        Ok(seeded)
+    }
+
+    pub async fn find_all(pool: &PgPool) -> Result<Vec<Self>, MetadataSchemaError> {
+        let schemas = sqlx::query_as!(
+            MetadataSchema,
+            r#"
+            SELECT * FROM metadata_schemas
+            ORDER BY transaction_type, version DESC
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(schemas)
+    }
+
+    pub async fn find_active(pool: &PgPool) -> Result<Vec<Self>, MetadataSchemaError> {
+        let schemas = sqlx::query_as!(
+            MetadataSchema,
+            r#"
+            SELECT * FROM metadata_schemas
+            WHERE active
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(schemas)
+    }
+
+    /// Adds the next version of a transaction type's schema, inactive
+    pub async fn create(
+        pool: &PgPool,
+        transaction_type: TransactionType,
+        schema: Value,
+        created_by: Uuid,
+    ) -> Result<Self, MetadataSchemaError> {
+        compile(&schema)?;
+
+        let mut db_tx = pool.begin().await?;
+
+        // Concurrent creates for one type would otherwise read the same
+        // MAX(version); the lock is released when the transaction ends
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind(format!("metadata_schemas:{}", transaction_type.as_str()))
+            .execute(&mut *db_tx)
+            .await?;
+
+        let created = sqlx::query_as!(
+            MetadataSchema,
+            r#"
+            INSERT INTO metadata_schemas (transaction_type, version, schema, created_by)
+            SELECT $1, COALESCE(MAX(version), 0) + 1, $2, $3
+            FROM metadata_schemas
+            WHERE transaction_type = $1
+            RETURNING *
+            "#,
+            transaction_type as TransactionType,
+            schema,
+            created_by
+        )
+        .fetch_one(&mut *db_tx)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+                MetadataSchemaError::VersionConflict
+            }
+            _ => MetadataSchemaError::DatabaseError(e),
+        })?;
+
+        db_tx.commit().await?;
+
+        Ok(created)
+    }
+
+    /// Makes a version the one enforced for its transaction type
+    pub async fn activate(pool: &PgPool, id: Uuid) -> Result<Self, MetadataSchemaError> {
+        let mut db_tx = pool.begin().await?;
+
+        let schema = sqlx::query_as!(
+            MetadataSchema,
+            r#"
+            SELECT * FROM metadata_schemas
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+            id
+        )
+        .fetch_optional(&mut *db_tx)
+        .await?
+        .ok_or(MetadataSchemaError::NotFound)?;
+
+        // Stored schemas were checked on the way in, but the compiler may
+        // have become stricter since
+        compile(&schema.schema)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE metadata_schemas SET active = false
+            WHERE transaction_type = $1 AND active
+            "#,
+            schema.transaction_type.clone() as TransactionType
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        let activated = sqlx::query_as!(
+            MetadataSchema,
+            r#"
+            UPDATE metadata_schemas SET active = true
+            WHERE id = $1
+            RETURNING *
+            "#,
+            id
+        )
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+
+        Ok(activated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_schemas_compile() {
+        for transaction_type in SCHEMA_TRANSACTION_TYPES {
+            let schema: Value = serde_json::from_str(bundled_schema(&transaction_type)).unwrap();
+            assert!(compile(&schema).is_ok(), "{:?}", transaction_type);
+        }
+    }
+
+    #[test]
+    fn test_withdrawal_requires_bank_details() {
+        let schema: Value =
+            serde_json::from_str(bundled_schema(&TransactionType::Withdrawal)).unwrap();
+        let compiled = compile(&schema).unwrap();
+
+        assert!(!compiled.is_valid(&serde_json::json!({ "narration": "Rent" })));
+        assert!(compiled.is_valid(&serde_json::json!({
+            "bank_reference": "FT2504011234",
+            "narration": "Rent",
+        })));
+    }
+}
@@ -3,3 +3,23 @@ pub mod wallet;
 pub mod transaction;
 pub mod reserve;
 pub mod audit;
+pub mod ledger;
+pub mod idempotency;
+pub mod hold;
+pub mod fee;
+pub mod limit;
+pub mod statement;
+pub mod pagination;
+pub mod metadata_schema;
+pub mod fx;
+pub mod currency;
+pub mod schedule;
+pub mod recipient;
+pub mod pending_transfer;
+pub mod two_factor;
+pub mod session;
+pub mod email_verification;
+pub mod password_reset;
+
+#[cfg(test)]
+pub mod testing;
@@ -0,0 +1,238 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, Postgres, QueryBuilder};
+use thiserror::Error;
+use uuid::Uuid;
+
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+#[derive(Debug, Error)]
+pub enum PaginationError {
+    #[error("Invalid cursor")]
+    InvalidCursor,
+    #[error("Page numbers start at 1")]
+    InvalidPage,
+    #[error("Use either a cursor or a page number, not both")]
+    ConflictingModes,
+}
+
+/// Position of a row in a listing. Listings are ordered by
+/// `(created_at, id)`, which is unique, so a cursor marks an exact place
+/// that rows inserted later cannot shift.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    /// Opaque token handed to clients
+    pub fn encode(&self) -> String {
+        // Postgres keeps microseconds, so nothing is lost on the way back
+        let raw = format!("{}:{}", self.created_at.timestamp_micros(), self.id);
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    pub fn decode(token: &str) -> Result<Self, PaginationError> {
+        let raw = URL_SAFE_NO_PAD
+            .decode(token)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or(PaginationError::InvalidCursor)?;
+        let (micros, id) = raw.split_once(':').ok_or(PaginationError::InvalidCursor)?;
+
+        let created_at = micros
+            .parse::<i64>()
+            .ok()
+            .and_then(DateTime::<Utc>::from_timestamp_micros)
+            .ok_or(PaginationError::InvalidCursor)?;
+        let id = Uuid::parse_str(id).map_err(|_| PaginationError::InvalidCursor)?;
+
+        Ok(Self { created_at, id })
+    }
+}
+
+/// Rows that can be listed with keyset pagination
+pub trait Keyset {
+    fn cursor(&self) -> Cursor;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PageMode {
+    /// Rows after the cursor, or from the newest row without one
+    Cursor(Option<Cursor>),
+    /// A numbered page, with a total count. Kept for the admin UI; the
+    /// count and offset make it slower on large tables.
+    Number(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageRequest {
+    pub mode: PageMode,
+    pub limit: u32,
+}
+
+impl PageRequest {
+    /// Builds a request from the raw query parameters. A page number selects
+    /// page mode; otherwise the listing is cursor based.
+    pub fn new(
+        cursor: Option<&str>,
+        page: Option<u32>,
+        limit: Option<u32>,
+    ) -> Result<Self, PaginationError> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let mode = match (cursor, page) {
+            (Some(_), Some(_)) => return Err(PaginationError::ConflictingModes),
+            (_, Some(0)) => return Err(PaginationError::InvalidPage),
+            (_, Some(page)) => PageMode::Number(page),
+            (Some(token), None) => PageMode::Cursor(Some(Cursor::decode(token)?)),
+            (None, None) => PageMode::Cursor(None),
+        };
+
+        Ok(Self { mode, limit })
+    }
+
+    pub fn is_numbered(&self) -> bool {
+        matches!(self.mode, PageMode::Number(_))
+    }
+
+    /// Appends the keyset condition, ordering and limit to a query whose
+    /// WHERE clause is already open, for a newest-first listing
+    pub fn push_to(&self, query: &mut QueryBuilder<'_, Postgres>, table: &str) {
+        self.push_sorted(query, table, true);
+    }
+
+    /// As `push_to`, in either direction of `(created_at, id)`
+    pub fn push_sorted(
+        &self,
+        query: &mut QueryBuilder<'_, Postgres>,
+        table: &str,
+        descending: bool,
+    ) {
+        let (comparison, direction) = if descending { ("<", "DESC") } else { (">", "ASC") };
+
+        if let PageMode::Cursor(Some(cursor)) = self.mode {
+            query.push(format!(" AND ({0}.created_at, {0}.id) {1} (", table, comparison));
+            query.push_bind(cursor.created_at);
+            query.push(", ");
+            query.push_bind(cursor.id);
+            query.push(")");
+        }
+
+        query.push(format!(
+            " ORDER BY {0}.created_at {1}, {0}.id {1}",
+            table, direction
+        ));
+        self.push_limit(query);
+    }
+
+    /// Appends the limit, and the offset in page mode. One extra row is
+    /// fetched so that `Page::from_rows` can tell whether another page
+    /// follows.
+    pub fn push_limit(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        query.push(" LIMIT ");
+        query.push_bind(self.limit as i64 + 1);
+
+        if let PageMode::Number(page) = self.mode {
+            query.push(" OFFSET ");
+            query.push_bind((page as i64 - 1) * self.limit as i64);
+        }
+    }
+
+    /// Total rows matching a listing's filters, counted in page mode only.
+    /// `push_filters` is the same closure that opened the listing's WHERE
+    /// clause.
+    pub async fn count<'e, E>(
+        &self,
+        executor: E,
+        table: &str,
+        push_filters: impl Fn(&mut QueryBuilder<'_, Postgres>),
+    ) -> Result<Option<i64>, sqlx::Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        if !self.is_numbered() {
+            return Ok(None);
+        }
+
+        let mut query = QueryBuilder::new(format!("SELECT COUNT(*) FROM {}", table));
+        push_filters(&mut query);
+        let total = query.build_query_scalar::<i64>().fetch_one(executor).await?;
+
+        Ok(Some(total))
+    }
+}
+
+/// One page of a listing
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Token for the following page, in cursor mode
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    /// Total matching rows, counted in page mode only
+    pub total: Option<i64>,
+}
+
+impl<T: Keyset> Page<T> {
+    /// Trims the extra row fetched by `PageRequest::push_limit`
+    pub fn from_rows(mut rows: Vec<T>, request: &PageRequest, total: Option<i64>) -> Self {
+        let has_more = rows.len() > request.limit as usize;
+        rows.truncate(request.limit as usize);
+
+        let next_cursor = if has_more && !request.is_numbered() {
+            rows.last().map(|row| row.cursor().encode())
+        } else {
+            None
+        };
+
+        Self {
+            items: rows,
+            next_cursor,
+            has_more,
+            total,
+        }
+    }
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            has_more: self.has_more,
+            total: self.total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor {
+            created_at: DateTime::<Utc>::from_timestamp_micros(1_743_465_600_123_456).unwrap(),
+            id: Uuid::new_v4(),
+        };
+
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(Cursor::decode("not-a-cursor").is_err());
+    }
+
+    #[test]
+    fn test_page_request_modes() {
+        let request = PageRequest::new(None, None, Some(500)).unwrap();
+        assert_eq!(request.mode, PageMode::Cursor(None));
+        assert_eq!(request.limit, MAX_PAGE_SIZE);
+
+        assert_eq!(
+            PageRequest::new(None, Some(3), None).unwrap().mode,
+            PageMode::Number(3)
+        );
+        assert!(PageRequest::new(None, Some(0), None).is_err());
+        assert!(PageRequest::new(Some("abc"), Some(1), None).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use crate::models::session::{Session, SessionError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use thiserror::Error;
+use uuid::Uuid;
+
+const DEFAULT_TTL_MINUTES: i64 = 30;
+/// At most this many reset emails per user per hour
+pub const MAX_REQUESTS_PER_HOUR: i64 = 3;
+
+/// A single-use token allowing the holder to set a new password. Only the
+/// token's hash is stored.
+#[derive(Debug, sqlx::FromRow)]
+pub struct PasswordResetToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Error)]
+pub enum PasswordResetError {
+    #[error("Invalid or expired reset token")]
+    InvalidToken,
+    #[error("Session error: {0}")]
+    SessionError(#[from] SessionError),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl PasswordResetToken {
+    /// How long a token is valid, from PASSWORD_RESET_TTL_MINUTES
+    pub fn ttl() -> Duration {
+        let minutes = std::env::var("PASSWORD_RESET_TTL_MINUTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_TTL_MINUTES);
+        Duration::minutes(minutes)
+    }
+
+    /// Issues a token for the user, returning the plain token for the email,
+    /// or `None` if the user has asked too often. Callers must not reveal
+    /// which, since that would confirm the account exists.
+    pub async fn issue(pool: &PgPool, user_id: Uuid) -> Result<Option<String>, PasswordResetError> {
+        let mut db_tx = pool.begin().await?;
+
+        // Serializes concurrent requests for the user
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user_id)
+            .fetch_one(&mut *db_tx)
+            .await?;
+
+        let recent = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!" FROM password_reset_tokens
+            WHERE user_id = $1 AND created_at > CURRENT_TIMESTAMP - INTERVAL '1 hour'
+            "#,
+            user_id
+        )
+        .fetch_one(&mut *db_tx)
+        .await?;
+        if recent >= MAX_REQUESTS_PER_HOUR {
+            return Ok(None);
+        }
+
+        let token = generate_token();
+        sqlx::query!(
+            r#"
+            INSERT INTO password_reset_tokens (user_id, token_hash, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            user_id,
+            hash_token(&token),
+            Utc::now() + Self::ttl()
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+
+        Ok(Some(token))
+    }
+
+    /// The user a still-valid token belongs to, without using it up, so the
+    /// new password can be checked against their account first
+    pub async fn find_user_id(pool: &PgPool, token: &str) -> Result<Uuid, PasswordResetError> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT user_id FROM password_reset_tokens
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+            "#,
+            hash_token(token)
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or(PasswordResetError::InvalidToken)
+    }
+
+    /// Uses up a token, replaces the user's password hash and revokes all
+    /// the user's sessions, returning the user's id. Every other outstanding
+    /// token for the user is spent too. All of it commits together, so a
+    /// reset never leaves the old sessions alive.
+    pub async fn consume(
+        pool: &PgPool,
+        token: &str,
+        password_hash: &str,
+    ) -> Result<Uuid, PasswordResetError> {
+        let mut db_tx = pool.begin().await?;
+
+        let user_id = sqlx::query_scalar!(
+            r#"
+            UPDATE password_reset_tokens SET used_at = CURRENT_TIMESTAMP
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+            RETURNING user_id
+            "#,
+            hash_token(token)
+        )
+        .fetch_optional(&mut *db_tx)
+        .await?
+        .ok_or(PasswordResetError::InvalidToken)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users SET password_hash = $2, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            "#,
+            user_id,
+            password_hash
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE password_reset_tokens SET used_at = CURRENT_TIMESTAMP
+            WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        // Whoever knew the old password may hold a session; end them all
+        Session::revoke_all(&mut *db_tx, user_id, "password_reset").await?;
+
+        db_tx.commit().await?;
+
+        Ok(user_id)
+    }
+}
+
+/// 256 random bits, URL safe so it can go in a link
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.trim().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        session::ClientInfo,
+        testing::{create_user, PASSWORD_HASH},
+        user::User,
+    };
+
+    async fn user_id(pool: &PgPool) -> Uuid {
+        create_user(pool, "reset@example.com").await.id
+    }
+
+    #[sqlx::test]
+    async fn test_issue_is_rate_limited(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+
+        for _ in 0..MAX_REQUESTS_PER_HOUR {
+            assert!(PasswordResetToken::issue(&pool, user_id).await.unwrap().is_some());
+        }
+        assert!(PasswordResetToken::issue(&pool, user_id).await.unwrap().is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_token_is_single_use(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+        let token = PasswordResetToken::issue(&pool, user_id).await.unwrap().unwrap();
+
+        assert_eq!(PasswordResetToken::find_user_id(&pool, &token).await.unwrap(), user_id);
+        assert_eq!(
+            PasswordResetToken::consume(&pool, &token, "new-hash").await.unwrap(),
+            user_id
+        );
+        let user = User::find_by_id(&pool, user_id).await.unwrap().unwrap();
+        assert_eq!(user.password_hash, "new-hash");
+
+        assert!(matches!(
+            PasswordResetToken::consume(&pool, &token, "other-hash").await,
+            Err(PasswordResetError::InvalidToken)
+        ));
+        assert!(matches!(
+            PasswordResetToken::find_user_id(&pool, &token).await,
+            Err(PasswordResetError::InvalidToken)
+        ));
+    }
+
+    #[sqlx::test]
+    async fn test_expired_token_is_refused(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+        let token = PasswordResetToken::issue(&pool, user_id).await.unwrap().unwrap();
+
+        sqlx::query!(
+            r#"
+            UPDATE password_reset_tokens SET expires_at = CURRENT_TIMESTAMP - INTERVAL '1 minute'
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            PasswordResetToken::find_user_id(&pool, &token).await,
+            Err(PasswordResetError::InvalidToken)
+        ));
+        assert!(matches!(
+            PasswordResetToken::consume(&pool, &token, "new-hash").await,
+            Err(PasswordResetError::InvalidToken)
+        ));
+        let user = User::find_by_id(&pool, user_id).await.unwrap().unwrap();
+        assert_eq!(user.password_hash, PASSWORD_HASH);
+    }
+
+    #[sqlx::test]
+    async fn test_consume_spends_siblings_and_revokes_sessions(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+        let first = PasswordResetToken::issue(&pool, user_id).await.unwrap().unwrap();
+        let second = PasswordResetToken::issue(&pool, user_id).await.unwrap().unwrap();
+        let client = ClientInfo {
+            device_name: None,
+            ip_address: "127.0.0.1".to_string(),
+            user_agent: "test".to_string(),
+        };
+        let (session, _) = Session::create(&pool, user_id, &client).await.unwrap();
+
+        PasswordResetToken::consume(&pool, &first, "new-hash").await.unwrap();
+
+        assert!(matches!(
+            PasswordResetToken::consume(&pool, &second, "other-hash").await,
+            Err(PasswordResetError::InvalidToken)
+        ));
+        let revoked_reason = sqlx::query_scalar!(
+            "SELECT revoked_reason FROM sessions WHERE id = $1",
+            session.id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(revoked_reason.as_deref(), Some("password_reset"));
+    }
+}
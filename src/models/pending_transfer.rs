@@ -0,0 +1,340 @@
+use crate::models::{
+    currency::{Money, MoneyError},
+    recipient::{Recipient, RecipientType},
+    transaction::{Transaction as WalletTransaction, TransactionError, TransactionType},
+    user::User,
+};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use thiserror::Error;
+use uuid::Uuid;
+
+const DEFAULT_CLAIM_DAYS: i64 = 7;
+
+/// A P2P transfer to someone without an account, held in escrow until they
+/// register and claim it or it expires and goes back to the sender
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PendingTransfer {
+    pub id: Uuid,
+    pub sender_id: Uuid,
+    pub debit_wallet_id: Uuid,
+    pub transaction_id: Uuid,
+    pub recipient_type: RecipientType,
+    pub recipient: String,
+    pub amount: Decimal,
+    pub currency: String,
+    pub note: Option<String>,
+    pub status: PendingTransferStatus,
+    pub claimed_by: Option<Uuid>,
+    pub claim_transaction_id: Option<Uuid>,
+    pub refund_transaction_id: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+pub enum PendingTransferStatus {
+    Pending,
+    Claimed,
+    Refunded,
+}
+
+#[derive(Debug, Error)]
+pub enum PendingTransferError {
+    #[error("Pending transfer not found")]
+    NotFound,
+    #[error("Pending transfer is no longer claimable")]
+    NotClaimable,
+    #[error("Pending transfer was sent to someone else")]
+    NotRecipient,
+    #[error("Money error: {0}")]
+    MoneyError(#[from] MoneyError),
+    #[error("Transaction error: {0}")]
+    TransactionError(#[from] TransactionError),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// How long an unregistered recipient has to claim a transfer, from
+/// `P2P_CLAIM_DAYS`
+pub fn claim_window() -> Duration {
+    let days = std::env::var("P2P_CLAIM_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(DEFAULT_CLAIM_DAYS);
+
+    Duration::days(days)
+}
+
+impl PendingTransfer {
+    /// Debits the sender and parks the money in escrow for `recipient`. The
+    /// escrow transfer has no credit wallet, so it settles into suspense.
+    pub async fn send(
+        pool: &PgPool,
+        sender_id: Uuid,
+        debit_wallet_id: Uuid,
+        recipient: &Recipient,
+        amount: &Money,
+        note: Option<String>,
+    ) -> Result<Self, PendingTransferError> {
+        let mut db_tx = pool.begin().await?;
+
+        let escrow = WalletTransaction::create_in(
+            &mut db_tx,
+            Some(debit_wallet_id),
+            None,
+            amount,
+            TransactionType::Transfer,
+            None,
+            Some(serde_json::json!({
+                "p2p_recipient_type": recipient.recipient_type,
+                "p2p_recipient": recipient.value,
+                "note": note,
+            })),
+            None,
+        )
+        .await?;
+
+        let pending = sqlx::query_as!(
+            PendingTransfer,
+            r#"
+            INSERT INTO pending_transfers (
+                sender_id, debit_wallet_id, transaction_id, recipient_type,
+                recipient, amount, currency, note, expires_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING *
+            "#,
+            sender_id,
+            debit_wallet_id,
+            escrow.id,
+            recipient.recipient_type.clone() as RecipientType,
+            recipient.value,
+            amount.amount,
+            amount.currency,
+            note,
+            Utc::now() + claim_window()
+        )
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+
+        Ok(pending)
+    }
+
+    pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, PendingTransferError> {
+        let pending = sqlx::query_as!(
+            PendingTransfer,
+            r#"
+            SELECT * FROM pending_transfers WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(pending)
+    }
+
+    /// Transfers a user has sent that went into escrow
+    pub async fn find_by_sender(pool: &PgPool, sender_id: Uuid) -> Result<Vec<Self>, PendingTransferError> {
+        let pending = sqlx::query_as!(
+            PendingTransfer,
+            r#"
+            SELECT * FROM pending_transfers
+            WHERE sender_id = $1
+            ORDER BY created_at DESC
+            "#,
+            sender_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(pending)
+    }
+
+    /// Unexpired transfers waiting for the user's email or verified phone
+    /// number
+    pub async fn find_claimable(pool: &PgPool, user: &User) -> Result<Vec<Self>, PendingTransferError> {
+        let pending = sqlx::query_as!(
+            PendingTransfer,
+            r#"
+            SELECT * FROM pending_transfers
+            WHERE status = 'pending'
+              AND expires_at > CURRENT_TIMESTAMP
+              AND ((recipient_type = 'email' AND recipient = lower($1))
+                OR (recipient_type = 'phone' AND recipient = $2))
+            ORDER BY created_at
+            "#,
+            user.email,
+            user.verified_phone()
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(pending)
+    }
+
+    pub fn money(&self) -> Money {
+        Money::from_stored(self.amount, &self.currency)
+    }
+
+    /// Whether the transfer was addressed to this user. Transfers sent to a
+    /// phone number are only theirs once they have verified it.
+    pub fn is_for(&self, user: &User) -> bool {
+        match self.recipient_type {
+            RecipientType::Email => self.recipient == user.email.to_lowercase(),
+            RecipientType::Phone => user.verified_phone() == Some(self.recipient.as_str()),
+        }
+    }
+
+    /// Pays the escrowed money into one of the recipient's wallets
+    pub async fn claim(
+        pool: &PgPool,
+        id: Uuid,
+        user: &User,
+        credit_wallet_id: Uuid,
+    ) -> Result<(Self, WalletTransaction), PendingTransferError> {
+        let mut db_tx = pool.begin().await?;
+
+        let mut pending = Self::lock(&mut db_tx, id).await?;
+        if !pending.is_for(user) {
+            return Err(PendingTransferError::NotRecipient);
+        }
+        if pending.status != PendingTransferStatus::Pending || pending.expires_at <= Utc::now() {
+            return Err(PendingTransferError::NotClaimable);
+        }
+
+        let mut escrow = pending.escrow(&mut db_tx).await?;
+        let payout = escrow
+            .pay_out_in(
+                &mut db_tx,
+                credit_wallet_id,
+                None,
+                Some(serde_json::json!({ "pending_transfer_id": pending.id })),
+            )
+            .await?;
+
+        pending = sqlx::query_as!(
+            PendingTransfer,
+            r#"
+            UPDATE pending_transfers
+            SET status = 'claimed', claimed_by = $1, claim_transaction_id = $2,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $3
+            RETURNING *
+            "#,
+            user.id,
+            payout.id,
+            pending.id
+        )
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+
+        Ok((pending, payout))
+    }
+
+    /// Unclaimed transfers whose claim window has passed
+    pub async fn find_expired_ids(pool: &PgPool) -> Result<Vec<Uuid>, PendingTransferError> {
+        let expired_ids = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM pending_transfers
+            WHERE status = 'pending' AND expires_at <= CURRENT_TIMESTAMP
+            ORDER BY expires_at
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(expired_ids)
+    }
+
+    /// Refunds an expired, unclaimed transfer to its sender. Returns false
+    /// if it was claimed or refunded since it was found.
+    ///
+    /// Only the amount goes back. The fee was charged for sending the
+    /// transfer and is kept, as it is when any other transfer is reversed.
+    pub async fn refund_expired(pool: &PgPool, id: Uuid) -> Result<bool, PendingTransferError> {
+        let mut db_tx = pool.begin().await?;
+        let pending = Self::lock(&mut db_tx, id).await?;
+
+        if pending.status != PendingTransferStatus::Pending || pending.expires_at > Utc::now() {
+            return Ok(false);
+        }
+
+        pending.refund_in(&mut db_tx).await?;
+        db_tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Reverses the escrowed amount, less the fee, back to the sender's
+    /// wallet
+    async fn refund_in(
+        &self,
+        db_tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), PendingTransferError> {
+        let mut escrow = self.escrow(db_tx).await?;
+        let refund = escrow
+            .reverse_in(db_tx, None, Some("p2p transfer not claimed".to_string()))
+            .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE pending_transfers
+            SET status = 'refunded', refund_transaction_id = $1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2
+            "#,
+            refund.id,
+            self.id
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn escrow(
+        &self,
+        db_tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<WalletTransaction, PendingTransferError> {
+        let escrow = sqlx::query_as!(
+            WalletTransaction,
+            r#"
+            SELECT * FROM transactions WHERE id = $1
+            "#,
+            self.transaction_id
+        )
+        .fetch_one(&mut **db_tx)
+        .await?;
+
+        Ok(escrow)
+    }
+
+    /// Locks the pending transfer before its escrow transaction, so claims
+    /// and refunds of the same transfer are serialized
+    async fn lock(
+        db_tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+    ) -> Result<Self, PendingTransferError> {
+        sqlx::query_as!(
+            PendingTransfer,
+            r#"
+            SELECT * FROM pending_transfers WHERE id = $1
+            FOR UPDATE
+            "#,
+            id
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?
+        .ok_or(PendingTransferError::NotFound)
+    }
+}
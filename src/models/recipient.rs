@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RecipientError {
+    #[error("Invalid phone number: {0}")]
+    InvalidPhoneNumber(String),
+    #[error("Invalid email address: {0}")]
+    InvalidEmail(String),
+}
+
+/// How a P2P recipient is addressed, normalized so it can be matched against
+/// `users.phone_number` and `users.email`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+pub enum RecipientType {
+    Phone,
+    Email,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipient {
+    pub recipient_type: RecipientType,
+    pub value: String,
+}
+
+impl Recipient {
+    /// Parses a phone number or email address; anything containing '@' is
+    /// treated as an email
+    pub fn parse(input: &str) -> Result<Self, RecipientError> {
+        let input = input.trim();
+        if input.contains('@') {
+            let email = input.to_lowercase();
+            let valid = email
+                .split_once('@')
+                .map(|(local, domain)| !local.is_empty() && domain.contains('.') && !domain.contains('@'))
+                .unwrap_or(false);
+            if !valid {
+                return Err(RecipientError::InvalidEmail(input.to_string()));
+            }
+
+            Ok(Self {
+                recipient_type: RecipientType::Email,
+                value: email,
+            })
+        } else {
+            Ok(Self {
+                recipient_type: RecipientType::Phone,
+                value: normalize_phone(input)?,
+            })
+        }
+    }
+}
+
+/// Normalizes a phone number to E.164. Spaces, dashes, dots and brackets
+/// are dropped, a "00" international prefix becomes "+", and a national
+/// number with a leading 0 takes `DEFAULT_COUNTRY_CODE`.
+pub fn normalize_phone(input: &str) -> Result<String, RecipientError> {
+    let invalid = || RecipientError::InvalidPhoneNumber(input.to_string());
+
+    let mut compact = String::with_capacity(input.len());
+    for (i, c) in input.trim().chars().enumerate() {
+        match c {
+            '0'..='9' => compact.push(c),
+            '+' if i == 0 => compact.push(c),
+            ' ' | '-' | '.' | '(' | ')' => {}
+            _ => return Err(invalid()),
+        }
+    }
+
+    let digits = if let Some(rest) = compact.strip_prefix('+') {
+        rest.to_string()
+    } else if let Some(rest) = compact.strip_prefix("00") {
+        rest.to_string()
+    } else if let Some(rest) = compact.strip_prefix('0') {
+        format!("{}{}", default_country_code(), rest)
+    } else {
+        return Err(invalid());
+    };
+
+    // E.164 allows at most 15 digits and country codes never start with 0
+    if !(8..=15).contains(&digits.len()) || digits.starts_with('0') {
+        return Err(invalid());
+    }
+
+    Ok(format!("+{}", digits))
+}
+
+fn default_country_code() -> String {
+    std::env::var("DEFAULT_COUNTRY_CODE")
+        .ok()
+        .map(|code| code.trim_start_matches('+').to_string())
+        .filter(|code| !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()))
+        .unwrap_or_else(|| "255".to_string())
+}
+
+/// Masks a name for confirmation screens, keeping the first letter of each
+/// word: "Jane Doe" becomes "J*** D**"
+pub fn mask_name(full_name: &str) -> String {
+    full_name
+        .split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => std::iter::once(first)
+                    .chain(chars.map(|_| '*'))
+                    .collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phone_normalization() {
+        assert_eq!(normalize_phone("+255 712-345 678").unwrap(), "+255712345678");
+        assert_eq!(normalize_phone("00255 (712) 345678").unwrap(), "+255712345678");
+        assert_eq!(normalize_phone("+1.415.555.0100").unwrap(), "+14155550100");
+        assert!(normalize_phone("712345678").is_err());
+        assert!(normalize_phone("+255 712 34x 678").is_err());
+        assert!(normalize_phone("+1234").is_err());
+        assert!(normalize_phone("+1234567890123456").is_err());
+    }
+
+    #[test]
+    fn test_recipient_parse() {
+        let email = Recipient::parse("  Jane.Doe@Example.com ").unwrap();
+        assert_eq!(email.recipient_type, RecipientType::Email);
+        assert_eq!(email.value, "jane.doe@example.com");
+        assert!(Recipient::parse("jane@localhost").is_err());
+
+        let phone = Recipient::parse("+44 20 7946 0958").unwrap();
+        assert_eq!(phone.recipient_type, RecipientType::Phone);
+        assert_eq!(phone.value, "+442079460958");
+    }
+
+    #[test]
+    fn test_mask_name() {
+        assert_eq!(mask_name("Jane Doe"), "J*** D**");
+        assert_eq!(mask_name("  Émile   Zola "), "É**** Z***");
+        assert_eq!(mask_name(""), "");
+    }
+}
@@ -1,5 +1,6 @@
+use crate::models::currency::Money;
 use chrono::{DateTime, Utc};
-use rust_decimal::Decimal;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::{PgPool, Postgres, Transaction};
@@ -32,6 +33,28 @@ pub struct ReserveTransaction {
     pub updated_at: DateTime<Utc>,
 }
 
+/// A reserve balance declared by an admin, with proof such as a bank
+/// statement. The latest row in a currency is the current reserve.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ReserveBalance {
+    pub id: Uuid,
+    pub amount: Decimal,
+    pub proof_url: String,
+    pub created_at: DateTime<Utc>,
+    pub currency: String,
+}
+
+/// How well customer balances in one currency are covered by the reserve
+/// declared in that currency
+#[derive(Debug, Serialize)]
+pub struct ReserveRatio {
+    pub currency: String,
+    pub reserve: Money,
+    pub liabilities: Money,
+    /// Reserve over liabilities; 1 when nothing is owed
+    pub ratio: f64,
+}
+
 #[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
 #[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
 pub enum ReserveStatus {
@@ -169,6 +192,85 @@ impl ReserveAccount {
     }
 }
 
+impl ReserveBalance {
+    pub async fn record(pool: &PgPool, amount: &Money, proof_url: &str) -> Result<Self, ReserveError> {
+        let balance = sqlx::query_as!(
+            ReserveBalance,
+            r#"
+            INSERT INTO reserve_balances (amount, currency, proof_url)
+            VALUES ($1, $2, $3)
+            RETURNING *
+            "#,
+            amount.amount,
+            amount.currency,
+            proof_url
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(balance)
+    }
+
+    /// The current reserve in each currency
+    pub async fn latest(pool: &PgPool) -> Result<Vec<Self>, ReserveError> {
+        let balances = sqlx::query_as!(
+            ReserveBalance,
+            r#"
+            SELECT DISTINCT ON (currency) * FROM reserve_balances
+            ORDER BY currency, created_at DESC
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(balances)
+    }
+
+    /// The reserve ratio in every currency that has a reserve or customer
+    /// balances. Currencies are never summed together.
+    pub async fn ratios(pool: &PgPool) -> Result<Vec<ReserveRatio>, ReserveError> {
+        let rows = sqlx::query!(
+            r#"
+            WITH reserves AS (
+                SELECT DISTINCT ON (currency) currency, amount
+                FROM reserve_balances
+                ORDER BY currency, created_at DESC
+            ),
+            liabilities AS (
+                SELECT currency, SUM(balance) AS total
+                FROM wallets
+                GROUP BY currency
+            )
+            SELECT
+                COALESCE(r.currency, l.currency) AS "currency!",
+                COALESCE(r.amount, 0) AS "reserve!",
+                COALESCE(l.total, 0) AS "liabilities!"
+            FROM reserves r
+            FULL OUTER JOIN liabilities l ON l.currency = r.currency
+            ORDER BY 1
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let ratios = rows
+            .into_iter()
+            .map(|row| ReserveRatio {
+                ratio: if row.liabilities > Decimal::ZERO {
+                    (row.reserve / row.liabilities).to_f64().unwrap_or(0.0)
+                } else {
+                    1.0
+                },
+                reserve: Money::from_stored(row.reserve, &row.currency),
+                liabilities: Money::from_stored(row.liabilities, &row.currency),
+                currency: row.currency,
+            })
+            .collect();
+
+        Ok(ratios)
+    }
+}
+
 impl ReserveTransaction {
     /// Creates a new reserve transaction
     pub async fn create(
@@ -0,0 +1,597 @@
+use crate::models::currency::{Money, MoneyError};
+use chrono::{DateTime, Duration, Months, Utc};
+use cron::Schedule;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::str::FromStr;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Attempts per occurrence before it is skipped and the owner notified
+pub const MAX_ATTEMPTS: i32 = 3;
+const RETRY_DELAY_MINUTES: i64 = 30;
+const MAX_INTERVAL_COUNT: i32 = 366;
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ScheduledTransfer {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub debit_wallet_id: Uuid,
+    pub credit_wallet_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    pub frequency: ScheduleFrequency,
+    pub interval_count: i32,
+    pub cron_expression: Option<String>,
+    pub description: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub occurrences: i32,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub last_transaction_id: Option<Uuid>,
+    pub status: ScheduleStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ScheduledTransferRun {
+    pub id: Uuid,
+    pub scheduled_transfer_id: Uuid,
+    pub occurrence: i32,
+    pub attempt: i32,
+    pub transaction_id: Option<Uuid>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+pub enum ScheduleFrequency {
+    Daily,
+    Weekly,
+    Monthly,
+    /// A cron expression with a seconds field, e.g. `0 0 9 1 * *`
+    Cron,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+pub enum ScheduleStatus {
+    Active,
+    Paused,
+    Cancelled,
+    Completed,
+}
+
+/// When a schedule runs
+#[derive(Debug, Clone)]
+pub struct ScheduleSpec {
+    pub frequency: ScheduleFrequency,
+    pub interval_count: i32,
+    pub cron_expression: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+/// What became of a failed attempt
+#[derive(Debug)]
+pub enum FailureOutcome {
+    /// The occurrence will be attempted again at this time
+    Retrying(DateTime<Utc>),
+    /// Attempts are exhausted; the schedule moved on to its next occurrence
+    Skipped,
+}
+
+#[derive(Debug, Error)]
+pub enum ScheduleError {
+    #[error("Invalid schedule: {0}")]
+    InvalidSchedule(String),
+    #[error("Scheduled transfer not found")]
+    NotFound,
+    #[error("Scheduled transfer is {0:?}")]
+    InvalidStatus(ScheduleStatus),
+    #[error("Money error: {0}")]
+    MoneyError(#[from] MoneyError),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl ScheduleSpec {
+    fn validate(&self) -> Result<(), ScheduleError> {
+        if !(1..=MAX_INTERVAL_COUNT).contains(&self.interval_count) {
+            return Err(ScheduleError::InvalidSchedule(format!(
+                "Interval must be between 1 and {}",
+                MAX_INTERVAL_COUNT
+            )));
+        }
+
+        match (&self.frequency, &self.cron_expression) {
+            (ScheduleFrequency::Cron, None) => {
+                return Err(ScheduleError::InvalidSchedule(
+                    "Cron schedules need an expression".to_string(),
+                ));
+            }
+            (ScheduleFrequency::Cron, Some(expression)) => {
+                Schedule::from_str(expression)
+                    .map_err(|e| ScheduleError::InvalidSchedule(e.to_string()))?;
+            }
+            (_, Some(_)) => {
+                return Err(ScheduleError::InvalidSchedule(
+                    "Only cron schedules take an expression".to_string(),
+                ));
+            }
+            (_, None) => {}
+        }
+
+        if matches!(self.ends_at, Some(ends_at) if ends_at <= self.starts_at) {
+            return Err(ScheduleError::InvalidSchedule(
+                "End date must be after the start date".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// When the given occurrence (counting from zero) falls due, or None once
+    /// the schedule has run past its end date. Interval schedules count from
+    /// `starts_at` so monthly runs keep their day of month; cron schedules
+    /// continue from the previous occurrence.
+    fn occurrence(
+        &self,
+        occurrence: i32,
+        previous: Option<DateTime<Utc>>,
+    ) -> Result<Option<DateTime<Utc>>, ScheduleError> {
+        let steps = i64::from(occurrence) * i64::from(self.interval_count);
+
+        let at = match self.frequency {
+            ScheduleFrequency::Daily => self.starts_at.checked_add_signed(Duration::days(steps)),
+            ScheduleFrequency::Weekly => self.starts_at.checked_add_signed(Duration::weeks(steps)),
+            ScheduleFrequency::Monthly => u32::try_from(steps)
+                .ok()
+                .and_then(|months| self.starts_at.checked_add_months(Months::new(months))),
+            ScheduleFrequency::Cron => {
+                let expression = self.cron_expression.as_deref().unwrap_or_default();
+                let schedule = Schedule::from_str(expression)
+                    .map_err(|e| ScheduleError::InvalidSchedule(e.to_string()))?;
+                let after = previous.unwrap_or(self.starts_at - Duration::seconds(1));
+                schedule.after(&after).next()
+            }
+        };
+
+        Ok(at.filter(|at| self.ends_at.map_or(true, |ends_at| *at <= ends_at)))
+    }
+}
+
+impl ScheduledTransfer {
+    /// Stores a standing order. The first occurrence is due at `starts_at`,
+    /// or for cron schedules at the first match on or after it.
+    pub async fn create(
+        pool: &PgPool,
+        user_id: Uuid,
+        debit_wallet_id: Uuid,
+        credit_wallet_id: Uuid,
+        amount: &Money,
+        spec: ScheduleSpec,
+        description: Option<String>,
+    ) -> Result<Self, ScheduleError> {
+        spec.validate()?;
+
+        if !amount.is_positive() {
+            return Err(ScheduleError::InvalidSchedule("Amount must be positive".to_string()));
+        }
+
+        if debit_wallet_id == credit_wallet_id {
+            return Err(ScheduleError::InvalidSchedule(
+                "Debit and credit wallets cannot be the same".to_string(),
+            ));
+        }
+
+        let first_run = spec.occurrence(0, None)?.ok_or_else(|| {
+            ScheduleError::InvalidSchedule("Schedule never runs before its end date".to_string())
+        })?;
+
+        let schedule = sqlx::query_as!(
+            ScheduledTransfer,
+            r#"
+            INSERT INTO scheduled_transfers (
+                user_id, debit_wallet_id, credit_wallet_id, amount, currency,
+                frequency, interval_count, cron_expression, description,
+                starts_at, ends_at, scheduled_for, next_run_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $12)
+            RETURNING *
+            "#,
+            user_id,
+            debit_wallet_id,
+            credit_wallet_id,
+            amount.amount,
+            amount.currency,
+            spec.frequency as ScheduleFrequency,
+            spec.interval_count,
+            spec.cron_expression,
+            description,
+            spec.starts_at,
+            spec.ends_at,
+            first_run,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(schedule)
+    }
+
+    /// Retrieves a scheduled transfer by its ID
+    pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, ScheduleError> {
+        let schedule = sqlx::query_as!(
+            ScheduledTransfer,
+            r#"
+            SELECT * FROM scheduled_transfers WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(schedule)
+    }
+
+    /// Gets all scheduled transfers for a user, newest first
+    pub async fn find_by_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<Self>, ScheduleError> {
+        let schedules = sqlx::query_as!(
+            ScheduledTransfer,
+            r#"
+            SELECT * FROM scheduled_transfers
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(schedules)
+    }
+
+    /// Gets the execution attempts for this schedule, newest first
+    pub async fn runs(&self, pool: &PgPool) -> Result<Vec<ScheduledTransferRun>, ScheduleError> {
+        let runs = sqlx::query_as!(
+            ScheduledTransferRun,
+            r#"
+            SELECT * FROM scheduled_transfer_runs
+            WHERE scheduled_transfer_id = $1
+            ORDER BY created_at DESC
+            "#,
+            self.id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(runs)
+    }
+
+    /// Stops the schedule from running until it is resumed
+    pub async fn pause(&mut self, pool: &PgPool) -> Result<(), ScheduleError> {
+        self.set_status(pool, ScheduleStatus::Active, ScheduleStatus::Paused).await
+    }
+
+    /// Restarts a paused schedule. Occurrences that fell due while it was
+    /// paused are skipped rather than paid out in a burst.
+    pub async fn resume(&mut self, pool: &PgPool) -> Result<(), ScheduleError> {
+        if self.status != ScheduleStatus::Paused {
+            return Err(ScheduleError::InvalidStatus(self.status.clone()));
+        }
+
+        let spec = self.spec();
+        let now = Utc::now();
+        let mut occurrences = self.occurrences;
+        let mut scheduled_for = self.scheduled_for;
+        while let Some(at) = scheduled_for.filter(|at| *at < now) {
+            occurrences += 1;
+            scheduled_for = spec.occurrence(occurrences, Some(at))?;
+        }
+
+        let status = if scheduled_for.is_some() {
+            ScheduleStatus::Active
+        } else {
+            ScheduleStatus::Completed
+        };
+
+        let schedule = sqlx::query_as!(
+            ScheduledTransfer,
+            r#"
+            UPDATE scheduled_transfers
+            SET status = $1, occurrences = $2, attempts = 0,
+                scheduled_for = $3, next_run_at = $3, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $4 AND status = 'paused'
+            RETURNING *
+            "#,
+            status as ScheduleStatus,
+            occurrences,
+            scheduled_for,
+            self.id
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ScheduleError::InvalidStatus(self.status.clone()))?;
+
+        *self = schedule;
+
+        Ok(())
+    }
+
+    /// Cancels the schedule for good
+    pub async fn cancel(&mut self, pool: &PgPool) -> Result<(), ScheduleError> {
+        match self.status {
+            ScheduleStatus::Active => {
+                self.set_status(pool, ScheduleStatus::Active, ScheduleStatus::Cancelled).await
+            }
+            ScheduleStatus::Paused => {
+                self.set_status(pool, ScheduleStatus::Paused, ScheduleStatus::Cancelled).await
+            }
+            _ => Err(ScheduleError::InvalidStatus(self.status.clone())),
+        }
+    }
+
+    /// IDs of active schedules whose next run is due
+    pub async fn due(pool: &PgPool, limit: i64) -> Result<Vec<Uuid>, ScheduleError> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM scheduled_transfers
+            WHERE status = 'active' AND next_run_at <= CURRENT_TIMESTAMP
+            ORDER BY next_run_at
+            LIMIT $1
+            "#,
+            limit
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Locks a schedule for execution if it is still due. Schedules already
+    /// locked by another scheduler instance are skipped.
+    pub async fn lock_due(
+        db_tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+    ) -> Result<Option<Self>, ScheduleError> {
+        let schedule = sqlx::query_as!(
+            ScheduledTransfer,
+            r#"
+            SELECT * FROM scheduled_transfers
+            WHERE id = $1 AND status = 'active' AND next_run_at <= CURRENT_TIMESTAMP
+            FOR UPDATE SKIP LOCKED
+            "#,
+            id
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?;
+
+        Ok(schedule)
+    }
+
+    /// The transaction reference for the pending occurrence. Transfers are
+    /// looked up by it so an occurrence is never paid twice.
+    pub fn occurrence_reference(&self) -> String {
+        format!("schedule:{}:{}", self.id, self.occurrences)
+    }
+
+    /// Finds the transfer already made for the pending occurrence, if a
+    /// previous run created it but failed to record it
+    pub async fn find_executed(
+        &self,
+        db_tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Uuid>, ScheduleError> {
+        let transaction_id = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM transactions
+            WHERE reference_id = $1 AND debit_wallet_id = $2
+            "#,
+            self.occurrence_reference(),
+            self.debit_wallet_id
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?;
+
+        Ok(transaction_id)
+    }
+
+    /// The amount each occurrence transfers
+    pub fn money(&self) -> Money {
+        Money::from_stored(self.amount, &self.currency)
+    }
+
+    /// Records a successful run and moves on to the next occurrence
+    pub async fn record_success(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        transaction_id: Uuid,
+    ) -> Result<(), ScheduleError> {
+        self.record_run(db_tx, Some(transaction_id), None).await?;
+        self.last_transaction_id = Some(transaction_id);
+        self.last_error = None;
+        self.advance(db_tx).await
+    }
+
+    /// Records a failed run. The occurrence is retried with a growing delay
+    /// until `MAX_ATTEMPTS` is reached, then skipped.
+    pub async fn record_failure(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        error: &str,
+    ) -> Result<FailureOutcome, ScheduleError> {
+        self.record_run(db_tx, None, Some(error)).await?;
+        self.last_error = Some(error.to_string());
+
+        let attempts = self.attempts + 1;
+        if attempts >= MAX_ATTEMPTS {
+            self.advance(db_tx).await?;
+            return Ok(FailureOutcome::Skipped);
+        }
+
+        let retry_at = Utc::now() + Duration::minutes(RETRY_DELAY_MINUTES * i64::from(attempts));
+        sqlx::query!(
+            r#"
+            UPDATE scheduled_transfers
+            SET attempts = $1, next_run_at = $2, last_error = $3, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $4
+            "#,
+            attempts,
+            retry_at,
+            error,
+            self.id
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        self.attempts = attempts;
+        self.next_run_at = Some(retry_at);
+
+        Ok(FailureOutcome::Retrying(retry_at))
+    }
+
+    fn spec(&self) -> ScheduleSpec {
+        ScheduleSpec {
+            frequency: self.frequency.clone(),
+            interval_count: self.interval_count,
+            cron_expression: self.cron_expression.clone(),
+            starts_at: self.starts_at,
+            ends_at: self.ends_at,
+        }
+    }
+
+    async fn record_run(
+        &self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        transaction_id: Option<Uuid>,
+        error: Option<&str>,
+    ) -> Result<(), ScheduleError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO scheduled_transfer_runs (
+                scheduled_transfer_id, occurrence, attempt, transaction_id, error
+            )
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            self.id,
+            self.occurrences,
+            self.attempts + 1,
+            transaction_id,
+            error,
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Moves to the next occurrence, completing the schedule when none is left
+    async fn advance(&mut self, db_tx: &mut Transaction<'_, Postgres>) -> Result<(), ScheduleError> {
+        let occurrences = self.occurrences + 1;
+        let scheduled_for = self.spec().occurrence(occurrences, self.scheduled_for)?;
+        let status = if scheduled_for.is_some() {
+            ScheduleStatus::Active
+        } else {
+            ScheduleStatus::Completed
+        };
+
+        let schedule = sqlx::query_as!(
+            ScheduledTransfer,
+            r#"
+            UPDATE scheduled_transfers
+            SET occurrences = $1, attempts = 0, scheduled_for = $2, next_run_at = $2,
+                status = $3, last_error = $4, last_transaction_id = $5,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $6
+            RETURNING *
+            "#,
+            occurrences,
+            scheduled_for,
+            status as ScheduleStatus,
+            self.last_error,
+            self.last_transaction_id,
+            self.id
+        )
+        .fetch_one(&mut **db_tx)
+        .await?;
+
+        *self = schedule;
+
+        Ok(())
+    }
+
+    async fn set_status(
+        &mut self,
+        pool: &PgPool,
+        from: ScheduleStatus,
+        to: ScheduleStatus,
+    ) -> Result<(), ScheduleError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE scheduled_transfers
+            SET status = $1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2 AND status = $3
+            "#,
+            to.clone() as ScheduleStatus,
+            self.id,
+            from as ScheduleStatus
+        )
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 1 {
+            self.status = to;
+            Ok(())
+        } else {
+            Err(ScheduleError::InvalidStatus(self.status.clone()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn spec(frequency: ScheduleFrequency, cron_expression: Option<&str>) -> ScheduleSpec {
+        ScheduleSpec {
+            frequency,
+            interval_count: 1,
+            cron_expression: cron_expression.map(str::to_string),
+            starts_at: Utc.with_ymd_and_hms(2025, 1, 31, 9, 0, 0).unwrap(),
+            ends_at: None,
+        }
+    }
+
+    #[test]
+    fn test_monthly_keeps_day_of_month() {
+        let monthly = spec(ScheduleFrequency::Monthly, None);
+        let feb = monthly.occurrence(1, None).unwrap().unwrap();
+        let mar = monthly.occurrence(2, Some(feb)).unwrap().unwrap();
+        assert_eq!(feb, Utc.with_ymd_and_hms(2025, 2, 28, 9, 0, 0).unwrap());
+        assert_eq!(mar, Utc.with_ymd_and_hms(2025, 3, 31, 9, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_schedule_ends() {
+        let mut weekly = spec(ScheduleFrequency::Weekly, None);
+        weekly.ends_at = Some(weekly.starts_at + Duration::days(10));
+        assert!(weekly.occurrence(1, None).unwrap().is_some());
+        assert!(weekly.occurrence(2, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_cron_schedule() {
+        let first_of_month = spec(ScheduleFrequency::Cron, Some("0 0 8 1 * *"));
+        assert!(first_of_month.validate().is_ok());
+        let first = first_of_month.occurrence(0, None).unwrap().unwrap();
+        assert_eq!(first, Utc.with_ymd_and_hms(2025, 2, 1, 8, 0, 0).unwrap());
+        assert!(spec(ScheduleFrequency::Cron, Some("not cron")).validate().is_err());
+        assert!(spec(ScheduleFrequency::Daily, Some("0 0 8 1 * *")).validate().is_err());
+    }
+}
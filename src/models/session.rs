@@ -0,0 +1,271 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use thiserror::Error;
+use uuid::Uuid;
+
+const DEFAULT_REFRESH_TTL_DAYS: i64 = 30;
+
+/// One refresh token generation. Every refresh replaces the row with a new
+/// one in the same family, so a family is what users see as a session: one
+/// login on one device.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Session {
+    pub id: Uuid,
+    pub family_id: Uuid,
+    pub user_id: Uuid,
+    #[serde(skip_serializing)]
+    pub refresh_token_hash: String,
+    pub device_name: Option<String>,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Set once the token has been exchanged for its successor
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub revoked_reason: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("Invalid refresh token")]
+    InvalidToken,
+    #[error("Refresh token has expired")]
+    Expired,
+    /// A rotated token was presented again, so it has probably been stolen.
+    /// The whole family has been revoked.
+    #[error("Refresh token was already used; the session has been revoked")]
+    TokenReused { family_id: Uuid },
+    #[error("Session not found")]
+    NotFound,
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Where a login or refresh came from
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub device_name: Option<String>,
+    pub ip_address: String,
+    pub user_agent: String,
+}
+
+impl Session {
+    /// How long a refresh token is valid, from REFRESH_TOKEN_TTL_DAYS
+    pub fn ttl() -> Duration {
+        let days = std::env::var("REFRESH_TOKEN_TTL_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_REFRESH_TTL_DAYS);
+        Duration::days(days)
+    }
+
+    /// Starts a new session family, returning the session and its plain
+    /// refresh token. Only the token's hash is stored.
+    pub async fn create(
+        pool: &PgPool,
+        user_id: Uuid,
+        client: &ClientInfo,
+    ) -> Result<(Self, String), SessionError> {
+        let token = generate_refresh_token();
+
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            INSERT INTO sessions (
+                family_id, user_id, refresh_token_hash, device_name,
+                ip_address, user_agent, expires_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            hash_refresh_token(&token),
+            client.device_name,
+            client.ip_address,
+            client.user_agent,
+            Utc::now() + Self::ttl()
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok((session, token))
+    }
+
+    /// Exchanges a refresh token for a new one in the same family. Presenting
+    /// a token that was already exchanged revokes the family.
+    pub async fn rotate(
+        pool: &PgPool,
+        refresh_token: &str,
+        client: &ClientInfo,
+    ) -> Result<(Self, String), SessionError> {
+        let mut db_tx = pool.begin().await?;
+
+        let current = sqlx::query_as!(
+            Session,
+            r#"
+            SELECT * FROM sessions
+            WHERE refresh_token_hash = $1
+            FOR UPDATE
+            "#,
+            hash_refresh_token(refresh_token)
+        )
+        .fetch_optional(&mut *db_tx)
+        .await?
+        .ok_or(SessionError::InvalidToken)?;
+
+        if current.revoked_at.is_some() {
+            return Err(SessionError::InvalidToken);
+        }
+
+        if current.rotated_at.is_some() {
+            sqlx::query!(
+                r#"
+                UPDATE sessions
+                SET revoked_at = CURRENT_TIMESTAMP, revoked_reason = 'refresh_token_reused'
+                WHERE family_id = $1 AND revoked_at IS NULL
+                "#,
+                current.family_id
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            db_tx.commit().await?;
+
+            return Err(SessionError::TokenReused {
+                family_id: current.family_id,
+            });
+        }
+
+        if current.expires_at <= Utc::now() {
+            return Err(SessionError::Expired);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE sessions SET rotated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            "#,
+            current.id
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        // The device name is kept from login; the address is the latest seen
+        let token = generate_refresh_token();
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            INSERT INTO sessions (
+                family_id, user_id, refresh_token_hash, device_name,
+                ip_address, user_agent, expires_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+            current.family_id,
+            current.user_id,
+            hash_refresh_token(&token),
+            current.device_name,
+            client.ip_address,
+            client.user_agent,
+            Utc::now() + Self::ttl()
+        )
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+
+        Ok((session, token))
+    }
+
+    /// The user's live sessions, one row per family, most recently used
+    /// first
+    pub async fn list_active(pool: &PgPool, user_id: Uuid) -> Result<Vec<Self>, SessionError> {
+        let sessions = sqlx::query_as!(
+            Session,
+            r#"
+            SELECT * FROM sessions
+            WHERE user_id = $1
+              AND rotated_at IS NULL
+              AND revoked_at IS NULL
+              AND expires_at > CURRENT_TIMESTAMP
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    /// Revokes one of the user's session families
+    pub async fn revoke(
+        pool: &PgPool,
+        user_id: Uuid,
+        family_id: Uuid,
+        reason: &str,
+    ) -> Result<(), SessionError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = CURRENT_TIMESTAMP, revoked_reason = $3
+            WHERE user_id = $1 AND family_id = $2 AND revoked_at IS NULL
+            "#,
+            user_id,
+            family_id,
+            reason
+        )
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Revokes every session of the user, returning how many were live.
+    /// Earlier generations are revoked too, so none can be replayed.
+    pub async fn revoke_all<'e, E: sqlx::PgExecutor<'e>>(
+        executor: E,
+        user_id: Uuid,
+        reason: &str,
+    ) -> Result<i64, SessionError> {
+        let live = sqlx::query_scalar!(
+            r#"
+            WITH revoked AS (
+                UPDATE sessions
+                SET revoked_at = CURRENT_TIMESTAMP, revoked_reason = $2
+                WHERE user_id = $1 AND revoked_at IS NULL
+                RETURNING rotated_at, expires_at
+            )
+            SELECT COUNT(*) AS "count!" FROM revoked
+            WHERE rotated_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+            "#,
+            user_id,
+            reason
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(live)
+    }
+}
+
+/// 256 random bits, URL safe
+fn generate_refresh_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn hash_refresh_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,309 @@
+use crate::models::{
+    currency::Money,
+    transaction::TransactionType,
+    wallet::Wallet,
+};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Longest period a single statement may cover
+pub const MAX_STATEMENT_DAYS: i64 = 366;
+
+#[derive(Debug, Error)]
+pub enum StatementError {
+    #[error("Invalid statement period: {0}")]
+    InvalidPeriod(String),
+    #[error("Statement does not reconcile: running balance {computed}, ledger {ledger}")]
+    LedgerMismatch { computed: Money, ledger: Money },
+    #[error("Could not render statement: {0}")]
+    RenderError(String),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// One movement on the wallet: the net of a journal entry's postings to the
+/// wallet's ledger account
+#[derive(Debug, Serialize)]
+pub struct StatementLine {
+    pub posted_at: DateTime<Utc>,
+    pub journal_entry_id: Uuid,
+    pub transaction_id: Option<Uuid>,
+    pub transaction_type: Option<TransactionType>,
+    pub description: String,
+    pub reference_id: Option<String>,
+    /// Money out, as a positive amount
+    pub debit: Decimal,
+    /// Money in
+    pub credit: Decimal,
+    pub balance: Decimal,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Statement {
+    pub wallet_id: Uuid,
+    pub account_holder: String,
+    pub wallet_name: String,
+    pub currency: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub opening_balance: Decimal,
+    pub lines: Vec<StatementLine>,
+    pub total_debits: Decimal,
+    pub total_credits: Decimal,
+    pub closing_balance: Decimal,
+    pub generated_at: DateTime<Utc>,
+}
+
+/// A journal entry's net effect on one ledger account
+struct EntryMovement {
+    journal_entry_id: Uuid,
+    posted_at: DateTime<Utc>,
+    entry_description: String,
+    transaction_id: Option<Uuid>,
+    transaction_type: Option<TransactionType>,
+    reference_id: Option<String>,
+    amount: Decimal,
+}
+
+impl Statement {
+    /// Builds the statement for `[period_start, period_end)` from the wallet's
+    /// postings, and refuses to return one whose running balance does not
+    /// agree with the ledger
+    pub async fn generate(
+        pool: &PgPool,
+        wallet: &Wallet,
+        account_holder: &str,
+        period_start: DateTime<Utc>,
+        period_end: DateTime<Utc>,
+    ) -> Result<Self, StatementError> {
+        if period_end <= period_start {
+            return Err(StatementError::InvalidPeriod(
+                "End must be after start".to_string(),
+            ));
+        }
+        if period_end - period_start > Duration::days(MAX_STATEMENT_DAYS) {
+            return Err(StatementError::InvalidPeriod(format!(
+                "A statement can cover at most {} days",
+                MAX_STATEMENT_DAYS
+            )));
+        }
+
+        // Read everything from one snapshot so postings made meanwhile cannot
+        // unbalance the statement
+        let mut db_tx = pool.begin().await?;
+        sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+            .execute(&mut *db_tx)
+            .await?;
+
+        let generated_at = Utc::now();
+        let opening_balance =
+            Self::ledger_balance_before(&mut db_tx, wallet.id, period_start).await?;
+
+        let movements = sqlx::query_as!(
+            EntryMovement,
+            r#"
+            SELECT
+                e.id AS journal_entry_id,
+                e.created_at AS posted_at,
+                e.description AS entry_description,
+                t.id AS "transaction_id?",
+                t.transaction_type AS "transaction_type?: TransactionType",
+                t.reference_id AS "reference_id?",
+                SUM(p.amount) AS "amount!"
+            FROM postings p
+            JOIN ledger_accounts a ON a.id = p.ledger_account_id
+            JOIN journal_entries e ON e.id = p.journal_entry_id
+            LEFT JOIN transactions t ON t.id = e.transaction_id
+            WHERE a.wallet_id = $1
+              AND e.created_at >= $2
+              AND e.created_at < $3
+            GROUP BY e.id, e.created_at, e.description, t.id, t.transaction_type, t.reference_id
+            HAVING SUM(p.amount) <> 0
+            ORDER BY e.created_at, e.id
+            "#,
+            wallet.id,
+            period_start,
+            period_end
+        )
+        .fetch_all(&mut *db_tx)
+        .await?;
+
+        let mut balance = opening_balance;
+        let mut total_debits = Decimal::ZERO;
+        let mut total_credits = Decimal::ZERO;
+        let mut lines = Vec::with_capacity(movements.len());
+        for movement in movements {
+            balance += movement.amount;
+            let (debit, credit) = if movement.amount < Decimal::ZERO {
+                (-movement.amount, Decimal::ZERO)
+            } else {
+                (Decimal::ZERO, movement.amount)
+            };
+            total_debits += debit;
+            total_credits += credit;
+
+            lines.push(StatementLine {
+                posted_at: movement.posted_at,
+                journal_entry_id: movement.journal_entry_id,
+                transaction_id: movement.transaction_id,
+                description: describe(
+                    movement.transaction_type.as_ref(),
+                    &movement.entry_description,
+                    movement.amount,
+                ),
+                transaction_type: movement.transaction_type,
+                reference_id: movement.reference_id,
+                debit,
+                credit,
+                balance,
+            });
+        }
+
+        // The running balance must land on the ledger's own total, and on
+        // the stored wallet balance when the period runs up to now
+        let ledger_closing = Self::ledger_balance_before(&mut db_tx, wallet.id, period_end).await?;
+        let mut expected = vec![ledger_closing];
+        if period_end >= generated_at {
+            let stored = sqlx::query_scalar!(
+                r#"
+                SELECT balance FROM wallets WHERE id = $1
+                "#,
+                wallet.id
+            )
+            .fetch_one(&mut *db_tx)
+            .await?;
+            expected.push(stored);
+        }
+        db_tx.commit().await?;
+
+        if let Some(ledger) = expected.into_iter().find(|expected| *expected != balance) {
+            return Err(StatementError::LedgerMismatch {
+                computed: wallet.money(balance),
+                ledger: wallet.money(ledger),
+            });
+        }
+
+        Ok(Self {
+            wallet_id: wallet.id,
+            account_holder: account_holder.to_string(),
+            wallet_name: wallet
+                .nickname
+                .clone()
+                .unwrap_or_else(|| format!("{} wallet", wallet.currency)),
+            currency: wallet.currency.clone(),
+            period_start,
+            period_end,
+            opening_balance,
+            lines,
+            total_debits,
+            total_credits,
+            closing_balance: balance,
+            generated_at,
+        })
+    }
+
+    /// An amount in the statement currency, for rendering
+    pub fn money(&self, amount: Decimal) -> Money {
+        Money::from_stored(amount, &self.currency)
+    }
+
+    /// Sum of the wallet's postings made before `at`
+    async fn ledger_balance_before(
+        db_tx: &mut Transaction<'_, Postgres>,
+        wallet_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<Decimal, StatementError> {
+        let balance = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(p.amount), 0) AS "balance!"
+            FROM postings p
+            JOIN ledger_accounts a ON a.id = p.ledger_account_id
+            JOIN journal_entries e ON e.id = p.journal_entry_id
+            WHERE a.wallet_id = $1 AND e.created_at < $2
+            "#,
+            wallet_id,
+            at
+        )
+        .fetch_one(&mut **db_tx)
+        .await?;
+
+        Ok(balance)
+    }
+}
+
+/// Statement wording for a movement
+fn describe(
+    transaction_type: Option<&TransactionType>,
+    entry_description: &str,
+    amount: Decimal,
+) -> String {
+    let incoming = amount > Decimal::ZERO;
+    match transaction_type {
+        Some(TransactionType::Deposit) => "Deposit".to_string(),
+        Some(TransactionType::Withdrawal) => "Withdrawal".to_string(),
+        Some(TransactionType::Transfer) if incoming => "Transfer in".to_string(),
+        Some(TransactionType::Transfer) => "Transfer out".to_string(),
+        Some(TransactionType::Fee) => "Fee".to_string(),
+        Some(TransactionType::Refund) if incoming => "Refund received".to_string(),
+        Some(TransactionType::Refund) => "Refund issued".to_string(),
+        Some(TransactionType::Conversion) if incoming => "Currency conversion in".to_string(),
+        Some(TransactionType::Conversion) => "Currency conversion out".to_string(),
+        None if entry_description.starts_with("opening_balance") => {
+            "Balance brought forward".to_string()
+        }
+        None => "Adjustment".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::testing::{complete_deposit, create_verified_user};
+
+    async fn balance(pool: &PgPool, wallet: &Wallet) -> Decimal {
+        Wallet::find_by_id(pool, wallet.id).await.unwrap().unwrap().balance
+    }
+
+    #[sqlx::test]
+    async fn test_opening_and_closing_balances(pool: PgPool) {
+        let user = create_verified_user(&pool, "statement@example.com", 1).await;
+        let wallet = Wallet::create(&pool, user.id, "USD", None).await.unwrap();
+
+        let start = Utc::now() - Duration::hours(1);
+        complete_deposit(&pool, &wallet, Decimal::new(10000, 2)).await;
+        let after_first = balance(&pool, &wallet).await;
+        let between = Utc::now();
+        complete_deposit(&pool, &wallet, Decimal::new(5000, 2)).await;
+        let after_second = balance(&pool, &wallet).await;
+        let end = Utc::now() + Duration::hours(1);
+
+        // Everything so far: opens empty and closes on the stored balance
+        let full = Statement::generate(&pool, &wallet, "Test User", start, end).await.unwrap();
+        assert_eq!(full.opening_balance, Decimal::ZERO);
+        assert_eq!(full.closing_balance, after_second);
+        assert_eq!(full.lines.last().unwrap().balance, after_second);
+
+        // A later period opens on what an earlier one closed on
+        let first = Statement::generate(&pool, &wallet, "Test User", start, between).await.unwrap();
+        let second = Statement::generate(&pool, &wallet, "Test User", between, end).await.unwrap();
+        assert_eq!(first.closing_balance, after_first);
+        assert_eq!(second.opening_balance, first.closing_balance);
+        assert_eq!(
+            second.closing_balance,
+            second.opening_balance + second.total_credits - second.total_debits
+        );
+        assert_eq!(second.closing_balance, after_second);
+    }
+
+    #[test]
+    fn test_describe_uses_direction() {
+        let transfer = TransactionType::Transfer;
+        assert_eq!(describe(Some(&transfer), "transfer:complete", Decimal::ONE), "Transfer in");
+        assert_eq!(describe(Some(&transfer), "transfer:complete", -Decimal::ONE), "Transfer out");
+        assert_eq!(describe(None, "opening_balance:abc", Decimal::ONE), "Balance brought forward");
+    }
+}
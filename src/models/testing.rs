@@ -0,0 +1,54 @@
+//! Fixtures shared by the database tests
+
+use crate::models::{
+    transaction::{Transaction, TransactionStatus, TransactionType},
+    user::{KycStatus, User},
+    wallet::Wallet,
+};
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+
+/// Stored for every test user; no test logs in with a password
+pub const PASSWORD_HASH: &str = "hash";
+
+/// A new user with an unverified KYC status
+pub async fn create_user(pool: &PgPool, email: &str) -> User {
+    User::create(
+        pool,
+        email.to_string(),
+        PASSWORD_HASH.to_string(),
+        "Test User".to_string(),
+        None,
+    )
+    .await
+    .unwrap()
+}
+
+/// A new user verified at `kyc_level`, so limits apply
+pub async fn create_verified_user(pool: &PgPool, email: &str, kyc_level: i32) -> User {
+    let mut user = create_user(pool, email).await;
+    user.update_kyc_status(pool, KycStatus::Verified, kyc_level)
+        .await
+        .unwrap();
+    user
+}
+
+/// Deposits `amount` into the wallet and settles it, as the bank would
+pub async fn complete_deposit(pool: &PgPool, wallet: &Wallet, amount: Decimal) -> Transaction {
+    let mut deposit = Transaction::create(
+        pool,
+        None,
+        Some(wallet.id),
+        amount,
+        wallet.currency.clone(),
+        TransactionType::Deposit,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    for status in [TransactionStatus::Processing, TransactionStatus::Completed] {
+        deposit.update_status(pool, status, None).await.unwrap();
+    }
+    deposit
+}
@@ -1,9 +1,19 @@
-use crate::models::wallet::{Wallet, WalletError};
+use crate::models::{
+    currency::{Money, MoneyError},
+    fee::FeeQuote,
+    fx::{FxError, FxQuote},
+    hold::{HoldError, HoldStatus, WalletHold},
+    ledger::{JournalEntry, LedgerAccount, LedgerAccountType, LedgerError, PostingLeg},
+    limit::{KycLimit, LimitError},
+    pagination::{Cursor, Keyset, Page, PageRequest},
+    wallet::{Wallet, WalletError},
+};
+use crate::telemetry;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use sqlx::{PgPool, Postgres, Transaction};
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -20,9 +30,13 @@ pub struct Transaction {
     pub metadata: Option<Value>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub fee_amount: Decimal,
+    pub fee_breakdown: Option<Value>,
+    pub fx_quote_id: Option<Uuid>,
+    pub parent_transaction_id: Option<Uuid>,
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone)]
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
 #[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
 pub enum TransactionType {
     Deposit,
@@ -30,6 +44,38 @@ pub enum TransactionType {
     Transfer,
     Fee,
     Refund,
+    Conversion,
+}
+
+impl TransactionType {
+    /// The platform account that stands in for a missing debit or credit wallet
+    pub fn contra_account_type(&self) -> LedgerAccountType {
+        match self {
+            TransactionType::Deposit | TransactionType::Withdrawal => LedgerAccountType::Reserve,
+            TransactionType::Fee => LedgerAccountType::FeeIncome,
+            TransactionType::Transfer | TransactionType::Refund => LedgerAccountType::Suspense,
+            TransactionType::Conversion => LedgerAccountType::FxPosition,
+        }
+    }
+
+    /// Internal movements complete as soon as they are created; deposits and
+    /// withdrawals wait for the bank to confirm settlement, which is recorded
+    /// with `Transaction::settle_in`.
+    pub fn settles_immediately(&self) -> bool {
+        !matches!(self, TransactionType::Deposit | TransactionType::Withdrawal)
+    }
+
+    /// The value stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Transfer => "transfer",
+            TransactionType::Fee => "fee",
+            TransactionType::Refund => "refund",
+            TransactionType::Conversion => "conversion",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
@@ -40,6 +86,54 @@ pub enum TransactionStatus {
     Completed,
     Failed,
     Reversed,
+    #[sqlx(rename = "partially_reversed")]
+    PartiallyReversed,
+}
+
+impl TransactionStatus {
+    /// Whether the lifecycle allows moving from this status to `next`.
+    /// Failed and Reversed are terminal.
+    pub fn can_transition_to(&self, next: &TransactionStatus) -> bool {
+        use TransactionStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Processing)
+                | (Pending, Failed)
+                | (Processing, Completed)
+                | (Processing, Failed)
+                | (Completed, Reversed)
+                | (Completed, PartiallyReversed)
+                | (PartiallyReversed, Reversed)
+        )
+    }
+
+    /// The value stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionStatus::Pending => "pending",
+            TransactionStatus::Processing => "processing",
+            TransactionStatus::Completed => "completed",
+            TransactionStatus::Failed => "failed",
+            TransactionStatus::Reversed => "reversed",
+            TransactionStatus::PartiallyReversed => "partially_reversed",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TransactionStatusChange {
+    pub id: Uuid,
+    pub transaction_id: Uuid,
+    pub from_status: Option<TransactionStatus>,
+    pub to_status: TransactionStatus,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The user behind a wallet, for pricing and limits
+struct WalletOwner {
+    user_id: Uuid,
+    kyc_level: i32,
 }
 
 #[derive(Debug, Error)]
@@ -48,14 +142,38 @@ pub enum TransactionError {
     InvalidTransaction(String),
     #[error("Wallet error: {0}")]
     WalletError(#[from] WalletError),
+    #[error("Ledger error: {0}")]
+    LedgerError(#[from] LedgerError),
+    #[error("Hold error: {0}")]
+    HoldError(#[from] HoldError),
+    #[error("FX error: {0}")]
+    FxError(#[from] FxError),
+    #[error("Money error: {0}")]
+    MoneyError(#[from] MoneyError),
+    #[error("Limit error: {0}")]
+    LimitError(#[from] LimitError),
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
+    #[error("Invalid status transition from {from:?} to {to:?}")]
+    InvalidStatusTransition {
+        from: TransactionStatus,
+        to: TransactionStatus,
+    },
     #[error("Transaction not found")]
     NotFound,
 }
 
+impl Keyset for Transaction {
+    fn cursor(&self) -> Cursor {
+        Cursor {
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+}
+
 impl Transaction {
-    /// Creates a new transaction with double-entry accounting
+    /// Creates a new transaction and posts its balanced journal entry
     pub async fn create(
         pool: &PgPool,
         debit_wallet_id: Option<Uuid>,
@@ -65,6 +183,123 @@ impl Transaction {
         transaction_type: TransactionType,
         reference_id: Option<String>,
         metadata: Option<Value>,
+    ) -> Result<Self, TransactionError> {
+        // The currency must be registered and the amount within its minor unit
+        let money = Money::new(amount, &currency)?;
+
+        // Start a database transaction
+        let mut db_tx = pool.begin().await?;
+
+        let transaction = Self::create_in(
+            &mut db_tx,
+            debit_wallet_id,
+            credit_wallet_id,
+            &money,
+            transaction_type,
+            reference_id,
+            metadata,
+            None,
+        )
+        .await?;
+
+        // Commit the transaction
+        db_tx.commit().await?;
+
+        Ok(transaction)
+    }
+
+    /// Converts between two of a user's wallets at the rate locked by an FX
+    /// quote. The debit wallet must hold the quote's source currency and the
+    /// credit wallet its target currency.
+    pub async fn convert(
+        pool: &PgPool,
+        quote_id: Uuid,
+        debit_wallet_id: Uuid,
+        credit_wallet_id: Uuid,
+    ) -> Result<Self, TransactionError> {
+        if debit_wallet_id == credit_wallet_id {
+            return Err(TransactionError::InvalidTransaction(
+                "Debit and credit wallets cannot be the same".to_string(),
+            ));
+        }
+
+        let mut db_tx = pool.begin().await?;
+
+        let mut quote = FxQuote::lock_for_use(&mut db_tx, quote_id).await?;
+
+        let metadata = telemetry::stamp_metadata(Some(serde_json::json!({
+            "to_currency": quote.to_currency,
+            "to_amount": quote.to_amount,
+            "rate": quote.rate,
+        })));
+
+        let mut transaction = sqlx::query_as!(
+            Transaction,
+            r#"
+            INSERT INTO transactions (
+                debit_wallet_id, credit_wallet_id, amount, currency,
+                transaction_type, metadata, fx_quote_id
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+            Some(debit_wallet_id),
+            Some(credit_wallet_id),
+            quote.from_amount,
+            quote.from_currency,
+            TransactionType::Conversion as TransactionType,
+            metadata,
+            quote.id
+        )
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        quote.mark_used(&mut db_tx, transaction.id).await?;
+
+        // As in create_in, the owner's row is locked before the wallets
+        let credit_owner = Self::wallet_owner(&mut db_tx, Some(credit_wallet_id)).await?;
+        if let Some(owner) = &credit_owner {
+            Wallet::lock_owner(&mut db_tx, owner.user_id).await?;
+        }
+
+        Self::lock_wallets(&mut db_tx, &transaction).await?;
+        Self::require_currency(&mut db_tx, debit_wallet_id, &quote.from_currency).await?;
+        Self::require_currency(&mut db_tx, credit_wallet_id, &quote.to_currency).await?;
+
+        let debit_wallet = Self::locked_wallet(&mut db_tx, debit_wallet_id).await?;
+        if debit_wallet.user_id != quote.user_id {
+            return Err(TransactionError::InvalidTransaction(
+                "Quote was issued to another user".to_string(),
+            ));
+        }
+
+        // The converted amount counts towards the target currency's cap
+        if let Some(owner) = &credit_owner {
+            let converted = Money::new(quote.to_amount, &quote.to_currency)?;
+            KycLimit::enforce_balance(&mut db_tx, owner.user_id, owner.kyc_level, &converted).await?;
+        }
+
+        transaction.start_lifecycle(&mut db_tx).await?;
+
+        db_tx.commit().await?;
+
+        Ok(transaction)
+    }
+
+    /// Validates, limit-checks, prices, inserts and starts a transaction
+    /// inside the caller's database transaction. Transactions that settle
+    /// immediately are completed before this returns. A transaction with a
+    /// parent (a refund or payout of funds the parent already moved) is not
+    /// charged a fee.
+    pub async fn create_in(
+        db_tx: &mut Transaction<'_, Postgres>,
+        debit_wallet_id: Option<Uuid>,
+        credit_wallet_id: Option<Uuid>,
+        money: &Money,
+        transaction_type: TransactionType,
+        reference_id: Option<String>,
+        metadata: Option<Value>,
+        parent_transaction_id: Option<Uuid>,
     ) -> Result<Self, TransactionError> {
         // Validate transaction
         if debit_wallet_id.is_none() && credit_wallet_id.is_none() {
@@ -79,98 +314,636 @@ impl Transaction {
             ));
         }
 
-        // Start a database transaction
-        let mut db_tx = pool.begin().await?;
+        if transaction_type == TransactionType::Conversion {
+            return Err(TransactionError::InvalidTransaction(
+                "Conversions must be created from an FX quote".to_string(),
+            ));
+        }
+
+        if !money.is_positive() {
+            return Err(TransactionError::InvalidTransaction(
+                "Amount must be positive".to_string(),
+            ));
+        }
+
+        let debit_owner = Self::wallet_owner(db_tx, debit_wallet_id).await?;
+        let credit_owner = Self::wallet_owner(db_tx, credit_wallet_id).await?;
+
+        // Lock the owners in id order so limit checks for a user run one at a
+        // time. User rows are always locked before wallet rows.
+        let mut owner_ids: Vec<Uuid> = debit_owner
+            .iter()
+            .chain(&credit_owner)
+            .map(|owner| owner.user_id)
+            .collect();
+        owner_ids.sort();
+        owner_ids.dedup();
+        for user_id in owner_ids {
+            Wallet::lock_owner(db_tx, user_id).await?;
+        }
+
+        // Enforce the KYC tier limits. Refunds and payouts of money already
+        // sent are exempt from outgoing caps, and refunds from balance caps.
+        if let (Some(owner), None) = (&debit_owner, parent_transaction_id) {
+            KycLimit::enforce_outgoing(db_tx, owner.user_id, owner.kyc_level, &transaction_type, money)
+                .await?;
+        }
+        if let Some(owner) = &credit_owner {
+            let between_own_wallets = debit_owner.as_ref().map(|debit| debit.user_id) == Some(owner.user_id);
+            if transaction_type != TransactionType::Refund && !between_own_wallets {
+                KycLimit::enforce_balance(db_tx, owner.user_id, owner.kyc_level, money).await?;
+            }
+        }
+
+        // Price the transaction for the paying wallet's owner
+        let kyc_level = debit_owner
+            .as_ref()
+            .or(credit_owner.as_ref())
+            .map(|owner| owner.kyc_level)
+            .unwrap_or(0);
+
+        let quote = match parent_transaction_id {
+            Some(_) => FeeQuote::free(transaction_type.clone(), money),
+            None => {
+                FeeQuote::calculate(
+                    &mut **db_tx,
+                    transaction_type.clone(),
+                    &money.currency,
+                    kyc_level,
+                    money.amount,
+                )
+                .await?
+            }
+        };
+
+        Self::insert_in(
+            db_tx,
+            debit_wallet_id,
+            credit_wallet_id,
+            money,
+            transaction_type,
+            reference_id,
+            metadata,
+            parent_transaction_id,
+            quote,
+        )
+        .await
+    }
+
+    /// Moves a wallet's whole balance into another wallet of the same
+    /// currency, free of fees, inside the caller's database transaction.
+    /// Used to empty a wallet that is being closed; the source must have no
+    /// active holds.
+    pub async fn sweep_in(
+        db_tx: &mut Transaction<'_, Postgres>,
+        from_wallet_id: Uuid,
+        to_wallet_id: Uuid,
+        metadata: Option<Value>,
+    ) -> Result<Self, TransactionError> {
+        if from_wallet_id == to_wallet_id {
+            return Err(TransactionError::InvalidTransaction(
+                "Debit and credit wallets cannot be the same".to_string(),
+            ));
+        }
+
+        // Lock in id order, as every other transaction does
+        let (first, second) = if from_wallet_id < to_wallet_id {
+            (from_wallet_id, to_wallet_id)
+        } else {
+            (to_wallet_id, from_wallet_id)
+        };
+        Self::locked_wallet(db_tx, first).await?;
+        Self::locked_wallet(db_tx, second).await?;
+
+        let from_wallet = Self::locked_wallet(db_tx, from_wallet_id).await?;
+        if from_wallet.held_balance != Decimal::ZERO {
+            return Err(TransactionError::InvalidTransaction(
+                "Wallet has active holds; release them before sweeping".to_string(),
+            ));
+        }
+
+        let money = from_wallet.money(from_wallet.balance);
+        if !money.is_positive() {
+            return Err(TransactionError::InvalidTransaction(
+                "Wallet has no balance to sweep".to_string(),
+            ));
+        }
+
+        let quote = FeeQuote::free(TransactionType::Transfer, &money);
+        Self::insert_in(
+            db_tx,
+            Some(from_wallet_id),
+            Some(to_wallet_id),
+            &money,
+            TransactionType::Transfer,
+            None,
+            metadata,
+            None,
+            quote,
+        )
+        .await
+    }
+
+    /// Inserts a priced transaction, locks its wallets and starts its
+    /// lifecycle
+    async fn insert_in(
+        db_tx: &mut Transaction<'_, Postgres>,
+        debit_wallet_id: Option<Uuid>,
+        credit_wallet_id: Option<Uuid>,
+        money: &Money,
+        transaction_type: TransactionType,
+        reference_id: Option<String>,
+        metadata: Option<Value>,
+        parent_transaction_id: Option<Uuid>,
+        quote: FeeQuote,
+    ) -> Result<Self, TransactionError> {
+        if quote.total <= Decimal::ZERO {
+            return Err(TransactionError::InvalidTransaction(
+                "Amount does not cover the fee".to_string(),
+            ));
+        }
+
+        let fee_breakdown = serde_json::to_value(&quote.fee)
+            .map_err(|e| TransactionError::InvalidTransaction(e.to_string()))?;
+        let metadata = telemetry::stamp_metadata(metadata);
 
         // Create the transaction record
-        let transaction = sqlx::query_as!(
+        let mut transaction = sqlx::query_as!(
             Transaction,
             r#"
             INSERT INTO transactions (
-                debit_wallet_id, credit_wallet_id, amount, currency,
-                transaction_type, reference_id, metadata
+                debit_wallet_id, credit_wallet_id, amount, currency, transaction_type,
+                reference_id, metadata, fee_amount, fee_breakdown, parent_transaction_id
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
             RETURNING *
             "#,
             debit_wallet_id,
             credit_wallet_id,
-            amount,
-            currency,
+            money.amount,
+            money.currency,
             transaction_type as TransactionType,
             reference_id,
-            metadata
+            metadata,
+            quote.fee.total,
+            fee_breakdown,
+            parent_transaction_id
         )
-        .fetch_one(&mut *db_tx)
+        .fetch_one(&mut **db_tx)
         .await?;
 
-        // Process wallet updates
-        Self::process_wallet_updates(&mut db_tx, &transaction).await?;
+        // Lock both wallets before any balance effect is applied
+        Self::lock_wallets(db_tx, &transaction).await?;
 
-        // Commit the transaction
-        db_tx.commit().await?;
+        // Both wallets must hold the transaction currency
+        for wallet_id in debit_wallet_id.into_iter().chain(credit_wallet_id) {
+            Self::require_currency(db_tx, wallet_id, &transaction.currency).await?;
+        }
+
+        transaction.start_lifecycle(db_tx).await?;
 
         Ok(transaction)
     }
 
-    /// Process wallet balance updates within a database transaction
-    async fn process_wallet_updates(
+    /// Enters Pending and, for movements that settle immediately, runs the
+    /// transaction through to Completed
+    async fn start_lifecycle(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), TransactionError> {
+        // Entering Pending places a hold on the debited funds
+        Self::apply_status_effects(db_tx, self, &TransactionStatus::Pending).await?;
+        Self::record_status_change(db_tx, self.id, None, &TransactionStatus::Pending, None).await?;
+
+        if self.transaction_type.settles_immediately() {
+            self.transition_in(db_tx, TransactionStatus::Processing, None).await?;
+            self.transition_in(db_tx, TransactionStatus::Completed, None).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn wallet_owner(
+        db_tx: &mut Transaction<'_, Postgres>,
+        wallet_id: Option<Uuid>,
+    ) -> Result<Option<WalletOwner>, TransactionError> {
+        let Some(wallet_id) = wallet_id else {
+            return Ok(None);
+        };
+
+        let owner = sqlx::query_as!(
+            WalletOwner,
+            r#"
+            SELECT w.user_id, u.kyc_level FROM wallets w
+            JOIN users u ON u.id = w.user_id
+            WHERE w.id = $1
+            "#,
+            wallet_id
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?;
+
+        Ok(owner)
+    }
+
+    async fn require_currency(
+        db_tx: &mut Transaction<'_, Postgres>,
+        wallet_id: Uuid,
+        currency: &str,
+    ) -> Result<(), TransactionError> {
+        let wallet = Self::locked_wallet(db_tx, wallet_id).await?;
+        if wallet.currency != currency {
+            return Err(TransactionError::InvalidTransaction(format!(
+                "Wallet {} holds {}, not {}",
+                wallet_id, wallet.currency, currency
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Locks the transaction's wallets in ascending id order so that two
+    /// transfers moving money in opposite directions cannot deadlock. Later
+    /// lookups in the same database transaction reuse the locks.
+    async fn lock_wallets(
         db_tx: &mut Transaction<'_, Postgres>,
         transaction: &Transaction,
     ) -> Result<(), TransactionError> {
-        // Update debit wallet if exists
-        if let Some(debit_id) = transaction.debit_wallet_id {
-            let mut debit_wallet = Wallet::find_by_id(db_tx, debit_id)
-                .await?
-                .ok_or_else(|| TransactionError::InvalidTransaction("Debit wallet not found".to_string()))?;
+        let mut wallet_ids: Vec<Uuid> = transaction
+            .debit_wallet_id
+            .into_iter()
+            .chain(transaction.credit_wallet_id)
+            .collect();
+        wallet_ids.sort();
 
-            debit_wallet.can_debit(transaction.amount)?;
-            debit_wallet
-                .update_balance(db_tx, -transaction.amount)
-                .await?;
+        for wallet_id in wallet_ids {
+            Self::locked_wallet(db_tx, wallet_id).await?;
         }
 
-        // Update credit wallet if exists
-        if let Some(credit_id) = transaction.credit_wallet_id {
-            let mut credit_wallet = Wallet::find_by_id(db_tx, credit_id)
-                .await?
-                .ok_or_else(|| {
-                    TransactionError::InvalidTransaction("Credit wallet not found".to_string())
-                })?;
+        Ok(())
+    }
 
-            credit_wallet.can_credit(transaction.amount)?;
-            credit_wallet
-                .update_balance(db_tx, transaction.amount)
-                .await?;
+    async fn locked_wallet(
+        db_tx: &mut Transaction<'_, Postgres>,
+        wallet_id: Uuid,
+    ) -> Result<Wallet, TransactionError> {
+        Wallet::find_by_id_for_update(db_tx, wallet_id)
+            .await?
+            .ok_or_else(|| TransactionError::InvalidTransaction(format!("Wallet {} not found", wallet_id)))
+    }
+
+    /// Applies the balance effect of entering a status:
+    /// - Pending: a hold is placed on the debit wallet for the amount plus fee
+    /// - Processing: no effect
+    /// - Completed: the hold is captured, the amount moves to the credit
+    ///   wallet and the fee to the fee income account; a missing side is
+    ///   booked against the contra account, and deposits are credited net of
+    ///   the fee
+    /// - Failed: the hold is released
+    /// - Reversed: no effect; the reversal transaction moves the money back
+    async fn apply_status_effects(
+        db_tx: &mut Transaction<'_, Postgres>,
+        transaction: &Transaction,
+        status: &TransactionStatus,
+    ) -> Result<(), TransactionError> {
+        let amount = transaction.amount;
+        let fee = transaction.fee_amount;
+        let currency = &transaction.currency;
+        let contra_type = transaction.transaction_type.contra_account_type();
+
+        match status {
+            TransactionStatus::Pending => {
+                if let Some(debit_id) = transaction.debit_wallet_id {
+                    let wallet = Self::locked_wallet(db_tx, debit_id).await?;
+                    WalletHold::place(db_tx, &wallet, amount + fee, Some(transaction.id), None, None)
+                        .await?;
+                }
+            }
+            TransactionStatus::Processing
+            | TransactionStatus::Reversed
+            | TransactionStatus::PartiallyReversed => {}
+            TransactionStatus::Completed
+                if transaction.transaction_type == TransactionType::Conversion =>
+            {
+                Self::settle_conversion(db_tx, transaction).await?;
+            }
+            TransactionStatus::Completed => {
+                // The fee comes out of the debit wallet on top of the amount,
+                // or out of the credited amount when there is no debit wallet
+                let (from, debited, credited) = match transaction.debit_wallet_id {
+                    Some(debit_id) => {
+                        let mut hold = Self::transaction_hold(db_tx, transaction.id).await?;
+                        hold.capture_in(db_tx, None).await?;
+                        let account = LedgerAccount::for_wallet(db_tx, debit_id, currency).await?;
+                        (account, amount + fee, amount)
+                    }
+                    None => {
+                        let account = LedgerAccount::system(db_tx, contra_type, currency).await?;
+                        (account, amount, amount - fee)
+                    }
+                };
+
+                let to = match transaction.credit_wallet_id {
+                    Some(credit_id) => {
+                        let mut wallet = Self::locked_wallet(db_tx, credit_id).await?;
+                        wallet.can_credit(credited)?;
+                        wallet.update_balance(db_tx, credited).await?;
+                        LedgerAccount::for_wallet(db_tx, credit_id, currency).await?
+                    }
+                    None => LedgerAccount::system(db_tx, contra_type, currency).await?,
+                };
+
+                let mut legs = vec![(from.id, -debited), (to.id, credited)];
+                if fee > Decimal::ZERO {
+                    let fee_income =
+                        LedgerAccount::system(db_tx, LedgerAccountType::FeeIncome, currency).await?;
+                    legs.push((fee_income.id, fee));
+                }
+
+                Self::post_entry(db_tx, transaction, "settle", &legs).await?;
+            }
+            TransactionStatus::Failed => {
+                if transaction.debit_wallet_id.is_some() {
+                    let mut hold = Self::transaction_hold(db_tx, transaction.id).await?;
+                    hold.release_in(db_tx, HoldStatus::Released).await?;
+                }
+            }
         }
 
         Ok(())
     }
 
-    /// Updates transaction status
-    pub async fn update_status(
+    /// Settles a conversion at its quoted rate. Each currency balances on its
+    /// own: the source amount moves into the FX position, and the FX position
+    /// pays out the mid-rate equivalent split between the credit wallet and
+    /// the spread account.
+    async fn settle_conversion(
+        db_tx: &mut Transaction<'_, Postgres>,
+        transaction: &Transaction,
+    ) -> Result<(), TransactionError> {
+        let (Some(debit_id), Some(credit_id), Some(quote_id)) = (
+            transaction.debit_wallet_id,
+            transaction.credit_wallet_id,
+            transaction.fx_quote_id,
+        ) else {
+            return Err(TransactionError::InvalidTransaction(
+                "Conversion needs both wallets and a quote".to_string(),
+            ));
+        };
+
+        let quote = FxQuote::find_by_id(db_tx, quote_id)
+            .await?
+            .ok_or(FxError::QuoteNotFound)?;
+
+        let mut hold = Self::transaction_hold(db_tx, transaction.id).await?;
+        hold.capture_in(db_tx, None).await?;
+
+        let mut credit_wallet = Self::locked_wallet(db_tx, credit_id).await?;
+        credit_wallet.can_credit(quote.to_amount)?;
+        credit_wallet.update_balance(db_tx, quote.to_amount).await?;
+
+        let debit_account = LedgerAccount::for_wallet(db_tx, debit_id, &quote.from_currency).await?;
+        let credit_account = LedgerAccount::for_wallet(db_tx, credit_id, &quote.to_currency).await?;
+        let source_position =
+            LedgerAccount::system(db_tx, LedgerAccountType::FxPosition, &quote.from_currency).await?;
+        let target_position =
+            LedgerAccount::system(db_tx, LedgerAccountType::FxPosition, &quote.to_currency).await?;
+
+        let mid_to_amount = quote.mid_to_amount();
+        let spread = mid_to_amount - quote.to_amount;
+
+        let mut legs = vec![
+            PostingLeg {
+                account: debit_account.id,
+                amount: -quote.from_amount,
+                currency: quote.from_currency.clone(),
+            },
+            PostingLeg {
+                account: source_position.id,
+                amount: quote.from_amount,
+                currency: quote.from_currency.clone(),
+            },
+            PostingLeg {
+                account: target_position.id,
+                amount: -mid_to_amount,
+                currency: quote.to_currency.clone(),
+            },
+            PostingLeg {
+                account: credit_account.id,
+                amount: quote.to_amount,
+                currency: quote.to_currency.clone(),
+            },
+        ];
+        if spread > Decimal::ZERO {
+            let spread_account =
+                LedgerAccount::system(db_tx, LedgerAccountType::FxSpread, &quote.to_currency).await?;
+            legs.push(PostingLeg {
+                account: spread_account.id,
+                amount: spread,
+                currency: quote.to_currency.clone(),
+            });
+        }
+
+        JournalEntry::post(db_tx, Some(transaction.id), "conversion:settle", &legs).await?;
+
+        Ok(())
+    }
+
+    async fn transaction_hold(
+        db_tx: &mut Transaction<'_, Postgres>,
+        transaction_id: Uuid,
+    ) -> Result<WalletHold, TransactionError> {
+        WalletHold::find_active_for_transaction(db_tx, transaction_id)
+            .await?
+            .ok_or_else(|| {
+                TransactionError::InvalidTransaction("No active hold for transaction".to_string())
+            })
+    }
+
+    /// Posts the journal entry for a stage of the transaction from
+    /// `(account, amount)` legs in the transaction currency
+    async fn post_entry(
+        db_tx: &mut Transaction<'_, Postgres>,
+        transaction: &Transaction,
+        stage: &str,
+        legs: &[(Uuid, Decimal)],
+    ) -> Result<JournalEntry, TransactionError> {
+        let legs: Vec<PostingLeg> = legs
+            .iter()
+            .map(|(account, amount)| PostingLeg {
+                account: *account,
+                amount: *amount,
+                currency: transaction.currency.clone(),
+            })
+            .collect();
+
+        let description = format!("{:?}:{}", transaction.transaction_type, stage).to_lowercase();
+        let entry = JournalEntry::post(db_tx, Some(transaction.id), &description, &legs).await?;
+
+        Ok(entry)
+    }
+
+    /// Moves the transaction to a new status inside an open database
+    /// transaction, applying its balance effect and recording the change
+    async fn transition_in(
         &mut self,
-        pool: &PgPool,
+        db_tx: &mut Transaction<'_, Postgres>,
         status: TransactionStatus,
+        reason: Option<String>,
     ) -> Result<(), TransactionError> {
+        if !self.status.can_transition_to(&status) {
+            return Err(TransactionError::InvalidStatusTransition {
+                from: self.status.clone(),
+                to: status,
+            });
+        }
+
+        Self::apply_status_effects(db_tx, self, &status).await?;
+
         let result = sqlx::query!(
             r#"
             UPDATE transactions
             SET status = $1, updated_at = CURRENT_TIMESTAMP
-            WHERE id = $2
+            WHERE id = $2 AND status = $3
             "#,
-            status as TransactionStatus,
-            self.id
+            status.clone() as TransactionStatus,
+            self.id,
+            self.status.clone() as TransactionStatus,
         )
-        .execute(pool)
+        .execute(&mut **db_tx)
         .await?;
 
-        if result.rows_affected() == 1 {
-            self.status = status;
-            Ok(())
-        } else {
-            Err(TransactionError::NotFound)
+        if result.rows_affected() != 1 {
+            return Err(TransactionError::NotFound);
         }
+
+        Self::record_status_change(db_tx, self.id, Some(&self.status), &status, reason).await?;
+        self.status = status;
+
+        Ok(())
+    }
+
+    async fn record_status_change(
+        db_tx: &mut Transaction<'_, Postgres>,
+        transaction_id: Uuid,
+        from_status: Option<&TransactionStatus>,
+        to_status: &TransactionStatus,
+        reason: Option<String>,
+    ) -> Result<(), TransactionError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO transaction_status_history (transaction_id, from_status, to_status, reason)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            transaction_id,
+            from_status.cloned() as Option<TransactionStatus>,
+            to_status.clone() as TransactionStatus,
+            reason,
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Updates transaction status, rejecting transitions the lifecycle does
+    /// not allow
+    pub async fn update_status(
+        &mut self,
+        pool: &PgPool,
+        status: TransactionStatus,
+        reason: Option<String>,
+    ) -> Result<(), TransactionError> {
+        let mut db_tx = pool.begin().await?;
+
+        // Re-read under lock so concurrent transitions are serialised
+        *self = sqlx::query_as!(
+            Transaction,
+            r#"
+            SELECT * FROM transactions WHERE id = $1
+            FOR UPDATE
+            "#,
+            self.id
+        )
+        .fetch_optional(&mut *db_tx)
+        .await?
+        .ok_or(TransactionError::NotFound)?;
+
+        Self::lock_wallets(&mut db_tx, self).await?;
+        self.transition_in(&mut db_tx, status, reason).await?;
+
+        db_tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Records the bank's progress on a deposit or withdrawal inside the
+    /// caller's database transaction, returning the status it moved from.
+    /// Only Processing, Completed and Failed can be reached this way, and
+    /// completing a Pending transaction passes through Processing.
+    pub async fn settle_in(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        status: TransactionStatus,
+        reason: Option<String>,
+    ) -> Result<TransactionStatus, TransactionError> {
+        self.lock_in(db_tx).await?;
+
+        if self.transaction_type.settles_immediately() {
+            return Err(TransactionError::InvalidTransaction(
+                "Only deposits and withdrawals are settled by the bank".to_string(),
+            ));
+        }
+        if !matches!(
+            status,
+            TransactionStatus::Processing | TransactionStatus::Completed | TransactionStatus::Failed
+        ) {
+            return Err(TransactionError::InvalidTransaction(format!(
+                "Settlement cannot move a transaction to {:?}",
+                status
+            )));
+        }
+
+        let previous_status = self.status.clone();
+        Self::lock_wallets(db_tx, self).await?;
+
+        if status == TransactionStatus::Completed && self.status == TransactionStatus::Pending {
+            self.transition_in(db_tx, TransactionStatus::Processing, reason.clone())
+                .await?;
+        }
+        self.transition_in(db_tx, status, reason).await?;
+
+        Ok(previous_status)
+    }
+
+    /// The transaction amount in its currency
+    pub fn money(&self) -> Money {
+        Money::from_stored(self.amount, &self.currency)
+    }
+
+    /// The fee charged on top of (or, for deposits, out of) the amount
+    pub fn fee(&self) -> Money {
+        Money::from_stored(self.fee_amount, &self.currency)
+    }
+
+    /// Gets the status history of a transaction, oldest first
+    pub async fn status_history(
+        &self,
+        pool: &PgPool,
+    ) -> Result<Vec<TransactionStatusChange>, TransactionError> {
+        let history = sqlx::query_as!(
+            TransactionStatusChange,
+            r#"
+            SELECT * FROM transaction_status_history
+            WHERE transaction_id = $1
+            ORDER BY created_at ASC
+            "#,
+            self.id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(history)
     }
 
     /// Retrieves a transaction by ID
@@ -188,6 +961,61 @@ impl Transaction {
         Ok(transaction)
     }
 
+    /// Lists transactions touching any of a user's wallets, newest first
+    pub async fn list(
+        pool: &PgPool,
+        user_id: Uuid,
+        wallet_id: Option<Uuid>,
+        transaction_type: Option<TransactionType>,
+        status: Option<TransactionStatus>,
+        start_date: Option<DateTime<Utc>>,
+        end_date: Option<DateTime<Utc>>,
+        page: &PageRequest,
+    ) -> Result<Page<Self>, TransactionError> {
+        let push_filters = |query: &mut QueryBuilder<'_, Postgres>| {
+            query.push(
+                " WHERE (debit_wallet_id IN (SELECT id FROM wallets WHERE user_id = ",
+            );
+            query.push_bind(user_id);
+            query.push(") OR credit_wallet_id IN (SELECT id FROM wallets WHERE user_id = ");
+            query.push_bind(user_id);
+            query.push("))");
+
+            if let Some(wallet_id) = wallet_id {
+                query.push(" AND (debit_wallet_id = ");
+                query.push_bind(wallet_id);
+                query.push(" OR credit_wallet_id = ");
+                query.push_bind(wallet_id);
+                query.push(")");
+            }
+            if let Some(transaction_type) = transaction_type.clone() {
+                query.push(" AND transaction_type = ");
+                query.push_bind(transaction_type);
+            }
+            if let Some(status) = status.clone() {
+                query.push(" AND status = ");
+                query.push_bind(status);
+            }
+            if let Some(start_date) = start_date {
+                query.push(" AND created_at >= ");
+                query.push_bind(start_date);
+            }
+            if let Some(end_date) = end_date {
+                query.push(" AND created_at <= ");
+                query.push_bind(end_date);
+            }
+        };
+
+        let mut query = QueryBuilder::new("SELECT * FROM transactions");
+        push_filters(&mut query);
+        page.push_to(&mut query, "transactions");
+        let rows = query.build_query_as::<Transaction>().fetch_all(pool).await?;
+
+        let total = page.count(pool, "transactions", push_filters).await?;
+
+        Ok(Page::from_rows(rows, page, total))
+    }
+
     /// Gets all transactions for a wallet
     pub async fn find_by_wallet(
         pool: &PgPool,
@@ -213,39 +1041,427 @@ impl Transaction {
         Ok(transactions)
     }
 
-    /// Reverses a transaction if possible
+    /// How much of the transaction can be handed back in total: the amount
+    /// the credit side received. Fees are not refunded.
+    pub fn reversible_amount(&self) -> Decimal {
+        match self.transaction_type {
+            TransactionType::Deposit => self.amount - self.fee_amount,
+            _ => self.amount,
+        }
+    }
+
+    /// What is left of the reversible amount once refunds and payouts made
+    /// against the transaction are taken off
+    async fn outstanding_amount(
+        &self,
+        db_tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Decimal, TransactionError> {
+        let drawn = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(amount), 0) AS "drawn!"
+            FROM transactions
+            WHERE parent_transaction_id = $1 AND status <> 'failed'
+            "#,
+            self.id
+        )
+        .fetch_one(&mut **db_tx)
+        .await?;
+
+        Ok(self.reversible_amount() - drawn)
+    }
+
+    /// Re-reads the transaction and locks its row so concurrent refunds and
+    /// payouts cannot overshoot it
+    async fn lock_in(&mut self, db_tx: &mut Transaction<'_, Postgres>) -> Result<(), TransactionError> {
+        *self = sqlx::query_as!(
+            Transaction,
+            r#"
+            SELECT * FROM transactions WHERE id = $1
+            FOR UPDATE
+            "#,
+            self.id
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?
+        .ok_or(TransactionError::NotFound)?;
+
+        Ok(())
+    }
+
+    /// Refunds all of the outstanding amount, or part of it when `amount` is
+    /// given. The refund is linked through `parent_transaction_id` and the
+    /// original becomes PartiallyReversed until nothing is left to refund.
     pub async fn reverse(
         &mut self,
         pool: &PgPool,
+        amount: Option<Decimal>,
         reason: Option<String>,
     ) -> Result<Transaction, TransactionError> {
-        if self.status != TransactionStatus::Completed {
+        let mut db_tx = pool.begin().await?;
+        let reversal = self.reverse_in(&mut db_tx, amount, reason).await?;
+        db_tx.commit().await?;
+
+        Ok(reversal)
+    }
+
+    /// Refunds the transaction inside the caller's database transaction
+    pub async fn reverse_in(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        amount: Option<Decimal>,
+        reason: Option<String>,
+    ) -> Result<Transaction, TransactionError> {
+        if self.transaction_type == TransactionType::Conversion {
+            return Err(TransactionError::InvalidTransaction(
+                "Conversions cannot be reversed; convert back with a new quote".to_string(),
+            ));
+        }
+
+        self.lock_in(db_tx).await?;
+
+        if !matches!(
+            self.status,
+            TransactionStatus::Completed | TransactionStatus::PartiallyReversed
+        ) {
             return Err(TransactionError::InvalidTransaction(
                 "Only completed transactions can be reversed".to_string(),
             ));
         }
 
-        let mut metadata = self.metadata.clone().unwrap_or_else(|| serde_json::json!({}));
-        if let Some(reason_text) = reason {
+        let outstanding = self.outstanding_amount(db_tx).await?;
+        let refund = Money::new(amount.unwrap_or(outstanding), &self.currency)?;
+        if !refund.is_positive() || refund.amount > outstanding {
+            return Err(TransactionError::InvalidTransaction(format!(
+                "Reversal must be between 0 and the outstanding {}",
+                Money::from_stored(outstanding, &self.currency)
+            )));
+        }
+
+        let mut metadata = serde_json::json!({ "reversal_of": self.id });
+        if let Some(reason_text) = &reason {
             metadata["reverse_reason"] = serde_json::json!(reason_text);
         }
 
         // Create reversal transaction
-        let reversal = Self::create(
-            pool,
+        let reversal = Self::create_in(
+            db_tx,
             self.credit_wallet_id, // Swap debit and credit
             self.debit_wallet_id,
-            self.amount,
-            self.currency.clone(),
+            &refund,
             TransactionType::Refund,
-            Some(format!("reversal_{}", self.id)),
+            None,
             Some(metadata),
+            Some(self.id),
         )
         .await?;
 
         // Update original transaction status
-        self.update_status(pool, TransactionStatus::Reversed).await?;
+        let status = if refund.amount == outstanding {
+            TransactionStatus::Reversed
+        } else {
+            TransactionStatus::PartiallyReversed
+        };
+        if status != self.status {
+            let reason = match reason {
+                Some(reason_text) => format!("reversal {}: {}", reversal.id, reason_text),
+                None => format!("reversal {}", reversal.id),
+            };
+            self.transition_in(db_tx, status, Some(reason)).await?;
+        }
 
         Ok(reversal)
     }
+
+    /// Pays a transfer that was parked in its contra account, such as a P2P
+    /// transfer to someone not yet registered, out to `credit_wallet_id`.
+    /// The payout is linked to the original and carries no fee of its own.
+    pub async fn pay_out_in(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        credit_wallet_id: Uuid,
+        reference_id: Option<String>,
+        metadata: Option<Value>,
+    ) -> Result<Transaction, TransactionError> {
+        self.lock_in(db_tx).await?;
+
+        if self.transaction_type != TransactionType::Transfer
+            || self.credit_wallet_id.is_some()
+            || self.status != TransactionStatus::Completed
+        {
+            return Err(TransactionError::InvalidTransaction(
+                "Only completed transfers without a recipient wallet can be paid out".to_string(),
+            ));
+        }
+
+        let outstanding = self.outstanding_amount(db_tx).await?;
+        if outstanding != self.reversible_amount() {
+            return Err(TransactionError::InvalidTransaction(
+                "Transfer has already been paid out or refunded".to_string(),
+            ));
+        }
+
+        Self::create_in(
+            db_tx,
+            None,
+            Some(credit_wallet_id),
+            &self.money(),
+            TransactionType::Transfer,
+            reference_id,
+            metadata,
+            Some(self.id),
+        )
+        .await
+    }
+
+    /// Gets the whole reversal chain the transaction belongs to: the
+    /// original transaction first, then every refund made against it (and
+    /// against those refunds) in creation order
+    pub async fn reversal_chain(&self, pool: &PgPool) -> Result<Vec<Transaction>, TransactionError> {
+        let chain = sqlx::query_as!(
+            Transaction,
+            r#"
+            WITH RECURSIVE ancestors AS (
+                SELECT id, parent_transaction_id FROM transactions WHERE id = $1
+                UNION ALL
+                SELECT t.id, t.parent_transaction_id
+                FROM transactions t
+                JOIN ancestors a ON t.id = a.parent_transaction_id
+            ),
+            chain AS (
+                SELECT id FROM ancestors WHERE parent_transaction_id IS NULL
+                UNION ALL
+                SELECT t.id
+                FROM transactions t
+                JOIN chain c ON t.parent_transaction_id = c.id
+            )
+            SELECT t.* FROM transactions t
+            JOIN chain c ON c.id = t.id
+            ORDER BY t.created_at, t.id
+            "#,
+            self.id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        ledger::LedgerAccount,
+        testing::{complete_deposit, create_verified_user},
+        user::{KycStatus, User},
+    };
+
+    /// A KYC level 1 user's USD wallet holding `balance`
+    async fn funded_wallet(pool: &PgPool, email: &str, balance: Decimal) -> Wallet {
+        let user = create_verified_user(pool, email, 1).await;
+        let wallet = Wallet::create(pool, user.id, "USD", None).await.unwrap();
+        complete_deposit(pool, &wallet, balance).await;
+
+        Wallet::find_by_id(pool, wallet.id).await.unwrap().unwrap()
+    }
+
+    async fn transfer(pool: PgPool, from: Uuid, to: Uuid, amount: Decimal) -> bool {
+        Transaction::create(
+            &pool,
+            Some(from),
+            Some(to),
+            amount,
+            "USD".to_string(),
+            TransactionType::Transfer,
+            None,
+            None,
+        )
+        .await
+        .is_ok()
+    }
+
+    #[test]
+    fn test_status_transitions() {
+        use TransactionStatus::*;
+
+        assert!(Pending.can_transition_to(&Processing));
+        assert!(Processing.can_transition_to(&Completed));
+        assert!(Completed.can_transition_to(&Reversed));
+        assert!(Completed.can_transition_to(&PartiallyReversed));
+        assert!(PartiallyReversed.can_transition_to(&Reversed));
+        assert!(!Pending.can_transition_to(&Completed));
+        assert!(!Failed.can_transition_to(&Completed));
+        for next in [Pending, Processing, Completed, Failed, Reversed, PartiallyReversed] {
+            assert!(!Reversed.can_transition_to(&next));
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_parallel_opposing_transfers_keep_balances(pool: PgPool) {
+        let a = funded_wallet(&pool, "a@example.com", Decimal::new(100, 0)).await;
+        let b = funded_wallet(&pool, "b@example.com", Decimal::new(100, 0)).await;
+
+        let mut handles = Vec::new();
+        for _ in 0..10 {
+            handles.push(tokio::spawn(transfer(pool.clone(), a.id, b.id, Decimal::new(5, 0))));
+            handles.push(tokio::spawn(transfer(pool.clone(), b.id, a.id, Decimal::new(3, 0))));
+        }
+        for handle in handles {
+            assert!(handle.await.unwrap());
+        }
+
+        let a = Wallet::find_by_id(&pool, a.id).await.unwrap().unwrap();
+        let b = Wallet::find_by_id(&pool, b.id).await.unwrap().unwrap();
+        assert_eq!(a.balance, Decimal::new(80, 0));
+        assert_eq!(b.balance, Decimal::new(120, 0));
+        assert!(LedgerAccount::wallet_discrepancies(&pool).await.unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_parallel_transfers_cannot_overdraw(pool: PgPool) {
+        let a = funded_wallet(&pool, "a@example.com", Decimal::new(100, 0)).await;
+        let b = funded_wallet(&pool, "b@example.com", Decimal::ONE).await;
+
+        let handles: Vec<_> = (0..15)
+            .map(|_| tokio::spawn(transfer(pool.clone(), a.id, b.id, Decimal::new(10, 0))))
+            .collect();
+
+        let mut succeeded = 0;
+        for handle in handles {
+            if handle.await.unwrap() {
+                succeeded += 1;
+            }
+        }
+
+        let a = Wallet::find_by_id(&pool, a.id).await.unwrap().unwrap();
+        let b = Wallet::find_by_id(&pool, b.id).await.unwrap().unwrap();
+        assert_eq!(succeeded, 10);
+        assert_eq!(a.balance, Decimal::ZERO);
+        assert_eq!(b.balance, Decimal::new(101, 0));
+    }
+
+    #[sqlx::test]
+    async fn test_partial_reversals_build_a_chain(pool: PgPool) {
+        let a = funded_wallet(&pool, "a@example.com", Decimal::new(100, 0)).await;
+        let b = funded_wallet(&pool, "b@example.com", Decimal::ONE).await;
+
+        let mut original = Transaction::create(
+            &pool,
+            Some(a.id),
+            Some(b.id),
+            Decimal::new(40, 0),
+            "USD".to_string(),
+            TransactionType::Transfer,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        original.reverse(&pool, Some(Decimal::new(15, 0)), None).await.unwrap();
+        assert_eq!(original.status, TransactionStatus::PartiallyReversed);
+
+        // Only the outstanding 25 can still be refunded
+        assert!(original.reverse(&pool, Some(Decimal::new(30, 0)), None).await.is_err());
+        original.reverse(&pool, None, Some("customer request".to_string())).await.unwrap();
+        assert_eq!(original.status, TransactionStatus::Reversed);
+        assert!(original.reverse(&pool, None, None).await.is_err());
+
+        let chain = original.reversal_chain(&pool).await.unwrap();
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain[0].id, original.id);
+        assert!(chain[1..].iter().all(|t| t.parent_transaction_id == Some(original.id)));
+
+        let a = Wallet::find_by_id(&pool, a.id).await.unwrap().unwrap();
+        assert_eq!(a.balance, Decimal::new(100, 0));
+        assert!(LedgerAccount::wallet_discrepancies(&pool).await.unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_every_currency_has_limits(pool: PgPool) {
+        let a = funded_wallet(&pool, "a@example.com", Decimal::new(100, 0)).await;
+        let tzs = Wallet::create(&pool, a.user_id, "TZS", None).await.unwrap();
+
+        let deposit = Transaction::create(
+            &pool,
+            None,
+            Some(tzs.id),
+            Decimal::new(50_000, 0),
+            "TZS".to_string(),
+            TransactionType::Deposit,
+            None,
+            None,
+        )
+        .await;
+        assert!(deposit.is_ok());
+    }
+
+    #[sqlx::test]
+    async fn test_higher_levels_inherit_limits(pool: PgPool) {
+        let a = funded_wallet(&pool, "a@example.com", Decimal::new(3000, 0)).await;
+        let b = funded_wallet(&pool, "b@example.com", Decimal::ONE).await;
+
+        // Level 1 caps transfers at 1000
+        assert!(!transfer(pool.clone(), a.id, b.id, Decimal::new(2000, 0)).await);
+
+        // Level 3 has no rows of its own and gets level 2's limits
+        let mut user = User::find_by_id(&pool, a.user_id).await.unwrap().unwrap();
+        user.update_kyc_status(&pool, KycStatus::Verified, 3).await.unwrap();
+        assert!(transfer(pool.clone(), a.id, b.id, Decimal::new(2000, 0)).await);
+    }
+
+    #[sqlx::test]
+    async fn test_withdrawal_waits_for_settlement(pool: PgPool) {
+        let a = funded_wallet(&pool, "a@example.com", Decimal::new(100, 0)).await;
+
+        let withdraw = |amount| {
+            Transaction::create(
+                &pool,
+                Some(a.id),
+                None,
+                amount,
+                "USD".to_string(),
+                TransactionType::Withdrawal,
+                None,
+                None,
+            )
+        };
+
+        // Pending holds the funds without moving them
+        let mut failed = withdraw(Decimal::new(30, 0)).await.unwrap();
+        assert_eq!(failed.status, TransactionStatus::Pending);
+        let wallet = Wallet::find_by_id(&pool, a.id).await.unwrap().unwrap();
+        assert_eq!(wallet.balance, Decimal::new(100, 0));
+        assert_eq!(wallet.held_balance, Decimal::new(30, 0));
+
+        // A bounced withdrawal releases the hold
+        let mut db_tx = pool.begin().await.unwrap();
+        failed
+            .settle_in(&mut db_tx, TransactionStatus::Failed, Some("bank rejected".to_string()))
+            .await
+            .unwrap();
+        db_tx.commit().await.unwrap();
+
+        let mut settled = withdraw(Decimal::new(40, 0)).await.unwrap();
+        let mut db_tx = pool.begin().await.unwrap();
+        let previous = settled
+            .settle_in(&mut db_tx, TransactionStatus::Completed, None)
+            .await
+            .unwrap();
+        db_tx.commit().await.unwrap();
+        assert_eq!(previous, TransactionStatus::Pending);
+        assert_eq!(settled.status, TransactionStatus::Completed);
+
+        let wallet = Wallet::find_by_id(&pool, a.id).await.unwrap().unwrap();
+        assert_eq!(wallet.balance, Decimal::new(60, 0));
+        assert_eq!(wallet.held_balance, Decimal::ZERO);
+        assert!(LedgerAccount::wallet_discrepancies(&pool).await.unwrap().is_empty());
+
+        // Settled transactions are final
+        let mut db_tx = pool.begin().await.unwrap();
+        assert!(settled
+            .settle_in(&mut db_tx, TransactionStatus::Failed, None)
+            .await
+            .is_err());
+    }
 }
@@ -0,0 +1,373 @@
+use crate::services::totp::{self, TotpError};
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use thiserror::Error;
+use uuid::Uuid;
+
+pub const BACKUP_CODE_COUNT: usize = 10;
+/// Wrong codes in a row before the user is locked out
+pub const MAX_FAILED_ATTEMPTS: i32 = 5;
+const DEFAULT_LOCKOUT_MINUTES: i64 = 15;
+// Unambiguous characters only: no 0/O, 1/I/L
+const BACKUP_CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+
+/// A user's TOTP secret. The secret is pending until the user proves their
+/// authenticator app has it by confirming a code; only then is 2FA enforced.
+#[derive(Debug, sqlx::FromRow)]
+pub struct TwoFactorSecret {
+    pub user_id: Uuid,
+    /// AES-GCM nonce followed by the encrypted secret
+    pub secret_ciphertext: Vec<u8>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    /// Last time step a code was accepted for, so it cannot be used again
+    pub last_used_step: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    /// Wrong codes since the last accepted one or the last lockout
+    pub failed_attempts: i32,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Error)]
+pub enum TwoFactorError {
+    #[error("Two-factor authentication is already enabled")]
+    AlreadyEnabled,
+    #[error("Two-factor authentication is not enabled")]
+    NotEnabled,
+    #[error("No two-factor enrollment is in progress")]
+    NoPendingEnrollment,
+    #[error("Invalid two-factor code")]
+    InvalidCode,
+    #[error("Too many invalid two-factor codes; try again later")]
+    LockedOut,
+    #[error("TOTP error: {0}")]
+    TotpError(#[from] TotpError),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Which kind of second factor a login was completed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecondFactor {
+    Totp,
+    BackupCode,
+}
+
+impl TwoFactorSecret {
+    /// How long a lockout lasts, from TWO_FACTOR_LOCKOUT_MINUTES
+    pub fn lockout() -> Duration {
+        let minutes = std::env::var("TWO_FACTOR_LOCKOUT_MINUTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_LOCKOUT_MINUTES);
+        Duration::minutes(minutes)
+    }
+
+    /// Starts (or restarts) enrollment with a new secret, returning the plain
+    /// secret so it can be shown to the user once
+    pub async fn begin_enrollment(pool: &PgPool, user_id: Uuid) -> Result<Vec<u8>, TwoFactorError> {
+        let secret = totp::generate_secret();
+        let ciphertext = totp::encrypt_secret(&secret)?;
+
+        // A confirmed secret is never replaced here; disabling comes first
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO two_factor_secrets (user_id, secret_ciphertext)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret_ciphertext = EXCLUDED.secret_ciphertext,
+                last_used_step = NULL,
+                created_at = CURRENT_TIMESTAMP
+            WHERE two_factor_secrets.confirmed_at IS NULL
+            "#,
+            user_id,
+            ciphertext
+        )
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(TwoFactorError::AlreadyEnabled);
+        }
+
+        Ok(secret)
+    }
+
+    /// Activates 2FA once the user has entered a code from the pending
+    /// secret, and issues a fresh set of backup codes
+    pub async fn confirm_enrollment(
+        pool: &PgPool,
+        user_id: Uuid,
+        code: &str,
+    ) -> Result<Vec<String>, TwoFactorError> {
+        let mut db_tx = pool.begin().await?;
+
+        let pending = Self::lock(&mut db_tx, user_id)
+            .await?
+            .filter(|secret| secret.confirmed_at.is_none())
+            .ok_or(TwoFactorError::NoPendingEnrollment)?;
+        let step = pending.accept_totp(code)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE two_factor_secrets
+            SET confirmed_at = CURRENT_TIMESTAMP, last_used_step = $2
+            WHERE user_id = $1
+            "#,
+            user_id,
+            step
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users SET two_factor_enabled = true, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        let backup_codes = Self::replace_backup_codes(&mut db_tx, user_id).await?;
+
+        db_tx.commit().await?;
+
+        Ok(backup_codes)
+    }
+
+    /// Checks a login code, which may be a TOTP code or an unused backup
+    /// code. Each is accepted once only. After `MAX_FAILED_ATTEMPTS` wrong
+    /// codes in a row every code is refused until the lockout ends.
+    pub async fn verify(
+        pool: &PgPool,
+        user_id: Uuid,
+        code: &str,
+    ) -> Result<SecondFactor, TwoFactorError> {
+        let mut db_tx = pool.begin().await?;
+
+        // Locking the row serializes concurrent attempts with the same code
+        let secret = Self::lock(&mut db_tx, user_id)
+            .await?
+            .filter(|secret| secret.confirmed_at.is_some())
+            .ok_or(TwoFactorError::NotEnabled)?;
+        if secret.locked_until.is_some_and(|until| until > Utc::now()) {
+            return Err(TwoFactorError::LockedOut);
+        }
+
+        let factor = match secret.accept_totp(code) {
+            Ok(step) => {
+                sqlx::query!(
+                    r#"
+                    UPDATE two_factor_secrets SET last_used_step = $2
+                    WHERE user_id = $1
+                    "#,
+                    user_id,
+                    step
+                )
+                .execute(&mut *db_tx)
+                .await?;
+                Some(SecondFactor::Totp)
+            }
+            Err(TwoFactorError::InvalidCode) => {
+                match Self::use_backup_code(&mut db_tx, user_id, code).await {
+                    Ok(()) => Some(SecondFactor::BackupCode),
+                    Err(TwoFactorError::InvalidCode) => None,
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
+        };
+
+        // The failure is committed, so it counts even though the check fails
+        let Some(factor) = factor else {
+            secret.record_failure(&mut db_tx).await?;
+            db_tx.commit().await?;
+            return Err(TwoFactorError::InvalidCode);
+        };
+
+        if secret.failed_attempts > 0 {
+            sqlx::query!(
+                r#"
+                UPDATE two_factor_secrets SET failed_attempts = 0
+                WHERE user_id = $1
+                "#,
+                user_id
+            )
+            .execute(&mut *db_tx)
+            .await?;
+        }
+
+        db_tx.commit().await?;
+
+        Ok(factor)
+    }
+
+    /// Turns 2FA off, removing the secret and any backup codes. The caller
+    /// must have checked the user's password and a current code.
+    pub async fn disable(pool: &PgPool, user_id: Uuid) -> Result<(), TwoFactorError> {
+        let mut db_tx = pool.begin().await?;
+
+        sqlx::query!("DELETE FROM two_factor_backup_codes WHERE user_id = $1", user_id)
+            .execute(&mut *db_tx)
+            .await?;
+        sqlx::query!("DELETE FROM two_factor_secrets WHERE user_id = $1", user_id)
+            .execute(&mut *db_tx)
+            .await?;
+        sqlx::query!(
+            r#"
+            UPDATE users SET two_factor_enabled = false, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn lock(
+        db_tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+    ) -> Result<Option<Self>, TwoFactorError> {
+        let secret = sqlx::query_as!(
+            TwoFactorSecret,
+            r#"
+            SELECT * FROM two_factor_secrets
+            WHERE user_id = $1
+            FOR UPDATE
+            "#,
+            user_id
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?;
+
+        Ok(secret)
+    }
+
+    /// Counts a wrong code, locking the user out once there have been too
+    /// many in a row. The count starts again after a lockout.
+    async fn record_failure(
+        &self,
+        db_tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), TwoFactorError> {
+        let failed_attempts = self.failed_attempts + 1;
+        let (failed_attempts, locked_until) = if failed_attempts >= MAX_FAILED_ATTEMPTS {
+            (0, Some(Utc::now() + Self::lockout()))
+        } else {
+            (failed_attempts, self.locked_until)
+        };
+
+        sqlx::query!(
+            r#"
+            UPDATE two_factor_secrets SET failed_attempts = $2, locked_until = $3
+            WHERE user_id = $1
+            "#,
+            self.user_id,
+            failed_attempts,
+            locked_until
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The time step the code belongs to, if it is valid and newer than the
+    /// last code accepted
+    fn accept_totp(&self, code: &str) -> Result<i64, TwoFactorError> {
+        let secret = totp::decrypt_secret(&self.secret_ciphertext)?;
+
+        totp::matching_step(&secret, code, Utc::now().timestamp())
+            .filter(|step| self.last_used_step.map_or(true, |last| *step > last))
+            .ok_or(TwoFactorError::InvalidCode)
+    }
+
+    async fn replace_backup_codes(
+        db_tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+    ) -> Result<Vec<String>, TwoFactorError> {
+        sqlx::query!("DELETE FROM two_factor_backup_codes WHERE user_id = $1", user_id)
+            .execute(&mut **db_tx)
+            .await?;
+
+        let codes: Vec<String> = (0..BACKUP_CODE_COUNT).map(|_| generate_backup_code()).collect();
+        for code in &codes {
+            sqlx::query!(
+                r#"
+                INSERT INTO two_factor_backup_codes (user_id, code_hash)
+                VALUES ($1, $2)
+                "#,
+                user_id,
+                hash_backup_code(code)
+            )
+            .execute(&mut **db_tx)
+            .await?;
+        }
+
+        Ok(codes)
+    }
+
+    async fn use_backup_code(
+        db_tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        code: &str,
+    ) -> Result<(), TwoFactorError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE two_factor_backup_codes SET used_at = CURRENT_TIMESTAMP
+            WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+            "#,
+            user_id,
+            hash_backup_code(code)
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        if result.rows_affected() == 1 {
+            Ok(())
+        } else {
+            Err(TwoFactorError::InvalidCode)
+        }
+    }
+}
+
+/// Ten random characters shown as `XXXXX-XXXXX`, about 49 bits
+fn generate_backup_code() -> String {
+    let mut rng = rand::thread_rng();
+    let chars: String = (0..10)
+        .map(|_| BACKUP_CODE_ALPHABET[rng.gen_range(0..BACKUP_CODE_ALPHABET.len())] as char)
+        .collect();
+
+    format!("{}-{}", &chars[..5], &chars[5..])
+}
+
+/// Backup codes are random enough that a plain SHA-256 is a sufficient
+/// hash. Case and the separator are ignored so users can type them loosely.
+fn hash_backup_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_code_hash_ignores_formatting() {
+        let code = generate_backup_code();
+        assert_eq!(code.len(), 11);
+        assert_eq!(hash_backup_code(&code), hash_backup_code(&code.to_lowercase().replace('-', " ")));
+        assert_ne!(hash_backup_code(&code), hash_backup_code(&generate_backup_code()));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::models::pagination::{Cursor, Keyset};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
@@ -15,6 +16,9 @@ pub struct User {
     pub kyc_level: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub two_factor_enabled: bool,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub phone_verified_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::Type)]
@@ -26,6 +30,15 @@ pub enum KycStatus {
     Rejected,
 }
 
+impl Keyset for User {
+    fn cursor(&self) -> Cursor {
+        Cursor {
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+}
+
 impl User {
     pub async fn create(
         pool: &sqlx::PgPool,
@@ -50,6 +63,18 @@ impl User {
         .await
     }
 
+    pub async fn find_by_id(pool: &sqlx::PgPool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            User,
+            r#"
+            SELECT * FROM users WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
     pub async fn find_by_email(pool: &sqlx::PgPool, email: &str) -> Result<Option<Self>, sqlx::Error> {
         sqlx::query_as!(
             User,
@@ -62,6 +87,27 @@ impl User {
         .await
     }
 
+    /// Finds the user who has verified an E.164-normalized phone number.
+    /// Unverified numbers are ignored, since anyone can register with one.
+    pub async fn find_by_phone(pool: &sqlx::PgPool, phone_number: &str) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            User,
+            r#"
+            SELECT * FROM users WHERE phone_number = $1 AND phone_verified_at IS NOT NULL
+            "#,
+            phone_number
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// The user's phone number, if they have verified it
+    pub fn verified_phone(&self) -> Option<&str> {
+        self.phone_number
+            .as_deref()
+            .filter(|_| self.phone_verified_at.is_some())
+    }
+
     pub async fn update_kyc_status(
         &mut self,
         pool: &sqlx::PgPool,
@@ -89,4 +135,30 @@ impl User {
             Err(sqlx::Error::RowNotFound)
         }
     }
+
+    /// Replaces the stored hash, e.g. after rehashing at new parameters
+    pub async fn update_password_hash(
+        &mut self,
+        pool: &sqlx::PgPool,
+        password_hash: &str,
+    ) -> Result<(), sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2
+            "#,
+            password_hash,
+            self.id
+        )
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 1 {
+            self.password_hash = password_hash.to_string();
+            Ok(())
+        } else {
+            Err(sqlx::Error::RowNotFound)
+        }
+    }
 }
@@ -1,8 +1,12 @@
-use crate::models::user::User;
+use crate::models::{
+    currency::{Currency, Money},
+    pagination::{Cursor, Keyset, Page, PageRequest},
+    user::User,
+};
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -11,13 +15,20 @@ pub struct Wallet {
     pub id: Uuid,
     pub user_id: Uuid,
     pub balance: Decimal,
+    pub held_balance: Decimal,
+    pub available_balance: Decimal,
     pub currency: String,
     pub status: WalletStatus,
+    pub nickname: Option<String>,
+    pub is_default: bool,
+    pub status_reason: Option<WalletStatusReason>,
+    pub status_note: Option<String>,
+    pub status_changed_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
 #[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
 pub enum WalletStatus {
     Active,
@@ -25,38 +36,98 @@ pub enum WalletStatus {
     Closed,
 }
 
+/// Why a wallet's status was changed
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+pub enum WalletStatusReason {
+    Fraud,
+    Compliance,
+    #[sqlx(rename = "court_order")]
+    CourtOrder,
+    #[sqlx(rename = "customer_request")]
+    CustomerRequest,
+    Dormant,
+    Resolved,
+    Other,
+}
+
+impl WalletStatusReason {
+    /// Wording shown to the wallet owner
+    pub fn description(&self) -> &'static str {
+        match self {
+            WalletStatusReason::Fraud => "Suspected fraudulent activity",
+            WalletStatusReason::Compliance => "Compliance review",
+            WalletStatusReason::CourtOrder => "Legal or court order",
+            WalletStatusReason::CustomerRequest => "Your request",
+            WalletStatusReason::Dormant => "Account inactivity",
+            WalletStatusReason::Resolved => "Review completed",
+            WalletStatusReason::Other => "Other",
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum WalletError {
     #[error("Insufficient funds: required {required}, available {available}")]
-    InsufficientFunds { required: Decimal, available: Decimal },
+    InsufficientFunds { required: Money, available: Money },
+    #[error("Unsupported currency: {0}")]
+    UnsupportedCurrency(String),
     #[error("Wallet is not active")]
     InactiveWallet,
     #[error("Invalid amount: {0}")]
     InvalidAmount(String),
+    #[error("Wallet still holds {balance} and cannot be closed")]
+    NonZeroBalance { balance: Money },
+    #[error("Wallet is already closed")]
+    AlreadyClosed,
+    #[error("Wallet is {0:?}")]
+    UnexpectedStatus(WalletStatus),
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
 }
 
+impl Keyset for Wallet {
+    fn cursor(&self) -> Cursor {
+        Cursor {
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+}
+
 impl Wallet {
-    /// Creates a new wallet for a user
+    /// Creates a new wallet for a user. The first open wallet in a currency
+    /// becomes the user's default for it.
     pub async fn create(
         pool: &PgPool,
         user_id: Uuid,
-        currency: String,
+        currency: &str,
+        nickname: Option<String>,
     ) -> Result<Self, WalletError> {
+        let currency = Currency::from_code(currency)
+            .ok_or_else(|| WalletError::UnsupportedCurrency(currency.to_string()))?;
+
+        let mut db_tx = pool.begin().await?;
+        Self::lock_owner(&mut db_tx, user_id).await?;
+
         let wallet = sqlx::query_as!(
             Wallet,
             r#"
-            INSERT INTO wallets (user_id, currency)
-            VALUES ($1, $2)
+            INSERT INTO wallets (user_id, currency, nickname, is_default)
+            VALUES ($1, $2, $3, NOT EXISTS (
+                SELECT 1 FROM wallets WHERE user_id = $1 AND currency = $2 AND is_default
+            ))
             RETURNING *
             "#,
             user_id,
-            currency,
+            currency.code,
+            nickname,
         )
-        .fetch_one(pool)
+        .fetch_one(&mut *db_tx)
         .await?;
 
+        db_tx.commit().await?;
+
         Ok(wallet)
     }
 
@@ -75,12 +146,34 @@ impl Wallet {
         Ok(wallet)
     }
 
-    /// Gets all wallets for a user
+    /// Retrieves a wallet and locks its row until the database transaction ends
+    pub async fn find_by_id_for_update(
+        db_tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+    ) -> Result<Option<Self>, WalletError> {
+        let wallet = sqlx::query_as!(
+            Wallet,
+            r#"
+            SELECT * FROM wallets WHERE id = $1
+            FOR UPDATE
+            "#,
+            id
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?;
+
+        Ok(wallet)
+    }
+
+    /// Gets all wallets for a user, grouped by currency with the default
+    /// wallet first
     pub async fn find_by_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<Self>, WalletError> {
         let wallets = sqlx::query_as!(
             Wallet,
             r#"
-            SELECT * FROM wallets WHERE user_id = $1
+            SELECT * FROM wallets
+            WHERE user_id = $1
+            ORDER BY currency, is_default DESC, created_at, id
             "#,
             user_id
         )
@@ -90,10 +183,65 @@ impl Wallet {
         Ok(wallets)
     }
 
-    /// Updates wallet balance within a transaction
+    /// Lists a user's wallets, newest first
+    pub async fn list(
+        pool: &PgPool,
+        user_id: Uuid,
+        currency: Option<&str>,
+        status: Option<WalletStatus>,
+        page: &PageRequest,
+    ) -> Result<Page<Self>, WalletError> {
+        let push_filters = |query: &mut QueryBuilder<'_, Postgres>| {
+            query.push(" WHERE user_id = ");
+            query.push_bind(user_id);
+
+            if let Some(currency) = currency {
+                query.push(" AND currency = ");
+                query.push_bind(currency.to_string());
+            }
+            if let Some(status) = status.clone() {
+                query.push(" AND status = ");
+                query.push_bind(status);
+            }
+        };
+
+        let mut query = QueryBuilder::new("SELECT * FROM wallets");
+        push_filters(&mut query);
+        page.push_to(&mut query, "wallets");
+        let rows = query.build_query_as::<Wallet>().fetch_all(pool).await?;
+
+        let total = page.count(pool, "wallets", push_filters).await?;
+
+        Ok(Page::from_rows(rows, page, total))
+    }
+
+    /// Gets the wallet payments to a user in `currency` are made into
+    pub async fn find_default(
+        pool: &PgPool,
+        user_id: Uuid,
+        currency: &str,
+    ) -> Result<Option<Self>, WalletError> {
+        let wallet = sqlx::query_as!(
+            Wallet,
+            r#"
+            SELECT * FROM wallets
+            WHERE user_id = $1 AND currency = $2 AND is_default AND status = 'active'
+            "#,
+            user_id,
+            currency
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(wallet)
+    }
+
+    /// Applies a balance change within a transaction. The caller must hold the
+    /// row lock from `find_by_id_for_update`; the update is relative and
+    /// guarded so a stale copy can never overwrite a newer balance.
     pub async fn update_balance(
         &mut self,
-        pool: &PgPool,
+        db_tx: &mut Transaction<'_, Postgres>,
         amount: Decimal,
     ) -> Result<(), WalletError> {
         // Validate wallet status
@@ -101,73 +249,320 @@ impl Wallet {
             return Err(WalletError::InactiveWallet);
         }
 
-        // Calculate new balance
-        let new_balance = self.balance + amount;
-        
-        // Validate non-negative balance
-        if new_balance < Decimal::ZERO {
+        // Validate the change does not eat into held funds
+        if self.available_balance + amount < Decimal::ZERO {
             return Err(WalletError::InsufficientFunds {
-                required: amount.abs(),
-                available: self.balance,
+                required: self.money(amount.abs()),
+                available: self.available(),
             });
         }
 
         // Update balance in database
-        let result = sqlx::query!(
+        let new_balance = sqlx::query_scalar!(
             r#"
             UPDATE wallets
-            SET balance = $1, updated_at = CURRENT_TIMESTAMP
-            WHERE id = $2 AND status = 'active'
+            SET balance = balance + $1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2 AND status = 'active' AND balance + $1 >= held_balance
+            RETURNING balance
             "#,
-            new_balance,
+            amount,
             self.id
         )
-        .execute(pool)
+        .fetch_optional(&mut **db_tx)
         .await?;
 
-        if result.rows_affected() == 1 {
-            self.balance = new_balance;
-            Ok(())
-        } else {
-            Err(WalletError::InactiveWallet)
+        match new_balance {
+            Some(balance) => {
+                self.balance = balance;
+                self.available_balance = balance - self.held_balance;
+                Ok(())
+            }
+            None => Err(WalletError::InactiveWallet),
+        }
+    }
+
+    /// Freezes an active wallet so no money can move in or out of it
+    pub async fn freeze_in(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        reason: WalletStatusReason,
+        note: Option<String>,
+    ) -> Result<(), WalletError> {
+        self.set_status_in(db_tx, WalletStatus::Active, WalletStatus::Frozen, reason, note)
+            .await
+    }
+
+    /// Returns a frozen wallet to active
+    pub async fn unfreeze_in(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        reason: WalletStatusReason,
+        note: Option<String>,
+    ) -> Result<(), WalletError> {
+        self.set_status_in(db_tx, WalletStatus::Frozen, WalletStatus::Active, reason, note)
+            .await
+    }
+
+    /// Moves the wallet from `from` to `to`, recording why. Fails without
+    /// changing anything if the wallet is no longer in `from`.
+    pub async fn set_status_in(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        from: WalletStatus,
+        to: WalletStatus,
+        reason: WalletStatusReason,
+        note: Option<String>,
+    ) -> Result<(), WalletError> {
+        let updated = sqlx::query_as!(
+            Wallet,
+            r#"
+            UPDATE wallets
+            SET status = $1, status_reason = $2, status_note = $3,
+                status_changed_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $4 AND status = $5
+            RETURNING *
+            "#,
+            to as WalletStatus,
+            reason as WalletStatusReason,
+            note,
+            self.id,
+            from as WalletStatus
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?;
+
+        match updated {
+            Some(wallet) => {
+                *self = wallet;
+                Ok(())
+            }
+            None => {
+                let current = sqlx::query_scalar!(
+                    r#"
+                    SELECT status AS "status: WalletStatus" FROM wallets WHERE id = $1
+                    "#,
+                    self.id
+                )
+                .fetch_one(&mut **db_tx)
+                .await?;
+
+                Err(WalletError::UnexpectedStatus(current))
+            }
         }
     }
 
-    /// Freezes a wallet
-    pub async fn freeze(&mut self, pool: &PgPool) -> Result<(), WalletError> {
+    /// Sets or clears the wallet's nickname
+    pub async fn rename(&mut self, pool: &PgPool, nickname: Option<String>) -> Result<(), WalletError> {
         let result = sqlx::query!(
             r#"
             UPDATE wallets
-            SET status = 'frozen', updated_at = CURRENT_TIMESTAMP
-            WHERE id = $1 AND status = 'active'
+            SET nickname = $1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2 AND status <> 'closed'
             "#,
+            nickname,
             self.id
         )
         .execute(pool)
         .await?;
 
         if result.rows_affected() == 1 {
-            self.status = WalletStatus::Frozen;
+            self.nickname = nickname;
             Ok(())
         } else {
-            Err(WalletError::InactiveWallet)
+            Err(WalletError::AlreadyClosed)
         }
     }
 
-    /// Validates if the wallet can process a debit transaction
+    /// Makes this the user's default wallet for its currency
+    pub async fn set_default(&mut self, pool: &PgPool) -> Result<(), WalletError> {
+        let mut db_tx = pool.begin().await?;
+        Self::lock_owner(&mut db_tx, self.user_id).await?;
+
+        let wallet = Self::find_by_id_for_update(&mut db_tx, self.id)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        if wallet.status != WalletStatus::Active {
+            return Err(WalletError::InactiveWallet);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE wallets
+            SET is_default = FALSE, updated_at = CURRENT_TIMESTAMP
+            WHERE user_id = $1 AND currency = $2 AND is_default AND id <> $3
+            "#,
+            self.user_id,
+            self.currency,
+            self.id
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        *self = sqlx::query_as!(
+            Wallet,
+            r#"
+            UPDATE wallets
+            SET is_default = TRUE, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            RETURNING *
+            "#,
+            self.id
+        )
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Closes an empty wallet at its owner's request. Only an active wallet
+    /// can be closed this way, so a frozen one stays frozen. If it was the
+    /// default, the user's oldest other active wallet in the currency takes
+    /// over.
+    pub async fn close(
+        &mut self,
+        pool: &PgPool,
+        reason: WalletStatusReason,
+        note: Option<String>,
+    ) -> Result<(), WalletError> {
+        let mut db_tx = pool.begin().await?;
+        self.close_from_in(&mut db_tx, &[WalletStatus::Active], reason, note)
+            .await?;
+        db_tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Closes an empty active or frozen wallet inside the caller's database
+    /// transaction
+    pub async fn close_in(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        reason: WalletStatusReason,
+        note: Option<String>,
+    ) -> Result<(), WalletError> {
+        self.close_from_in(db_tx, &[WalletStatus::Active, WalletStatus::Frozen], reason, note)
+            .await
+    }
+
+    async fn close_from_in(
+        &mut self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        allowed: &[WalletStatus],
+        reason: WalletStatusReason,
+        note: Option<String>,
+    ) -> Result<(), WalletError> {
+        Self::lock_owner(db_tx, self.user_id).await?;
+
+        let wallet = Self::find_by_id_for_update(db_tx, self.id)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        if wallet.status == WalletStatus::Closed {
+            return Err(WalletError::AlreadyClosed);
+        }
+        if !allowed.contains(&wallet.status) {
+            return Err(WalletError::UnexpectedStatus(wallet.status));
+        }
+        if wallet.balance != Decimal::ZERO || wallet.held_balance != Decimal::ZERO {
+            return Err(WalletError::NonZeroBalance {
+                balance: wallet.money(wallet.balance),
+            });
+        }
+
+        *self = wallet;
+        let was_default = self.is_default;
+
+        sqlx::query!(
+            r#"
+            UPDATE wallets SET is_default = FALSE WHERE id = $1
+            "#,
+            self.id
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        let from = self.status.clone();
+        self.set_status_in(db_tx, from, WalletStatus::Closed, reason, note)
+            .await?;
+
+        if was_default {
+            Self::promote_default(db_tx, self.user_id, &self.currency).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Makes the oldest active wallet in the currency the default, if any.
+    /// A frozen wallet cannot be paid into, so it is passed over.
+    async fn promote_default(
+        db_tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        currency: &str,
+    ) -> Result<(), WalletError> {
+        sqlx::query!(
+            r#"
+            UPDATE wallets
+            SET is_default = TRUE, updated_at = CURRENT_TIMESTAMP
+            WHERE id = (
+                SELECT id FROM wallets
+                WHERE user_id = $1 AND currency = $2 AND status = 'active'
+                ORDER BY created_at, id
+                LIMIT 1
+            )
+            "#,
+            user_id,
+            currency
+        )
+        .execute(&mut **db_tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Serializes changes to which of a user's wallets is the default. Take
+    /// this before any wallet row lock.
+    pub async fn lock_owner(
+        db_tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+    ) -> Result<(), WalletError> {
+        sqlx::query!(
+            r#"
+            SELECT id FROM users WHERE id = $1
+            FOR UPDATE
+            "#,
+            user_id
+        )
+        .fetch_optional(&mut **db_tx)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+        Ok(())
+    }
+
+    /// An amount in this wallet's currency
+    pub fn money(&self, amount: Decimal) -> Money {
+        Money::from_stored(amount, &self.currency)
+    }
+
+    /// The balance not reserved by holds
+    pub fn available(&self) -> Money {
+        self.money(self.available_balance)
+    }
+
+    /// Validates if the wallet can process a debit transaction. Only the
+    /// available balance counts; held funds are already spoken for.
     pub fn can_debit(&self, amount: Decimal) -> Result<(), WalletError> {
         if self.status != WalletStatus::Active {
             return Err(WalletError::InactiveWallet);
         }
 
-        if amount <= Decimal::ZERO {
-            return Err(WalletError::InvalidAmount("Amount must be positive".to_string()));
-        }
+        self.check_amount(amount)?;
 
-        if self.balance < amount {
+        if self.available_balance < amount {
             return Err(WalletError::InsufficientFunds {
-                required: amount,
-                available: self.balance,
+                required: self.money(amount),
+                available: self.available(),
             });
         }
 
@@ -180,10 +575,199 @@ impl Wallet {
             return Err(WalletError::InactiveWallet);
         }
 
+        self.check_amount(amount)
+    }
+
+    /// Rejects non-positive amounts and amounts finer than the currency's
+    /// minor unit
+    fn check_amount(&self, amount: Decimal) -> Result<(), WalletError> {
         if amount <= Decimal::ZERO {
             return Err(WalletError::InvalidAmount("Amount must be positive".to_string()));
         }
 
+        Money::new(amount, &self.currency)
+            .map_err(|e| WalletError::InvalidAmount(e.to_string()))?;
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::testing::create_user;
+
+    async fn user_id(pool: &PgPool) -> Uuid {
+        create_user(pool, "wallets@example.com").await.id
+    }
+
+    async fn reload(pool: &PgPool, wallet: &Wallet) -> Wallet {
+        Wallet::find_by_id(pool, wallet.id).await.unwrap().unwrap()
+    }
+
+    async fn freeze(pool: &PgPool, wallet: &mut Wallet) {
+        let mut db_tx = pool.begin().await.unwrap();
+        wallet
+            .freeze_in(&mut db_tx, WalletStatusReason::Compliance, None)
+            .await
+            .unwrap();
+        db_tx.commit().await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_set_default(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+        let first = Wallet::create(&pool, user_id, "USD", None).await.unwrap();
+        let mut second = Wallet::create(&pool, user_id, "USD", None).await.unwrap();
+        let euros = Wallet::create(&pool, user_id, "EUR", None).await.unwrap();
+        assert!(first.is_default && euros.is_default);
+        assert!(!second.is_default);
+
+        second.set_default(&pool).await.unwrap();
+        assert!(second.is_default);
+        assert!(!reload(&pool, &first).await.is_default);
+        assert!(reload(&pool, &euros).await.is_default);
+        let default = Wallet::find_default(&pool, user_id, "USD").await.unwrap().unwrap();
+        assert_eq!(default.id, second.id);
+
+        let mut frozen = Wallet::create(&pool, user_id, "USD", None).await.unwrap();
+        freeze(&pool, &mut frozen).await;
+        assert!(matches!(
+            frozen.set_default(&pool).await,
+            Err(WalletError::InactiveWallet)
+        ));
+        assert!(reload(&pool, &second).await.is_default);
+    }
+
+    #[sqlx::test]
+    async fn test_closing_default_promotes_oldest_active(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+        let mut default = Wallet::create(&pool, user_id, "USD", None).await.unwrap();
+        let mut frozen = Wallet::create(&pool, user_id, "USD", None).await.unwrap();
+        let active = Wallet::create(&pool, user_id, "USD", None).await.unwrap();
+        let newest = Wallet::create(&pool, user_id, "USD", None).await.unwrap();
+        freeze(&pool, &mut frozen).await;
+
+        default
+            .close(&pool, WalletStatusReason::CustomerRequest, None)
+            .await
+            .unwrap();
+        assert!(!default.is_default);
+        assert!(!reload(&pool, &frozen).await.is_default);
+        assert!(reload(&pool, &active).await.is_default);
+        assert!(!reload(&pool, &newest).await.is_default);
+    }
+
+    #[sqlx::test]
+    async fn test_closing_last_active_wallet_leaves_no_default(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+        let mut default = Wallet::create(&pool, user_id, "USD", None).await.unwrap();
+        let mut frozen = Wallet::create(&pool, user_id, "USD", None).await.unwrap();
+        freeze(&pool, &mut frozen).await;
+
+        default
+            .close(&pool, WalletStatusReason::CustomerRequest, None)
+            .await
+            .unwrap();
+        assert!(!reload(&pool, &frozen).await.is_default);
+        assert!(Wallet::find_default(&pool, user_id, "USD").await.unwrap().is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_freeze_and_unfreeze(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+        let mut wallet = Wallet::create(&pool, user_id, "USD", None).await.unwrap();
+
+        let mut db_tx = pool.begin().await.unwrap();
+        wallet
+            .freeze_in(&mut db_tx, WalletStatusReason::Fraud, Some("case 12".to_string()))
+            .await
+            .unwrap();
+        db_tx.commit().await.unwrap();
+
+        let frozen = reload(&pool, &wallet).await;
+        assert_eq!(frozen.status, WalletStatus::Frozen);
+        assert_eq!(frozen.status_reason, Some(WalletStatusReason::Fraud));
+        assert!(frozen.can_credit(Decimal::ONE).is_err());
+
+        let mut db_tx = pool.begin().await.unwrap();
+        assert!(matches!(
+            wallet.freeze_in(&mut db_tx, WalletStatusReason::Fraud, None).await,
+            Err(WalletError::UnexpectedStatus(WalletStatus::Frozen))
+        ));
+        wallet
+            .unfreeze_in(&mut db_tx, WalletStatusReason::Resolved, None)
+            .await
+            .unwrap();
+        db_tx.commit().await.unwrap();
+
+        let active = reload(&pool, &wallet).await;
+        assert_eq!(active.status, WalletStatus::Active);
+        assert_eq!(active.status_reason, Some(WalletStatusReason::Resolved));
+
+        let mut db_tx = pool.begin().await.unwrap();
+        assert!(matches!(
+            wallet.unfreeze_in(&mut db_tx, WalletStatusReason::Resolved, None).await,
+            Err(WalletError::UnexpectedStatus(WalletStatus::Active))
+        ));
+    }
+
+    #[sqlx::test]
+    async fn test_owner_cannot_close_frozen_wallet(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+        let mut wallet = Wallet::create(&pool, user_id, "USD", None).await.unwrap();
+
+        let mut db_tx = pool.begin().await.unwrap();
+        wallet
+            .freeze_in(&mut db_tx, WalletStatusReason::CourtOrder, None)
+            .await
+            .unwrap();
+        db_tx.commit().await.unwrap();
+
+        assert!(matches!(
+            wallet.close(&pool, WalletStatusReason::CustomerRequest, None).await,
+            Err(WalletError::UnexpectedStatus(WalletStatus::Frozen))
+        ));
+        let frozen = reload(&pool, &wallet).await;
+        assert_eq!(frozen.status, WalletStatus::Frozen);
+        assert_eq!(frozen.status_reason, Some(WalletStatusReason::CourtOrder));
+
+        // An admin can still close it
+        let mut db_tx = pool.begin().await.unwrap();
+        wallet
+            .close_in(&mut db_tx, WalletStatusReason::CourtOrder, None)
+            .await
+            .unwrap();
+        db_tx.commit().await.unwrap();
+        assert_eq!(reload(&pool, &wallet).await.status, WalletStatus::Closed);
+    }
+
+    #[sqlx::test]
+    async fn test_close_requires_empty_wallet(pool: PgPool) {
+        let user_id = user_id(&pool).await;
+        let mut wallet = Wallet::create(&pool, user_id, "USD", None).await.unwrap();
+
+        sqlx::query!("UPDATE wallets SET balance = 5 WHERE id = $1", wallet.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(matches!(
+            wallet.close(&pool, WalletStatusReason::CustomerRequest, None).await,
+            Err(WalletError::NonZeroBalance { .. })
+        ));
+
+        sqlx::query!("UPDATE wallets SET balance = 0 WHERE id = $1", wallet.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        wallet
+            .close(&pool, WalletStatusReason::CustomerRequest, None)
+            .await
+            .unwrap();
+        assert_eq!(wallet.status, WalletStatus::Closed);
+        assert!(matches!(
+            wallet.close(&pool, WalletStatusReason::CustomerRequest, None).await,
+            Err(WalletError::AlreadyClosed)
+        ));
+    }
+}
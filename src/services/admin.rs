@@ -1,14 +1,21 @@
 use crate::{
     models::{
+        currency::Money,
+        pagination::{Page, PageRequest},
         user::{User, UserKycLevel},
-        transaction::Transaction,
-        reserve::ReserveBalance,
+        transaction::{Transaction, TransactionError, TransactionStatus, TransactionType},
+        reserve::{ReserveBalance, ReserveError, ReserveRatio},
+        wallet::{Wallet, WalletError, WalletStatus, WalletStatusReason},
     },
     db::DbPool,
+    services::{audit::AuditActor, statement::spreadsheet_safe},
 };
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPool;
+use serde_json::Value;
+use sqlx::{postgres::PgPool, Postgres, QueryBuilder};
+use std::collections::BTreeMap;
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -20,6 +27,18 @@ pub enum AdminError {
     UserNotFound,
     #[error("Invalid input: {0}")]
     InvalidInput(String),
+    #[error("Transaction not found")]
+    TransactionNotFound,
+    #[error("Transaction error: {0}")]
+    TransactionError(#[from] TransactionError),
+    #[error("Wallet not found")]
+    WalletNotFound,
+    #[error("Wallet error: {0}")]
+    WalletError(#[from] WalletError),
+    #[error("Export failed: {0}")]
+    ExportError(String),
+    #[error("Reserve error: {0}")]
+    ReserveError(#[from] ReserveError),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -30,14 +49,86 @@ pub struct UserFilter {
     pub created_before: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Most transactions a single CSV export may contain
+pub const EXPORT_MAX_ROWS: usize = 50_000;
+
+const MAX_METADATA_PREDICATES: usize = 10;
+const MAX_METADATA_DEPTH: usize = 8;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct TransactionFilter {
+    /// Transactions touching any of the user's wallets
     pub user_id: Option<Uuid>,
-    pub status: Option<String>,
-    pub min_amount: Option<i64>,
-    pub max_amount: Option<i64>,
+    /// Transactions on either side of the wallet
+    pub wallet_id: Option<Uuid>,
+    pub transaction_type: Option<TransactionType>,
+    pub status: Option<TransactionStatus>,
+    pub currency: Option<String>,
+    pub min_amount: Option<Decimal>,
+    pub max_amount: Option<Decimal>,
+    pub reference_prefix: Option<String>,
+    /// Words to find in the reference or metadata
+    pub q: Option<String>,
+    #[serde(default)]
+    pub metadata: Vec<MetadataPredicate>,
     pub created_after: Option<DateTime<Utc>>,
     pub created_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub sort: TransactionSort,
+}
+
+/// A condition on a value inside transaction metadata
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetadataPredicate {
+    /// Dotted path to the value, e.g. `p2p_recipient_type`
+    pub path: String,
+    #[serde(default)]
+    pub op: MetadataOp,
+    /// Compared as JSON, so `"5"` and `5` differ
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataOp {
+    #[default]
+    Eq,
+    Ne,
+    Exists,
+    Missing,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionSort {
+    #[default]
+    Newest,
+    Oldest,
+    AmountDesc,
+    AmountAsc,
+}
+
+/// Matching transactions counted by status and by type
+#[derive(Debug, Serialize)]
+pub struct TransactionFacets {
+    pub status: BTreeMap<String, i64>,
+    pub transaction_type: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReversalResult {
+    pub previous_status: TransactionStatus,
+    pub original: Transaction,
+    pub reversal: Transaction,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WalletStatusChange {
+    pub previous_status: WalletStatus,
+    pub previous_reason: Option<WalletStatusReason>,
+    pub wallet: Wallet,
+    /// The transfer that emptied the wallet before it was closed
+    pub sweep: Option<Transaction>,
 }
 
 #[derive(Debug, Serialize)]
@@ -45,8 +136,10 @@ pub struct SystemStats {
     pub total_users: i64,
     pub active_users: i64,
     pub total_transactions: i64,
-    pub total_volume: i64,
-    pub reserve_ratio: f64,
+    /// Completed volume, one entry per currency
+    pub total_volume: Vec<Money>,
+    /// One entry per currency
+    pub reserve_ratios: Vec<ReserveRatio>,
 }
 
 pub struct AdminService {
@@ -59,39 +152,46 @@ impl AdminService {
     }
 
     // User Management
-    pub async fn get_users(&self, filter: &UserFilter) -> Result<Vec<User>, AdminError> {
-        let mut query = sqlx::QueryBuilder::new(
-            "SELECT * FROM users WHERE true",
-        );
-
-        if let Some(kyc_level) = &filter.kyc_level {
-            query.push(" AND kyc_level = ");
-            query.push_bind(kyc_level);
-        }
-
-        if let Some(status) = &filter.status {
-            query.push(" AND status = ");
-            query.push_bind(status);
-        }
-
-        if let Some(after) = &filter.created_after {
-            query.push(" AND created_at >= ");
-            query.push_bind(after);
-        }
-
-        if let Some(before) = &filter.created_before {
-            query.push(" AND created_at <= ");
-            query.push_bind(before);
-        }
-
-        query.push(" ORDER BY created_at DESC");
+    pub async fn get_users(
+        &self,
+        filter: &UserFilter,
+        page: &PageRequest,
+    ) -> Result<Page<User>, AdminError> {
+        let push_filters = |query: &mut QueryBuilder<'_, Postgres>| {
+            query.push(" WHERE true");
+
+            if let Some(kyc_level) = filter.kyc_level.clone() {
+                query.push(" AND kyc_level = ");
+                query.push_bind(kyc_level);
+            }
+
+            if let Some(status) = filter.status.clone() {
+                query.push(" AND status = ");
+                query.push_bind(status);
+            }
+
+            if let Some(after) = filter.created_after {
+                query.push(" AND created_at >= ");
+                query.push_bind(after);
+            }
+
+            if let Some(before) = filter.created_before {
+                query.push(" AND created_at <= ");
+                query.push_bind(before);
+            }
+        };
 
+        let mut query = QueryBuilder::new("SELECT * FROM users");
+        push_filters(&mut query);
+        page.push_to(&mut query, "users");
         let users = query
             .build_query_as::<User>()
             .fetch_all(&self.pool)
             .await?;
 
-        Ok(users)
+        let total = page.count(&self.pool, "users", push_filters).await?;
+
+        Ok(Page::from_rows(users, page, total))
     }
 
     pub async fn update_user_kyc(
@@ -111,78 +211,420 @@ impl AdminService {
         Ok(user)
     }
 
+    /// Marks the user's phone number verified, e.g. once it has been checked
+    /// during KYC review, so P2P payments to it reach them
+    pub async fn verify_user_phone(
+        &self,
+        actor: &AuditActor,
+        user_id: Uuid,
+    ) -> Result<User, AdminError> {
+        let mut db_tx = self.pool.begin().await?;
+
+        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .fetch_optional(&mut *db_tx)
+            .await?
+            .ok_or(AdminError::UserNotFound)?;
+        let phone_number = user
+            .phone_number
+            .clone()
+            .ok_or_else(|| AdminError::InvalidInput("User has no phone number".to_string()))?;
+        if user.phone_verified_at.is_some() {
+            return Ok(user);
+        }
+
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            UPDATE users SET phone_verified_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        actor
+            .log_in(
+                &mut db_tx,
+                "verify_phone",
+                "user",
+                Some(user_id),
+                None,
+                Some(serde_json::json!({ "phone_number": phone_number })),
+            )
+            .await?;
+
+        db_tx.commit().await?;
+
+        Ok(user)
+    }
+
     // Transaction Management
     pub async fn get_transactions(
         &self,
         filter: &TransactionFilter,
-    ) -> Result<Vec<Transaction>, AdminError> {
-        let mut query = sqlx::QueryBuilder::new(
-            "SELECT * FROM transactions WHERE true",
-        );
-
-        if let Some(user_id) = &filter.user_id {
-            query.push(" AND user_id = ");
-            query.push_bind(user_id);
+        page: &PageRequest,
+    ) -> Result<Page<Transaction>, AdminError> {
+        filter.validate()?;
+
+        let mut query = QueryBuilder::new("SELECT * FROM transactions");
+        push_transaction_filters(&mut query, filter);
+        match filter.sort {
+            TransactionSort::Newest => page.push_to(&mut query, "transactions"),
+            TransactionSort::Oldest => page.push_sorted(&mut query, "transactions", false),
+            TransactionSort::AmountDesc | TransactionSort::AmountAsc => {
+                // Cursors follow creation order, so amount order is paged by number
+                if !page.is_numbered() {
+                    return Err(AdminError::InvalidInput(
+                        "Sorting by amount needs a page number".to_string(),
+                    ));
+                }
+                query.push(filter.sort.order_by());
+                page.push_limit(&mut query);
+            }
         }
 
-        if let Some(status) = &filter.status {
-            query.push(" AND status = ");
-            query.push_bind(status);
-        }
+        let transactions = query
+            .build_query_as::<Transaction>()
+            .fetch_all(&self.pool)
+            .await?;
 
-        if let Some(min) = &filter.min_amount {
-            query.push(" AND amount >= ");
-            query.push_bind(min);
-        }
+        let total = page
+            .count(&self.pool, "transactions", |query| push_transaction_filters(query, filter))
+            .await?;
 
-        if let Some(max) = &filter.max_amount {
-            query.push(" AND amount <= ");
-            query.push_bind(max);
-        }
+        Ok(Page::from_rows(transactions, page, total))
+    }
 
-        if let Some(after) = &filter.created_after {
-            query.push(" AND created_at >= ");
-            query.push_bind(after);
-        }
+    /// Counts of the transactions matching a filter, by status and by type
+    pub async fn get_transaction_facets(
+        &self,
+        filter: &TransactionFilter,
+    ) -> Result<TransactionFacets, AdminError> {
+        filter.validate()?;
 
-        if let Some(before) = &filter.created_before {
-            query.push(" AND created_at <= ");
-            query.push_bind(before);
+        let mut facets = TransactionFacets {
+            status: BTreeMap::new(),
+            transaction_type: BTreeMap::new(),
+        };
+        for (column, counts) in [
+            ("status", &mut facets.status),
+            ("transaction_type", &mut facets.transaction_type),
+        ] {
+            let mut query =
+                QueryBuilder::new(format!("SELECT {}, COUNT(*) FROM transactions", column));
+            push_transaction_filters(&mut query, filter);
+            query.push(format!(" GROUP BY {}", column));
+
+            let rows = query
+                .build_query_as::<(String, i64)>()
+                .fetch_all(&self.pool)
+                .await?;
+            counts.extend(rows);
         }
 
-        query.push(" ORDER BY created_at DESC");
+        Ok(facets)
+    }
+
+    /// Renders every transaction matching a filter as CSV, in the filter's
+    /// sort order
+    pub async fn export_transactions(
+        &self,
+        filter: &TransactionFilter,
+    ) -> Result<Vec<u8>, AdminError> {
+        filter.validate()?;
+
+        let mut query = QueryBuilder::new("SELECT * FROM transactions");
+        push_transaction_filters(&mut query, filter);
+        query.push(filter.sort.order_by());
+        query.push(" LIMIT ");
+        query.push_bind(EXPORT_MAX_ROWS as i64 + 1);
 
         let transactions = query
             .build_query_as::<Transaction>()
             .fetch_all(&self.pool)
             .await?;
+        if transactions.len() > EXPORT_MAX_ROWS {
+            return Err(AdminError::InvalidInput(format!(
+                "More than {} transactions match; narrow the filters",
+                EXPORT_MAX_ROWS
+            )));
+        }
+
+        let export_error = |e: csv::Error| AdminError::ExportError(e.to_string());
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer
+            .write_record([
+                "id", "created_at", "transaction_type", "status", "amount", "fee_amount",
+                "currency", "debit_wallet_id", "credit_wallet_id", "reference_id",
+                "parent_transaction_id", "metadata",
+            ])
+            .map_err(export_error)?;
+
+        // Reference and metadata are free text; the rest is generated here
+        let optional_id = |id: Option<Uuid>| id.map(|id| id.to_string()).unwrap_or_default();
+        for transaction in &transactions {
+            writer
+                .write_record([
+                    transaction.id.to_string(),
+                    transaction.created_at.to_rfc3339(),
+                    transaction.transaction_type.as_str().to_string(),
+                    transaction.status.as_str().to_string(),
+                    transaction.money().format_amount(),
+                    Money::from_stored(transaction.fee_amount, &transaction.currency)
+                        .format_amount(),
+                    transaction.currency.clone(),
+                    optional_id(transaction.debit_wallet_id),
+                    optional_id(transaction.credit_wallet_id),
+                    spreadsheet_safe(transaction.reference_id.as_deref().unwrap_or(""))
+                        .into_owned(),
+                    optional_id(transaction.parent_transaction_id),
+                    transaction
+                        .metadata
+                        .as_ref()
+                        .map(|metadata| spreadsheet_safe(&metadata.to_string()).into_owned())
+                        .unwrap_or_default(),
+                ])
+                .map_err(export_error)?;
+        }
+
+        writer
+            .into_inner()
+            .map_err(|e| AdminError::ExportError(e.to_string()))
+    }
+
+    /// Refunds a transaction. The audit entry is written in the same database
+    /// transaction, so a refund is never made without one and a failed audit
+    /// write leaves nothing to retry twice.
+    pub async fn reverse_transaction(
+        &self,
+        actor: &AuditActor,
+        transaction_id: Uuid,
+        amount: Option<Decimal>,
+        reason: &str,
+    ) -> Result<ReversalResult, AdminError> {
+        let mut original = Transaction::find_by_id(&self.pool, transaction_id)
+            .await?
+            .ok_or(AdminError::TransactionNotFound)?;
+        let previous_status = original.status.clone();
+
+        let mut db_tx = self.pool.begin().await?;
+        let reversal = original
+            .reverse_in(&mut db_tx, amount, Some(reason.to_string()))
+            .await?;
+
+        actor
+            .log_in(
+                &mut db_tx,
+                "reverse_transaction",
+                "transaction",
+                Some(transaction_id),
+                Some(serde_json::json!({ "status": previous_status })),
+                Some(serde_json::json!({
+                    "status": original.status,
+                    "reversal_id": reversal.id,
+                    "amount": reversal.amount,
+                    "reason": reason,
+                })),
+            )
+            .await?;
+
+        db_tx.commit().await?;
+
+        Ok(ReversalResult {
+            previous_status,
+            original,
+            reversal,
+        })
+    }
+
+    /// Records the bank's outcome for a deposit or withdrawal, auditing it in
+    /// the same database transaction
+    pub async fn settle_transaction(
+        &self,
+        actor: &AuditActor,
+        transaction_id: Uuid,
+        status: TransactionStatus,
+        reason: Option<String>,
+    ) -> Result<Transaction, AdminError> {
+        let mut transaction = Transaction::find_by_id(&self.pool, transaction_id)
+            .await?
+            .ok_or(AdminError::TransactionNotFound)?;
+
+        let mut db_tx = self.pool.begin().await?;
+        let previous_status = transaction
+            .settle_in(&mut db_tx, status, reason.clone())
+            .await?;
+
+        actor
+            .log_in(
+                &mut db_tx,
+                "settle_transaction",
+                "transaction",
+                Some(transaction_id),
+                Some(serde_json::json!({ "status": previous_status })),
+                Some(serde_json::json!({
+                    "status": transaction.status,
+                    "reason": reason,
+                })),
+            )
+            .await?;
+
+        db_tx.commit().await?;
+
+        Ok(transaction)
+    }
+
+    // Wallet Management
+    pub async fn freeze_wallet(
+        &self,
+        actor: &AuditActor,
+        wallet_id: Uuid,
+        reason: WalletStatusReason,
+        note: Option<String>,
+    ) -> Result<WalletStatusChange, AdminError> {
+        let mut db_tx = self.pool.begin().await?;
+        let mut wallet = Wallet::find_by_id_for_update(&mut db_tx, wallet_id)
+            .await?
+            .ok_or(AdminError::WalletNotFound)?;
+        let (previous_status, previous_reason) = (wallet.status.clone(), wallet.status_reason.clone());
+
+        wallet.freeze_in(&mut db_tx, reason, note).await?;
+
+        let change = WalletStatusChange {
+            previous_status,
+            previous_reason,
+            wallet,
+            sweep: None,
+        };
+        audit_wallet_change(&mut db_tx, actor, "freeze_wallet", &change).await?;
+        db_tx.commit().await?;
+
+        Ok(change)
+    }
+
+    pub async fn unfreeze_wallet(
+        &self,
+        actor: &AuditActor,
+        wallet_id: Uuid,
+        reason: WalletStatusReason,
+        note: Option<String>,
+    ) -> Result<WalletStatusChange, AdminError> {
+        let mut db_tx = self.pool.begin().await?;
+        let mut wallet = Wallet::find_by_id_for_update(&mut db_tx, wallet_id)
+            .await?
+            .ok_or(AdminError::WalletNotFound)?;
+        let (previous_status, previous_reason) = (wallet.status.clone(), wallet.status_reason.clone());
+
+        wallet.unfreeze_in(&mut db_tx, reason, note).await?;
+
+        let change = WalletStatusChange {
+            previous_status,
+            previous_reason,
+            wallet,
+            sweep: None,
+        };
+        audit_wallet_change(&mut db_tx, actor, "unfreeze_wallet", &change).await?;
+        db_tx.commit().await?;
+
+        Ok(change)
+    }
+
+    /// Closes a wallet. A wallet with money in it is only closed when
+    /// `sweep_to` names a wallet to move the balance into first.
+    pub async fn close_wallet(
+        &self,
+        actor: &AuditActor,
+        wallet_id: Uuid,
+        reason: WalletStatusReason,
+        note: Option<String>,
+        sweep_to: Option<Uuid>,
+    ) -> Result<WalletStatusChange, AdminError> {
+        let wallet = self.find_wallet(wallet_id).await?;
+
+        let mut db_tx = self.pool.begin().await?;
+        Wallet::lock_owner(&mut db_tx, wallet.user_id).await?;
+
+        // Lock the wallets in id order before touching either
+        let mut wallet_ids: Vec<Uuid> = std::iter::once(wallet_id).chain(sweep_to).collect();
+        wallet_ids.sort();
+        for id in wallet_ids {
+            Wallet::find_by_id_for_update(&mut db_tx, id)
+                .await?
+                .ok_or(AdminError::WalletNotFound)?;
+        }
+
+        let mut wallet = Wallet::find_by_id_for_update(&mut db_tx, wallet_id)
+            .await?
+            .ok_or(AdminError::WalletNotFound)?;
+        let (previous_status, previous_reason) = (wallet.status.clone(), wallet.status_reason.clone());
+
+        let sweep = match sweep_to {
+            Some(destination) if wallet.balance != Decimal::ZERO => {
+                // A frozen wallet cannot be debited, so it is reopened for the
+                // sweep; nothing outside this database transaction sees it
+                if wallet.status == WalletStatus::Frozen {
+                    wallet
+                        .set_status_in(
+                            &mut db_tx,
+                            WalletStatus::Frozen,
+                            WalletStatus::Active,
+                            reason.clone(),
+                            note.clone(),
+                        )
+                        .await?;
+                }
+
+                let sweep = Transaction::sweep_in(
+                    &mut db_tx,
+                    wallet_id,
+                    destination,
+                    Some(serde_json::json!({
+                        "sweep_of": wallet_id,
+                        "close_reason": reason,
+                    })),
+                )
+                .await?;
+                Some(sweep)
+            }
+            _ => None,
+        };
+
+        wallet.close_in(&mut db_tx, reason, note).await?;
+
+        let change = WalletStatusChange {
+            previous_status,
+            previous_reason,
+            wallet,
+            sweep,
+        };
+        audit_wallet_change(&mut db_tx, actor, "close_wallet", &change).await?;
+        db_tx.commit().await?;
+
+        Ok(change)
+    }
 
-        Ok(transactions)
+    async fn find_wallet(&self, wallet_id: Uuid) -> Result<Wallet, AdminError> {
+        Wallet::find_by_id(&self.pool, wallet_id)
+            .await?
+            .ok_or(AdminError::WalletNotFound)
     }
 
     // Reserve Management
-    pub async fn get_reserve_balance(&self) -> Result<ReserveBalance, AdminError> {
-        let balance = sqlx::query_as::<_, ReserveBalance>(
-            "SELECT * FROM reserve_balances ORDER BY created_at DESC LIMIT 1",
-        )
-        .fetch_one(&self.pool)
-        .await?;
+    /// The current reserve in each currency
+    pub async fn get_reserve_balances(&self) -> Result<Vec<ReserveBalance>, AdminError> {
+        let balances = ReserveBalance::latest(&self.pool).await?;
 
-        Ok(balance)
+        Ok(balances)
     }
 
     pub async fn update_reserve_balance(
         &self,
-        amount: i64,
+        amount: &Money,
         proof_url: &str,
     ) -> Result<ReserveBalance, AdminError> {
-        let balance = sqlx::query_as::<_, ReserveBalance>(
-            "INSERT INTO reserve_balances (amount, proof_url) VALUES ($1, $2) RETURNING *",
-        )
-        .bind(amount)
-        .bind(proof_url)
-        .fetch_one(&self.pool)
-        .await?;
+        let balance = ReserveBalance::record(&self.pool, amount, proof_url).await?;
 
         Ok(balance)
     }
@@ -195,31 +637,314 @@ impl AdminService {
         .fetch_one(&self.pool)
         .await?;
 
-        let (total_transactions, total_volume): (i64, i64) = sqlx::query_as(
-            "SELECT COUNT(*), COALESCE(SUM(amount), 0) FROM transactions WHERE status = 'completed'",
+        let total_transactions: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM transactions WHERE status = 'completed'",
         )
         .fetch_one(&self.pool)
         .await?;
 
-        let reserve_balance = self.get_reserve_balance().await?.amount;
-        let total_wallet_balance: i64 = sqlx::query_scalar(
-            "SELECT COALESCE(SUM(balance), 0) FROM wallets",
+        // Amounts in different currencies cannot be summed together
+        let total_volume = sqlx::query_as::<_, (String, Decimal)>(
+            "SELECT currency, SUM(amount) FROM transactions WHERE status = 'completed' GROUP BY currency ORDER BY currency",
         )
-        .fetch_one(&self.pool)
-        .await?;
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(currency, amount)| Money::from_stored(amount, &currency))
+        .collect();
 
-        let reserve_ratio = if total_wallet_balance > 0 {
-            reserve_balance as f64 / total_wallet_balance as f64
-        } else {
-            1.0
-        };
+        let reserve_ratios = ReserveBalance::ratios(&self.pool).await?;
 
         Ok(SystemStats {
             total_users,
             active_users,
             total_transactions,
             total_volume,
-            reserve_ratio,
+            reserve_ratios,
         })
     }
 }
+
+impl TransactionFilter {
+    fn validate(&self) -> Result<(), AdminError> {
+        if (self.min_amount.is_some() || self.max_amount.is_some()) && self.currency.is_none() {
+            return Err(AdminError::InvalidInput(
+                "Amount bounds need a currency".to_string(),
+            ));
+        }
+
+        if self.metadata.len() > MAX_METADATA_PREDICATES {
+            return Err(AdminError::InvalidInput(format!(
+                "At most {} metadata conditions are allowed",
+                MAX_METADATA_PREDICATES
+            )));
+        }
+
+        for predicate in &self.metadata {
+            let segments = predicate.segments();
+            if segments.iter().any(|segment| segment.is_empty())
+                || segments.len() > MAX_METADATA_DEPTH
+            {
+                return Err(AdminError::InvalidInput(format!(
+                    "Invalid metadata path: {}",
+                    predicate.path
+                )));
+            }
+
+            let needs_value = matches!(predicate.op, MetadataOp::Eq | MetadataOp::Ne);
+            if needs_value && predicate.value.is_none() {
+                return Err(AdminError::InvalidInput(format!(
+                    "Metadata condition on {} needs a value",
+                    predicate.path
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MetadataPredicate {
+    fn segments(&self) -> Vec<String> {
+        self.path.split('.').map(str::to_string).collect()
+    }
+
+    /// The object `metadata @>` has to contain for an equality match, so the
+    /// GIN index can serve it
+    fn containment(&self) -> Value {
+        let value = self.value.clone().unwrap_or(Value::Null);
+        self.segments()
+            .into_iter()
+            .rev()
+            .fold(value, |inner, segment| {
+                Value::Object(serde_json::Map::from_iter([(segment, inner)]))
+            })
+    }
+}
+
+impl TransactionSort {
+    fn order_by(&self) -> &'static str {
+        match self {
+            TransactionSort::Newest => " ORDER BY created_at DESC, id DESC",
+            TransactionSort::Oldest => " ORDER BY created_at ASC, id ASC",
+            TransactionSort::AmountDesc => " ORDER BY amount DESC, created_at DESC, id DESC",
+            TransactionSort::AmountAsc => " ORDER BY amount ASC, created_at DESC, id DESC",
+        }
+    }
+}
+
+/// Audits a wallet status change inside the database transaction making it.
+/// The note is for other admins and is only kept here.
+async fn audit_wallet_change(
+    db_tx: &mut sqlx::Transaction<'_, Postgres>,
+    actor: &AuditActor,
+    action: &str,
+    change: &WalletStatusChange,
+) -> Result<(), AdminError> {
+    let wallet = &change.wallet;
+
+    actor
+        .log_in(
+            db_tx,
+            action,
+            "wallet",
+            Some(wallet.id),
+            Some(serde_json::json!({
+                "status": change.previous_status,
+                "reason": change.previous_reason,
+            })),
+            Some(serde_json::json!({
+                "status": wallet.status,
+                "reason": wallet.status_reason,
+                "note": wallet.status_note,
+                "sweep_transaction_id": change.sweep.as_ref().map(|sweep| sweep.id),
+                "sweep_amount": change.sweep.as_ref().map(|sweep| sweep.money()),
+            })),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Opens the WHERE clause of a transaction search
+fn push_transaction_filters(query: &mut QueryBuilder<'_, Postgres>, filter: &TransactionFilter) {
+    query.push(" WHERE true");
+
+    if let Some(user_id) = filter.user_id {
+        query.push(" AND (debit_wallet_id IN (SELECT id FROM wallets WHERE user_id = ");
+        query.push_bind(user_id);
+        query.push(") OR credit_wallet_id IN (SELECT id FROM wallets WHERE user_id = ");
+        query.push_bind(user_id);
+        query.push("))");
+    }
+
+    if let Some(wallet_id) = filter.wallet_id {
+        query.push(" AND (debit_wallet_id = ");
+        query.push_bind(wallet_id);
+        query.push(" OR credit_wallet_id = ");
+        query.push_bind(wallet_id);
+        query.push(")");
+    }
+
+    if let Some(transaction_type) = filter.transaction_type.clone() {
+        query.push(" AND transaction_type = ");
+        query.push_bind(transaction_type);
+    }
+
+    if let Some(status) = filter.status.clone() {
+        query.push(" AND status = ");
+        query.push_bind(status);
+    }
+
+    if let Some(currency) = &filter.currency {
+        query.push(" AND currency = ");
+        query.push_bind(currency.to_uppercase());
+    }
+
+    if let Some(min) = filter.min_amount {
+        query.push(" AND amount >= ");
+        query.push_bind(min);
+    }
+
+    if let Some(max) = filter.max_amount {
+        query.push(" AND amount <= ");
+        query.push_bind(max);
+    }
+
+    if let Some(prefix) = &filter.reference_prefix {
+        query.push(" AND reference_id LIKE ");
+        query.push_bind(format!("{}%", escape_like(prefix)));
+    }
+
+    // Matches the expression of idx_transactions_search
+    if let Some(q) = filter.q.as_deref().filter(|q| !q.trim().is_empty()) {
+        query.push(
+            " AND to_tsvector('simple', \
+             coalesce(reference_id, '') || ' ' || coalesce(metadata::text, '')) \
+             @@ plainto_tsquery('simple', ",
+        );
+        query.push_bind(q.to_string());
+        query.push(")");
+    }
+
+    for predicate in &filter.metadata {
+        match predicate.op {
+            MetadataOp::Eq => {
+                query.push(" AND metadata @> ");
+                query.push_bind(predicate.containment());
+            }
+            MetadataOp::Ne => {
+                query.push(" AND NOT COALESCE(metadata @> ");
+                query.push_bind(predicate.containment());
+                query.push(", false)");
+            }
+            MetadataOp::Exists => {
+                query.push(" AND metadata #> ");
+                query.push_bind(predicate.segments());
+                query.push(" IS NOT NULL");
+            }
+            MetadataOp::Missing => {
+                query.push(" AND metadata #> ");
+                query.push_bind(predicate.segments());
+                query.push(" IS NULL");
+            }
+        }
+    }
+
+    if let Some(after) = filter.created_after {
+        query.push(" AND created_at >= ");
+        query.push_bind(after);
+    }
+
+    if let Some(before) = filter.created_before {
+        query.push(" AND created_at <= ");
+        query.push_bind(before);
+    }
+}
+
+/// Escapes LIKE wildcards so a prefix matches literally
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn predicate(path: &str, op: MetadataOp, value: Option<Value>) -> MetadataPredicate {
+        MetadataPredicate {
+            path: path.to_string(),
+            op,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("INV-42"), "INV-42");
+        assert_eq!(escape_like("100%_off"), "100\\%\\_off");
+        assert_eq!(escape_like("a\\b"), "a\\\\b");
+    }
+
+    #[test]
+    fn test_containment_nests_path() {
+        let nested = predicate("p2p.recipient.type", MetadataOp::Eq, Some(json!("phone")));
+        assert_eq!(nested.containment(), json!({ "p2p": { "recipient": { "type": "phone" } } }));
+
+        let flat = predicate("order_id", MetadataOp::Eq, Some(json!(5)));
+        assert_eq!(flat.containment(), json!({ "order_id": 5 }));
+    }
+
+    #[test]
+    fn test_filter_validation() {
+        assert!(TransactionFilter::default().validate().is_ok());
+
+        let unbounded_currency = TransactionFilter {
+            min_amount: Some(Decimal::ONE),
+            ..Default::default()
+        };
+        assert!(unbounded_currency.validate().is_err());
+
+        let bounded = TransactionFilter {
+            min_amount: Some(Decimal::ONE),
+            currency: Some("USD".to_string()),
+            ..Default::default()
+        };
+        assert!(bounded.validate().is_ok());
+
+        let too_many = TransactionFilter {
+            metadata: (0..=MAX_METADATA_PREDICATES)
+                .map(|i| predicate(&format!("key{}", i), MetadataOp::Exists, None))
+                .collect(),
+            ..Default::default()
+        };
+        assert!(too_many.validate().is_err());
+
+        let too_deep = vec!["a"; MAX_METADATA_DEPTH + 1].join(".");
+        for invalid in [
+            predicate("a..b", MetadataOp::Exists, None),
+            predicate(&too_deep, MetadataOp::Exists, None),
+            predicate("a", MetadataOp::Eq, None),
+            predicate("a", MetadataOp::Ne, None),
+        ] {
+            let filter = TransactionFilter {
+                metadata: vec![invalid],
+                ..Default::default()
+            };
+            assert!(filter.validate().is_err());
+        }
+
+        let valid = TransactionFilter {
+            metadata: vec![
+                predicate("a.b", MetadataOp::Eq, Some(json!(1))),
+                predicate("a.c", MetadataOp::Missing, None),
+            ],
+            ..Default::default()
+        };
+        assert!(valid.validate().is_ok());
+    }
+}
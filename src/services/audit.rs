@@ -1,7 +1,13 @@
-use crate::models::audit::AuditLog;
+use crate::{
+    models::{
+        audit::AuditLog,
+        pagination::{Page, PageRequest},
+    },
+    telemetry,
+};
 use axum::http::HeaderMap;
 use serde_json::Value;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use std::net::IpAddr;
 use thiserror::Error;
 use uuid::Uuid;
@@ -14,6 +20,73 @@ pub enum AuditError {
     MissingHeader(String),
 }
 
+/// The admin and request behind an action, captured up front so its audit
+/// entry can be written inside the database transaction making the change
+#[derive(Debug, Clone)]
+pub struct AuditActor {
+    pub admin_id: Uuid,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub request_id: Option<String>,
+    pub trace_id: Option<String>,
+}
+
+impl AuditActor {
+    pub fn from_request(admin_id: Uuid, headers: &HeaderMap) -> Self {
+        // Extract IP address
+        let ip_address = headers
+            .get("x-forwarded-for")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|s| s.split(',').next())
+            .unwrap_or("unknown")
+            .to_string();
+
+        // Extract user agent
+        let user_agent = headers
+            .get("user-agent")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("unknown")
+            .to_string();
+
+        let context = telemetry::current();
+
+        Self {
+            admin_id,
+            ip_address,
+            user_agent,
+            request_id: context.as_ref().map(|c| c.request_id.clone()),
+            trace_id: context.as_ref().map(|c| c.trace.trace_id.clone()),
+        }
+    }
+
+    /// Writes the audit entry inside the caller's database transaction, so
+    /// it commits or rolls back with the change it records
+    pub async fn log_in(
+        &self,
+        db_tx: &mut Transaction<'_, Postgres>,
+        action: &str,
+        entity_type: &str,
+        entity_id: Option<Uuid>,
+        old_value: Option<Value>,
+        new_value: Option<Value>,
+    ) -> Result<AuditLog, sqlx::Error> {
+        AuditLog::create(
+            &mut **db_tx,
+            self.admin_id,
+            action,
+            entity_type,
+            entity_id,
+            old_value,
+            new_value,
+            &self.ip_address,
+            &self.user_agent,
+            self.request_id.as_deref(),
+            self.trace_id.as_deref(),
+        )
+        .await
+    }
+}
+
 pub struct AuditService {
     pool: PgPool,
 }
@@ -33,20 +106,7 @@ impl AuditService {
         new_value: Option<Value>,
         headers: &HeaderMap,
     ) -> Result<AuditLog, AuditError> {
-        // Extract IP address
-        let ip_address = headers
-            .get("x-forwarded-for")
-            .and_then(|h| h.to_str().ok())
-            .and_then(|s| s.split(',').next())
-            .unwrap_or("unknown")
-            .to_string();
-
-        // Extract user agent
-        let user_agent = headers
-            .get("user-agent")
-            .and_then(|h| h.to_str().ok())
-            .unwrap_or("unknown")
-            .to_string();
+        let actor = AuditActor::from_request(admin_id, headers);
 
         let log = AuditLog::create(
             &self.pool,
@@ -56,8 +116,10 @@ impl AuditService {
             entity_id,
             old_value,
             new_value,
-            &ip_address,
-            &user_agent,
+            &actor.ip_address,
+            &actor.user_agent,
+            actor.request_id.as_deref(),
+            actor.trace_id.as_deref(),
         )
         .await?;
 
@@ -67,10 +129,9 @@ impl AuditService {
     pub async fn get_admin_logs(
         &self,
         admin_id: Uuid,
-        limit: i64,
-        offset: i64,
-    ) -> Result<Vec<AuditLog>, AuditError> {
-        let logs = AuditLog::list_by_admin(&self.pool, admin_id, limit, offset).await?;
+        page: &PageRequest,
+    ) -> Result<Page<AuditLog>, AuditError> {
+        let logs = AuditLog::list_by_admin(&self.pool, admin_id, page).await?;
         Ok(logs)
     }
 
@@ -78,20 +139,18 @@ impl AuditService {
         &self,
         entity_type: &str,
         entity_id: Uuid,
-        limit: i64,
-        offset: i64,
-    ) -> Result<Vec<AuditLog>, AuditError> {
-        let logs = AuditLog::list_by_entity(&self.pool, entity_type, entity_id, limit, offset).await?;
+        page: &PageRequest,
+    ) -> Result<Page<AuditLog>, AuditError> {
+        let logs = AuditLog::list_by_entity(&self.pool, entity_type, entity_id, page).await?;
         Ok(logs)
     }
 
     pub async fn search_logs(
         &self,
         query: &str,
-        limit: i64,
-        offset: i64,
-    ) -> Result<Vec<AuditLog>, AuditError> {
-        let logs = AuditLog::search(&self.pool, query, limit, offset).await?;
+        page: &PageRequest,
+    ) -> Result<Page<AuditLog>, AuditError> {
+        let logs = AuditLog::search(&self.pool, query, page).await?;
         Ok(logs)
     }
 }
@@ -55,6 +55,9 @@ impl EmailService {
         hb.register_template_string("reset_password", include_str!("../templates/reset_password.hbs"))?;
         hb.register_template_string("two_factor_enabled", include_str!("../templates/two_factor_enabled.hbs"))?;
         hb.register_template_string("security_alert", include_str!("../templates/security_alert.hbs"))?;
+        hb.register_template_string("scheduled_transfer_failed", include_str!("../templates/scheduled_transfer_failed.hbs"))?;
+        hb.register_template_string("p2p_claim_invite", include_str!("../templates/p2p_claim_invite.hbs"))?;
+        hb.register_template_string("wallet_status_changed", include_str!("../templates/wallet_status_changed.hbs"))?;
 
         Ok(Self {
             mailer,
@@ -137,17 +140,16 @@ impl EmailService {
         .await
     }
 
-    // Send 2FA enabled notification
+    // Send 2FA enabled notification. Backup codes are never emailed; the
+    // user is shown them once when enabling 2FA.
     pub async fn send_2fa_enabled(
         &self,
         to_email: &str,
         full_name: &str,
-        backup_codes: &[String],
     ) -> Result<(), EmailError> {
         #[derive(Serialize)]
         struct TwoFactorData {
             full_name: String,
-            backup_codes: Vec<String>,
         }
 
         let data = EmailTemplate {
@@ -156,7 +158,6 @@ impl EmailService {
             support_email: std::env::var("SUPPORT_EMAIL").unwrap(),
             data: TwoFactorData {
                 full_name: full_name.to_string(),
-                backup_codes: backup_codes.to_vec(),
             },
         };
 
@@ -200,6 +201,123 @@ impl EmailService {
         self.queue_email(to_email, "Security Alert", "security_alert", &data).await
     }
 
+    // Send scheduled transfer failure notice
+    pub async fn send_scheduled_transfer_failed(
+        &self,
+        to_email: &str,
+        full_name: &str,
+        amount: &str,
+        description: &str,
+        reason: &str,
+    ) -> Result<(), EmailError> {
+        #[derive(Serialize)]
+        struct ScheduledTransferFailedData {
+            full_name: String,
+            amount: String,
+            description: String,
+            reason: String,
+        }
+
+        let data = EmailTemplate {
+            app_name: "NEDApay".to_string(),
+            app_url: std::env::var("APP_URL").unwrap(),
+            support_email: std::env::var("SUPPORT_EMAIL").unwrap(),
+            data: ScheduledTransferFailedData {
+                full_name: full_name.to_string(),
+                amount: amount.to_string(),
+                description: description.to_string(),
+                reason: reason.to_string(),
+            },
+        };
+
+        self.queue_email(
+            to_email,
+            "Scheduled Transfer Failed",
+            "scheduled_transfer_failed",
+            &data,
+        )
+        .await
+    }
+
+    // Send P2P claim invitation to an unregistered recipient
+    pub async fn send_p2p_claim_invite(
+        &self,
+        to_email: &str,
+        sender_name: &str,
+        amount: &str,
+        note: Option<&str>,
+        expires_at: &str,
+    ) -> Result<(), EmailError> {
+        #[derive(Serialize)]
+        struct P2pClaimInviteData {
+            sender_name: String,
+            amount: String,
+            note: Option<String>,
+            expires_at: String,
+        }
+
+        let data = EmailTemplate {
+            app_name: "NEDApay".to_string(),
+            app_url: std::env::var("APP_URL").unwrap(),
+            support_email: std::env::var("SUPPORT_EMAIL").unwrap(),
+            data: P2pClaimInviteData {
+                sender_name: sender_name.to_string(),
+                amount: amount.to_string(),
+                note: note.map(str::to_string),
+                expires_at: expires_at.to_string(),
+            },
+        };
+
+        self.queue_email(
+            to_email,
+            &format!("{} sent you {}", sender_name, amount),
+            "p2p_claim_invite",
+            &data,
+        )
+        .await
+    }
+
+    // Send wallet status change notice
+    pub async fn send_wallet_status_changed(
+        &self,
+        to_email: &str,
+        full_name: &str,
+        wallet: &str,
+        status: &str,
+        reason: &str,
+        consequence: &str,
+    ) -> Result<(), EmailError> {
+        #[derive(Serialize)]
+        struct WalletStatusChangedData {
+            full_name: String,
+            wallet: String,
+            status: String,
+            reason: String,
+            consequence: String,
+        }
+
+        let data = EmailTemplate {
+            app_name: "NEDApay".to_string(),
+            app_url: std::env::var("APP_URL").unwrap(),
+            support_email: std::env::var("SUPPORT_EMAIL").unwrap(),
+            data: WalletStatusChangedData {
+                full_name: full_name.to_string(),
+                wallet: wallet.to_string(),
+                status: status.to_string(),
+                reason: reason.to_string(),
+                consequence: consequence.to_string(),
+            },
+        };
+
+        self.queue_email(
+            to_email,
+            &format!("Your wallet has been {}", status.to_lowercase()),
+            "wallet_status_changed",
+            &data,
+        )
+        .await
+    }
+
     // Queue email for sending
     async fn queue_email<T: Serialize>(
         &self,
@@ -0,0 +1,31 @@
+use crate::models::hold::WalletHold;
+use sqlx::PgPool;
+use tokio::time::{self, Duration};
+use tracing::{error, info};
+
+pub struct HoldExpiryService {
+    pool: PgPool,
+}
+
+impl HoldExpiryService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Starts the periodic release of expired holds
+    pub async fn start(&self) {
+        let pool = self.pool.clone();
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                match WalletHold::expire_stale(&pool).await {
+                    Ok(0) => {}
+                    Ok(count) => info!("Released {} expired holds", count),
+                    Err(e) => error!("Error expiring holds: {}", e),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,31 @@
+use crate::models::idempotency::IdempotencyKey;
+use sqlx::PgPool;
+use tokio::time::{self, Duration};
+use tracing::{error, info};
+
+pub struct IdempotencyKeyPurgeService {
+    pool: PgPool,
+}
+
+impl IdempotencyKeyPurgeService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Starts the periodic removal of expired idempotency keys
+    pub async fn start(&self) {
+        let pool = self.pool.clone();
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(60 * 60)); // Hourly
+            loop {
+                interval.tick().await;
+                match IdempotencyKey::purge_expired(&pool).await {
+                    Ok(0) => {}
+                    Ok(count) => info!("Purged {} expired idempotency keys", count),
+                    Err(e) => error!("Error purging idempotency keys: {}", e),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,172 @@
+use crate::models::{
+    metadata_schema::{compile, MetadataSchema, MetadataSchemaError},
+    transaction::TransactionType,
+};
+use jsonschema::{error::ValidationErrorKind, JSONSchema};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+use tokio::time::{self, Duration};
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// One way metadata failed its schema
+#[derive(Debug, Clone, Serialize)]
+pub struct MetadataViolation {
+    /// Dotted path to the offending value, starting at `metadata`
+    pub field: String,
+    /// Which keyword failed, named like the validator codes on request
+    /// fields, e.g. `required` or `length`
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// The active metadata schema of each transaction type, compiled once. The
+/// registry is loaded at startup and reloaded when an admin changes a schema
+/// or, for other instances, by the refresh loop.
+pub struct MetadataSchemaRegistry {
+    pool: PgPool,
+    schemas: RwLock<HashMap<&'static str, Arc<JSONSchema>>>,
+}
+
+impl MetadataSchemaRegistry {
+    /// Seeds the bundled schemas if needed and compiles the active versions
+    pub async fn load(pool: PgPool) -> Result<Self, MetadataSchemaError> {
+        let seeded = MetadataSchema::seed_bundled(&pool).await?;
+        if seeded > 0 {
+            info!("Seeded {} bundled metadata schemas", seeded);
+        }
+
+        let registry = Self {
+            pool,
+            schemas: RwLock::new(HashMap::new()),
+        };
+        registry.reload().await?;
+
+        Ok(registry)
+    }
+
+    /// Recompiles the active schemas from the database
+    pub async fn reload(&self) -> Result<(), MetadataSchemaError> {
+        let mut schemas = HashMap::new();
+        for schema in MetadataSchema::find_active(&self.pool).await? {
+            let compiled = compile(&schema.schema)?;
+            schemas.insert(schema.transaction_type.as_str(), Arc::new(compiled));
+        }
+
+        *self.schemas.write().unwrap_or_else(|e| e.into_inner()) = schemas;
+
+        Ok(())
+    }
+
+    /// Starts the periodic reload that picks up schema changes made through
+    /// other instances
+    pub async fn start(self: &Arc<Self>) {
+        let registry = Arc::clone(self);
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                if let Err(e) = registry.reload().await {
+                    error!("Error reloading metadata schemas: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Checks metadata against the transaction type's active schema. Missing
+    /// metadata is checked as an empty object, so required fields still
+    /// apply.
+    pub fn validate(
+        &self,
+        transaction_type: &TransactionType,
+        metadata: Option<&Value>,
+    ) -> Result<(), Vec<MetadataViolation>> {
+        let Some(schema) = self.active(transaction_type) else {
+            return Ok(());
+        };
+
+        let empty = Value::Object(serde_json::Map::new());
+        let metadata = metadata.unwrap_or(&empty);
+
+        let violations: Vec<MetadataViolation> = match schema.validate(metadata) {
+            Ok(()) => return Ok(()),
+            Err(errors) => errors
+                .map(|error| {
+                    let mut field = String::from("metadata");
+                    for segment in error.instance_path.to_string().split('/').skip(1) {
+                        field.push('.');
+                        field.push_str(segment);
+                    }
+                    // Report a missing property against the property itself
+                    if let ValidationErrorKind::Required { property } = &error.kind {
+                        field.push('.');
+                        field.push_str(property.as_str().unwrap_or_default());
+                    }
+
+                    MetadataViolation {
+                        field,
+                        code: violation_code(&error.kind),
+                        message: error.to_string(),
+                    }
+                })
+                .collect(),
+        };
+
+        Err(violations)
+    }
+
+    /// Adds a new version of a transaction type's schema, and enforces it
+    /// straight away if `activate` is set
+    pub async fn create(
+        &self,
+        transaction_type: TransactionType,
+        schema: Value,
+        created_by: Uuid,
+        activate: bool,
+    ) -> Result<MetadataSchema, MetadataSchemaError> {
+        let created =
+            MetadataSchema::create(&self.pool, transaction_type, schema, created_by).await?;
+        if !activate {
+            return Ok(created);
+        }
+
+        self.activate(created.id).await
+    }
+
+    pub async fn activate(&self, id: Uuid) -> Result<MetadataSchema, MetadataSchemaError> {
+        let activated = MetadataSchema::activate(&self.pool, id).await?;
+        self.reload().await?;
+
+        Ok(activated)
+    }
+
+    fn active(&self, transaction_type: &TransactionType) -> Option<Arc<JSONSchema>> {
+        self.schemas
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(transaction_type.as_str())
+            .cloned()
+    }
+}
+
+fn violation_code(kind: &ValidationErrorKind) -> &'static str {
+    match kind {
+        ValidationErrorKind::Required { .. } => "required",
+        ValidationErrorKind::Type { .. } => "type",
+        ValidationErrorKind::MinLength { .. } | ValidationErrorKind::MaxLength { .. } => "length",
+        ValidationErrorKind::Minimum { .. }
+        | ValidationErrorKind::Maximum { .. }
+        | ValidationErrorKind::ExclusiveMinimum { .. }
+        | ValidationErrorKind::ExclusiveMaximum { .. } => "range",
+        ValidationErrorKind::Pattern { .. } => "pattern",
+        ValidationErrorKind::Enum { .. } => "enum",
+        ValidationErrorKind::AdditionalProperties { .. } => "unknown_field",
+        _ => "invalid",
+    }
+}
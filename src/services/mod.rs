@@ -2,3 +2,13 @@ pub mod reconciliation;
 pub mod notification;
 pub mod cache;
 pub mod email;
+pub mod queue;
+pub mod holds;
+pub mod scheduler;
+pub mod p2p;
+pub mod statement;
+pub mod metadata_schema;
+pub mod totp;
+pub mod token_denylist;
+pub mod password;
+pub mod idempotency;
@@ -0,0 +1,47 @@
+use crate::models::pending_transfer::{PendingTransfer, PendingTransferError};
+use sqlx::PgPool;
+use tokio::time::{self, Duration};
+use tracing::{error, info};
+
+pub struct PendingTransferExpiryService {
+    pool: PgPool,
+}
+
+impl PendingTransferExpiryService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Starts the periodic refund of unclaimed P2P transfers
+    pub async fn start(&self) {
+        let pool = self.pool.clone();
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(300));
+            loop {
+                interval.tick().await;
+                match refund_expired(&pool).await {
+                    Ok(0) => {}
+                    Ok(count) => info!("Refunded {} unclaimed P2P transfers", count),
+                    Err(e) => error!("Error finding unclaimed P2P transfers: {}", e),
+                }
+            }
+        });
+    }
+}
+
+/// Refunds each expired transfer in its own database transaction, so one
+/// that fails is logged and retried on the next run without holding up the
+/// rest
+async fn refund_expired(pool: &PgPool) -> Result<u64, PendingTransferError> {
+    let mut refunded = 0;
+    for id in PendingTransfer::find_expired_ids(pool).await? {
+        match PendingTransfer::refund_expired(pool, id).await {
+            Ok(true) => refunded += 1,
+            Ok(false) => {}
+            Err(e) => error!("Error refunding unclaimed P2P transfer {}: {}", id, e),
+        }
+    }
+
+    Ok(refunded)
+}
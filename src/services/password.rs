@@ -0,0 +1,258 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use thiserror::Error;
+
+/// OWASP's recommended Argon2id baseline: 19 MiB, 2 passes, 1 lane
+const DEFAULT_MEMORY_KIB: u32 = 19_456;
+const DEFAULT_ITERATIONS: u32 = 2;
+const DEFAULT_PARALLELISM: u32 = 1;
+
+pub const MIN_LENGTH: usize = 12;
+/// Bounds the work an attacker can make a single login attempt cost us
+pub const MAX_LENGTH: usize = 128;
+
+const BUNDLED_BREACHED: &str = include_str!("../data/breached_passwords.txt");
+
+#[derive(Debug, Error)]
+pub enum PasswordError {
+    #[error("Invalid Argon2 parameters: {0}")]
+    InvalidParams(String),
+    #[error("Unrecognized password hash format")]
+    UnknownFormat,
+    #[error("Password hashing failed: {0}")]
+    Hashing(String),
+    #[error("Bcrypt error: {0}")]
+    Bcrypt(#[from] bcrypt::BcryptError),
+}
+
+/// Why a new password was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum PolicyViolation {
+    #[error("Password must be at least {} characters", MIN_LENGTH)]
+    TooShort,
+    #[error("Password must be at most {} characters", MAX_LENGTH)]
+    TooLong,
+    #[error("Password appears in a list of breached passwords")]
+    Breached,
+    #[error("Password must not contain your email address")]
+    ContainsEmail,
+}
+
+impl PolicyViolation {
+    /// Stable code for API clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            PolicyViolation::TooShort | PolicyViolation::TooLong => "length",
+            PolicyViolation::Breached => "breached",
+            PolicyViolation::ContainsEmail => "contains_email",
+        }
+    }
+}
+
+/// Outcome of checking a password against a stored hash
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verification {
+    Invalid,
+    /// `needs_rehash` is set when the hash is bcrypt or uses parameters
+    /// other than the current ones
+    Valid { needs_rehash: bool },
+}
+
+/// Reads and validates the Argon2id parameters. Called at startup so a bad
+/// setting stops the server instead of failing every login.
+pub fn init() -> Result<(), PasswordError> {
+    let params = load_params()?;
+    let _ = PARAMS.set(params);
+    Ok(())
+}
+
+static PARAMS: OnceLock<Params> = OnceLock::new();
+
+/// Argon2id parameters from PASSWORD_ARGON2_MEMORY_KIB,
+/// PASSWORD_ARGON2_ITERATIONS and PASSWORD_ARGON2_PARALLELISM
+fn params() -> &'static Params {
+    PARAMS.get_or_init(|| load_params().expect("Argon2 parameters are validated by password::init"))
+}
+
+fn load_params() -> Result<Params, PasswordError> {
+    Params::new(
+        env_u32("PASSWORD_ARGON2_MEMORY_KIB", DEFAULT_MEMORY_KIB)?,
+        env_u32("PASSWORD_ARGON2_ITERATIONS", DEFAULT_ITERATIONS)?,
+        env_u32("PASSWORD_ARGON2_PARALLELISM", DEFAULT_PARALLELISM)?,
+        None,
+    )
+    .map_err(|e| PasswordError::InvalidParams(e.to_string()))
+}
+
+fn env_u32(name: &str, default: u32) -> Result<u32, PasswordError> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| PasswordError::InvalidParams(format!("{name} must be a whole number"))),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Hashes a password with Argon2id at the current parameters
+pub fn hash(password: &str) -> Result<String, PasswordError> {
+    hash_with(password, params().clone())
+}
+
+fn hash_with(password: &str, params: Params) -> Result<String, PasswordError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| PasswordError::Hashing(e.to_string()))
+}
+
+/// Checks a password against a stored Argon2 or legacy bcrypt hash
+pub fn verify(password: &str, stored_hash: &str) -> Result<Verification, PasswordError> {
+    verify_with(password, stored_hash, params())
+}
+
+fn verify_with(
+    password: &str,
+    stored_hash: &str,
+    current: &Params,
+) -> Result<Verification, PasswordError> {
+    // $2a$, $2b$ and $2y$ hashes predate the move to Argon2
+    if stored_hash.starts_with("$2") {
+        return Ok(match bcrypt::verify(password, stored_hash)? {
+            true => Verification::Valid { needs_rehash: true },
+            false => Verification::Invalid,
+        });
+    }
+
+    let parsed = PasswordHash::new(stored_hash).map_err(|_| PasswordError::UnknownFormat)?;
+
+    // The hash carries its own algorithm and parameters
+    match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+        Ok(()) => Ok(Verification::Valid {
+            needs_rehash: is_outdated(&parsed, current),
+        }),
+        Err(argon2::password_hash::Error::Password) => Ok(Verification::Invalid),
+        Err(e) => Err(PasswordError::Hashing(e.to_string())),
+    }
+}
+
+fn is_outdated(hash: &PasswordHash, current: &Params) -> bool {
+    if hash.algorithm != Algorithm::Argon2id.ident() || hash.version != Some(Version::V0x13.into()) {
+        return true;
+    }
+
+    match Params::try_from(hash) {
+        Ok(params) => {
+            params.m_cost() != current.m_cost()
+                || params.t_cost() != current.t_cost()
+                || params.p_cost() != current.p_cost()
+        }
+        Err(_) => true,
+    }
+}
+
+/// Checks a new password against the registration policy. `email` is the
+/// account's address, which the password may not contain.
+pub fn check_policy(password: &str, email: &str) -> Result<(), PolicyViolation> {
+    let length = password.chars().count();
+    if length < MIN_LENGTH {
+        return Err(PolicyViolation::TooShort);
+    }
+    if length > MAX_LENGTH {
+        return Err(PolicyViolation::TooLong);
+    }
+
+    let lowered = password.to_lowercase();
+    if breached_passwords().contains(lowered.trim()) {
+        return Err(PolicyViolation::Breached);
+    }
+
+    // Very short local parts, e.g. "jo@", would match too many passwords
+    let email = email.trim().to_lowercase();
+    let local_part = email.split('@').next().unwrap_or_default();
+    if lowered.contains(&email) || (local_part.len() >= 4 && lowered.contains(local_part)) {
+        return Err(PolicyViolation::ContainsEmail);
+    }
+
+    Ok(())
+}
+
+/// The bundled list plus any entries from BREACHED_PASSWORDS_FILE, loaded
+/// on first use
+fn breached_passwords() -> &'static HashSet<String> {
+    static BREACHED: OnceLock<HashSet<String>> = OnceLock::new();
+    BREACHED.get_or_init(|| {
+        let mut passwords = parse_list(BUNDLED_BREACHED);
+
+        if let Ok(path) = std::env::var("BREACHED_PASSWORDS_FILE") {
+            match std::fs::read_to_string(&path) {
+                Ok(contents) => passwords.extend(parse_list(&contents)),
+                Err(e) => tracing::error!("Could not read breached password list {}: {}", path, e),
+            }
+        }
+
+        passwords
+    })
+}
+
+fn parse_list(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap parameters so the tests stay fast
+    fn test_params(iterations: u32) -> Params {
+        Params::new(1024, iterations, 1, None).unwrap()
+    }
+
+    #[test]
+    fn rehashes_legacy_and_outdated_hashes() {
+        let current = test_params(2);
+
+        let legacy = bcrypt::hash("correct horse battery", 4).unwrap();
+        assert_eq!(
+            verify_with("correct horse battery", &legacy, &current).unwrap(),
+            Verification::Valid { needs_rehash: true }
+        );
+        assert_eq!(
+            verify_with("wrong password", &legacy, &current).unwrap(),
+            Verification::Invalid
+        );
+
+        let outdated = hash_with("correct horse battery", test_params(1)).unwrap();
+        assert_eq!(
+            verify_with("correct horse battery", &outdated, &current).unwrap(),
+            Verification::Valid { needs_rehash: true }
+        );
+
+        let fresh = hash_with("correct horse battery", test_params(2)).unwrap();
+        assert_eq!(
+            verify_with("correct horse battery", &fresh, &current).unwrap(),
+            Verification::Valid { needs_rehash: false }
+        );
+    }
+
+    #[test]
+    fn enforces_policy() {
+        let email = "amina.otieno@example.com";
+
+        assert_eq!(check_policy("short", email), Err(PolicyViolation::TooShort));
+        assert_eq!(check_policy(&"x".repeat(MAX_LENGTH + 1), email), Err(PolicyViolation::TooLong));
+        assert_eq!(check_policy("Password1234", email), Err(PolicyViolation::Breached));
+        assert_eq!(check_policy("Amina.Otieno!2024", email), Err(PolicyViolation::ContainsEmail));
+        assert_eq!(check_policy("maize-kettle-horizon", email), Ok(()));
+    }
+}
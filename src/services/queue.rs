@@ -1,10 +1,13 @@
-use crate::services::email::{EmailError, EmailService};
+use crate::{
+    services::email::{EmailError, EmailService},
+    telemetry,
+};
 use redis::{aio::ConnectionManager, AsyncCommands};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::time::{self, Duration};
-use tracing::{error, info};
+use tracing::{error, info, info_span, Instrument};
 use uuid::Uuid;
 
 const EMAIL_QUEUE_KEY: &str = "email:queue";
@@ -32,25 +35,27 @@ pub struct QueuedEmail {
     pub data: serde_json::Value,
     pub retries: i32,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Request that queued the email. Emails queued before this was added
+    /// have none.
+    #[serde(default)]
+    pub request_id: Option<String>,
+    #[serde(default)]
+    pub trace_id: Option<String>,
 }
 
 pub struct EmailQueue {
     redis: ConnectionManager,
-    email_service: Arc<EmailService>,
 }
 
 impl EmailQueue {
-    pub fn new(redis: ConnectionManager, email_service: Arc<EmailService>) -> Self {
-        Self {
-            redis,
-            email_service,
-        }
+    pub fn new(redis: ConnectionManager) -> Self {
+        Self { redis }
     }
 
-    // Start the email queue processor
-    pub async fn start(&self) {
+    // Start the email queue processor. The service is passed here rather
+    // than to `new` because it needs the queue to be built first.
+    pub async fn start(&self, email_service: Arc<EmailService>) {
         let redis = self.redis.clone();
-        let email_service = Arc::clone(&self.email_service);
 
         tokio::spawn(async move {
             let mut interval = time::interval(Duration::from_secs(5));
@@ -74,6 +79,7 @@ impl EmailQueue {
         template: &str,
         data: serde_json::Value,
     ) -> Result<Uuid, QueueError> {
+        let context = telemetry::current();
         let email = QueuedEmail {
             id: Uuid::new_v4(),
             to_email: to_email.to_string(),
@@ -82,6 +88,8 @@ impl EmailQueue {
             data,
             retries: 0,
             created_at: chrono::Utc::now(),
+            request_id: context.as_ref().map(|c| c.request_id.clone()),
+            trace_id: context.map(|c| c.trace.trace_id),
         };
 
         let json = serde_json::to_string(&email)?;
@@ -105,50 +113,69 @@ impl EmailQueue {
 
         for email_json in emails {
             let email: QueuedEmail = serde_json::from_str(&email_json)?;
-            
-            // Move to processing queue
-            redis.zrem(EMAIL_QUEUE_KEY, &email_json).await?;
-            redis
-                .zadd(
-                    EMAIL_PROCESSING_KEY,
-                    email_json.clone(),
-                    chrono::Utc::now().timestamp(),
-                )
+            // Sends are logged under the request that queued the email
+            let span = info_span!(
+                "email",
+                email_id = %email.id,
+                request_id = email.request_id.as_deref(),
+                trace_id = email.trace_id.as_deref(),
+            );
+            Self::process_email(redis, email_service, email, email_json)
+                .instrument(span)
                 .await?;
+        }
+
+        Ok(())
+    }
 
-            // Try to send the email
-            match email_service
-                .send_email(&email.to_email, &email.subject, &email.template, &email.data)
-                .await
-            {
-                Ok(_) => {
-                    info!("Email sent successfully: {}", email.id);
+    async fn process_email(
+        redis: &ConnectionManager,
+        email_service: &EmailService,
+        email: QueuedEmail,
+        email_json: String,
+    ) -> Result<(), QueueError> {
+        // Move to processing queue
+        redis.zrem(EMAIL_QUEUE_KEY, &email_json).await?;
+        redis
+            .zadd(
+                EMAIL_PROCESSING_KEY,
+                email_json.clone(),
+                chrono::Utc::now().timestamp(),
+            )
+            .await?;
+
+        // Try to send the email
+        match email_service
+            .send_email(&email.to_email, &email.subject, &email.template, &email.data)
+            .await
+        {
+            Ok(_) => {
+                info!("Email sent successfully: {}", email.id);
+                redis.zrem(EMAIL_PROCESSING_KEY, email_json).await?;
+            }
+            Err(e) => {
+                error!("Failed to send email {}: {}", email.id, e);
+                let mut email = email;
+                email.retries += 1;
+
+                if email.retries >= MAX_RETRIES {
+                    // Move to failed queue
+                    let json = serde_json::to_string(&email)?;
                     redis.zrem(EMAIL_PROCESSING_KEY, email_json).await?;
-                }
-                Err(e) => {
-                    error!("Failed to send email {}: {}", email.id, e);
-                    let mut email = email;
-                    email.retries += 1;
-
-                    if email.retries >= MAX_RETRIES {
-                        // Move to failed queue
-                        let json = serde_json::to_string(&email)?;
-                        redis.zrem(EMAIL_PROCESSING_KEY, email_json).await?;
-                        redis
-                            .zadd(
-                                EMAIL_FAILED_KEY,
-                                json,
-                                chrono::Utc::now().timestamp(),
-                            )
-                            .await?;
-                    } else {
-                        // Requeue with exponential backoff
-                        let delay = 60 * (2_i64.pow(email.retries as u32));
-                        let next_attempt = chrono::Utc::now().timestamp() + delay;
-                        let json = serde_json::to_string(&email)?;
-                        redis.zrem(EMAIL_PROCESSING_KEY, email_json).await?;
-                        redis.zadd(EMAIL_QUEUE_KEY, json, next_attempt).await?;
-                    }
+                    redis
+                        .zadd(
+                            EMAIL_FAILED_KEY,
+                            json,
+                            chrono::Utc::now().timestamp(),
+                        )
+                        .await?;
+                } else {
+                    // Requeue with exponential backoff
+                    let delay = 60 * (2_i64.pow(email.retries as u32));
+                    let next_attempt = chrono::Utc::now().timestamp() + delay;
+                    let json = serde_json::to_string(&email)?;
+                    redis.zrem(EMAIL_PROCESSING_KEY, email_json).await?;
+                    redis.zadd(EMAIL_QUEUE_KEY, json, next_attempt).await?;
                 }
             }
         }
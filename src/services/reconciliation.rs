@@ -1,5 +1,7 @@
+use crate::models::ledger::{LedgerAccount, LedgerDiscrepancy, LedgerError};
 use crate::models::reserve::{ReserveAccount, ReserveTransaction};
 use crate::services::notification::NotificationService;
+use crate::telemetry::RequestContext;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
@@ -17,6 +19,8 @@ pub enum ReconciliationError {
     ReserveRatioError(Decimal),
     #[error("Reconciliation failed: {0}")]
     ReconciliationFailed(String),
+    #[error("Ledger error: {0}")]
+    LedgerError(#[from] LedgerError),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,6 +30,7 @@ pub struct ReconciliationReport {
     pub reserve_total: Decimal,
     pub ratio: Decimal,
     pub discrepancy: Option<Decimal>,
+    pub ledger_discrepancies: Vec<LedgerDiscrepancy>,
     pub status: ReconciliationStatus,
 }
 
@@ -70,30 +75,46 @@ impl ReconciliationService {
 
             loop {
                 interval.tick().await;
-                match Self::perform_reconciliation(&pool, min_ratio, warning_ratio).await {
-                    Ok(report) => {
-                        info!("Daily reconciliation completed: {:?}", report);
-                        if let Some(discrepancy) = report.discrepancy {
-                            if discrepancy != Decimal::ZERO {
+                // Each run gets its own ID so its log lines and alerts can be
+                // told apart from other runs
+                let run = async {
+                    match Self::perform_reconciliation(&pool, min_ratio, warning_ratio).await {
+                        Ok(report) => {
+                            info!("Daily reconciliation completed: {:?}", report);
+                            if !report.ledger_discrepancies.is_empty() {
                                 notification_service
                                     .send_alert(
-                                        "Reconciliation Discrepancy",
+                                        "Ledger Mismatch",
                                         &format!(
-                                            "Found discrepancy of {} in daily reconciliation",
-                                            discrepancy
+                                            "{} wallet balances do not match their ledger postings",
+                                            report.ledger_discrepancies.len()
                                         ),
                                     )
                                     .await;
                             }
+                            if let Some(discrepancy) = report.discrepancy {
+                                if discrepancy != Decimal::ZERO {
+                                    notification_service
+                                        .send_alert(
+                                            "Reconciliation Discrepancy",
+                                            &format!(
+                                                "Found discrepancy of {} in daily reconciliation",
+                                                discrepancy
+                                            ),
+                                        )
+                                        .await;
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            error!("Reconciliation failed: {}", e);
+                            notification_service
+                                .send_alert("Reconciliation Failed", &e.to_string())
+                                .await;
                         }
                     }
-                    Err(e) => {
-                        error!("Reconciliation failed: {}", e);
-                        notification_service
-                            .send_alert("Reconciliation Failed", &e.to_string())
-                            .await;
-                    }
-                }
+                };
+                RequestContext::generate().scope("reconciliation", run).await;
             }
         });
     }
@@ -137,8 +158,17 @@ impl ReconciliationService {
             reserve_total / wallet_total
         };
 
+        // Check wallet balances against ledger postings
+        let ledger_discrepancies = LedgerAccount::wallet_discrepancies(pool).await?;
+
         // Check reserve ratio
-        let status = if ratio < min_ratio {
+        let status = if !ledger_discrepancies.is_empty() {
+            error!(
+                "{} wallets disagree with the ledger",
+                ledger_discrepancies.len()
+            );
+            ReconciliationStatus::Error
+        } else if ratio < min_ratio {
             error!("Reserve ratio {} below minimum threshold {}", ratio, min_ratio);
             ReconciliationStatus::Error
         } else if ratio < warning_ratio {
@@ -170,6 +200,7 @@ impl ReconciliationService {
             reserve_total,
             ratio,
             discrepancy,
+            ledger_discrepancies,
             status,
         };
 
@@ -0,0 +1,180 @@
+use crate::{
+    models::{
+        schedule::{FailureOutcome, ScheduleError, ScheduledTransfer},
+        transaction::{Transaction, TransactionError, TransactionType},
+        user::User,
+        wallet::WalletError,
+    },
+    services::email::EmailService,
+    telemetry::RequestContext,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tokio::time::{self, Duration};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+const BATCH_SIZE: i64 = 100;
+
+/// Executes standing orders as they fall due
+pub struct TransferScheduler {
+    pool: PgPool,
+    email_service: Arc<EmailService>,
+}
+
+impl TransferScheduler {
+    pub fn new(pool: PgPool, email_service: Arc<EmailService>) -> Self {
+        Self {
+            pool,
+            email_service,
+        }
+    }
+
+    /// Starts the scheduler loop
+    pub async fn start(&self) {
+        let pool = self.pool.clone();
+        let email_service = Arc::clone(&self.email_service);
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                match Self::run_due(&pool, &email_service).await {
+                    Ok(0) => {}
+                    Ok(count) => info!("Executed {} scheduled transfers", count),
+                    Err(e) => error!("Error running scheduled transfers: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Runs every schedule that is due, returning how many transfers were made
+    async fn run_due(pool: &PgPool, email_service: &EmailService) -> Result<usize, ScheduleError> {
+        let mut executed = 0;
+        for id in ScheduledTransfer::due(pool, BATCH_SIZE).await? {
+            // Each execution is traced on its own, like an API request, so
+            // the transaction and emails it produces share one ID
+            let run = Self::run_one(pool, email_service, id);
+            match RequestContext::generate().scope("scheduled_transfer", run).await {
+                Ok(true) => executed += 1,
+                Ok(false) => {}
+                Err(e) => error!("Error running scheduled transfer {}: {}", id, e),
+            }
+        }
+
+        Ok(executed)
+    }
+
+    /// Makes the pending transfer for one schedule. The schedule row stays
+    /// locked until the outcome is recorded so no other instance runs it.
+    async fn run_one(
+        pool: &PgPool,
+        email_service: &EmailService,
+        id: Uuid,
+    ) -> Result<bool, ScheduleError> {
+        let mut db_tx = pool.begin().await?;
+
+        // Picked up by another instance, paused or cancelled since the scan
+        let Some(mut schedule) = ScheduledTransfer::lock_due(&mut db_tx, id).await? else {
+            return Ok(false);
+        };
+
+        let result = match schedule.find_executed(&mut db_tx).await? {
+            Some(transaction_id) => Ok(transaction_id),
+            None => Transaction::create(
+                pool,
+                Some(schedule.debit_wallet_id),
+                Some(schedule.credit_wallet_id),
+                schedule.amount,
+                schedule.currency.clone(),
+                TransactionType::Transfer,
+                Some(schedule.occurrence_reference()),
+                Some(serde_json::json!({
+                    "scheduled_transfer_id": schedule.id,
+                    "occurrence": schedule.occurrences,
+                })),
+            )
+            .await
+            .map(|transaction| transaction.id),
+        };
+
+        let outcome = match result {
+            Ok(transaction_id) => {
+                schedule.record_success(&mut db_tx, transaction_id).await?;
+                None
+            }
+            Err(e) => {
+                warn!("Scheduled transfer {} failed: {}", schedule.id, e);
+                let reason = failure_reason(&e);
+                Some((schedule.record_failure(&mut db_tx, reason).await?, reason))
+            }
+        };
+
+        db_tx.commit().await?;
+
+        match outcome {
+            None => Ok(true),
+            Some((FailureOutcome::Retrying(retry_at), _)) => {
+                info!("Scheduled transfer {} will retry at {}", schedule.id, retry_at);
+                Ok(false)
+            }
+            Some((FailureOutcome::Skipped, reason)) => {
+                Self::notify_failure(pool, email_service, &schedule, reason).await;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Tells the owner an occurrence was given up on
+    async fn notify_failure(
+        pool: &PgPool,
+        email_service: &EmailService,
+        schedule: &ScheduledTransfer,
+        reason: &str,
+    ) {
+        let user = match User::find_by_id(pool, schedule.user_id).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return,
+            Err(e) => {
+                error!("Error loading owner of scheduled transfer {}: {}", schedule.id, e);
+                return;
+            }
+        };
+
+        let description = schedule
+            .description
+            .clone()
+            .unwrap_or_else(|| "Scheduled transfer".to_string());
+
+        if let Err(e) = email_service
+            .send_scheduled_transfer_failed(
+                &user.email,
+                &user.full_name,
+                &schedule.money().to_string(),
+                &description,
+                reason,
+            )
+            .await
+        {
+            error!("Error notifying owner of scheduled transfer {}: {}", schedule.id, e);
+        }
+    }
+}
+
+/// What the owner is told about a failed occurrence. The schedule and the
+/// email are customer-facing, so internal error text only goes to the log.
+fn failure_reason(error: &TransactionError) -> &'static str {
+    match error {
+        TransactionError::WalletError(WalletError::InsufficientFunds { .. }) => {
+            "There was not enough money in the wallet"
+        }
+        TransactionError::WalletError(
+            WalletError::InactiveWallet | WalletError::AlreadyClosed | WalletError::UnexpectedStatus(_),
+        ) => "One of the wallets is frozen or closed",
+        TransactionError::WalletError(WalletError::UnsupportedCurrency(_)) => {
+            "The wallet does not hold this currency"
+        }
+        TransactionError::LimitError(_) => "The transfer would exceed your account limits",
+        _ => "We could not complete the transfer",
+    }
+}
@@ -1,11 +1,14 @@
 use crate::{
     models::{
+        currency::Money,
         user::User,
         audit::AuditLog,
+        reserve::{ReserveBalance, ReserveError},
     },
     services::email::EmailService,
 };
 use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::sync::Arc;
@@ -20,6 +23,8 @@ pub enum SecurityError {
     RedisError(#[from] redis::RedisError),
     #[error("Email error: {0}")]
     EmailError(String),
+    #[error("Reserve error: {0}")]
+    ReserveError(#[from] ReserveError),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -100,25 +105,32 @@ impl SecurityService {
     pub async fn monitor_transactions(
         &self,
         user_id: Uuid,
-        amount: i64,
+        amount: &Money,
         recipient: &str,
     ) -> Result<bool, SecurityError> {
-        // Get user's transaction history
-        let avg_amount: i64 = sqlx::query_scalar(
+        // Get user's transaction history in the same currency
+        let avg_amount: Option<Decimal> = sqlx::query_scalar(
             r#"
-            SELECT AVG(amount)::BIGINT
-            FROM transactions
-            WHERE user_id = $1
-            AND created_at > NOW() - INTERVAL '30 days'
-            AND status = 'completed'
+            SELECT AVG(t.amount)
+            FROM transactions t
+            JOIN wallets w ON w.id = t.debit_wallet_id
+            WHERE w.user_id = $1
+            AND t.currency = $2
+            AND t.created_at > NOW() - INTERVAL '30 days'
+            AND t.status = 'completed'
             "#,
         )
         .bind(user_id)
+        .bind(&amount.currency)
         .fetch_one(&self.pool)
         .await?;
 
+        let Some(avg_amount) = avg_amount else {
+            return Ok(true);
+        };
+
         // Check if amount is significantly higher than average
-        if amount > avg_amount * 3 {
+        if amount.amount > avg_amount * Decimal::new(3, 0) {
             self.create_security_alert(
                 "unusual_transaction_amount",
                 AlertSeverity::Medium,
@@ -126,7 +138,7 @@ impl SecurityService {
                 serde_json::json!({
                     "user_id": user_id,
                     "amount": amount,
-                    "avg_amount": avg_amount,
+                    "avg_amount": Money::rounded(avg_amount, &amount.currency).ok(),
                     "recipient": recipient
                 }),
             ).await?;
@@ -136,36 +148,20 @@ impl SecurityService {
         Ok(true)
     }
 
-    // Monitor reserve ratio
+    // Monitor reserve ratio, currency by currency
     pub async fn monitor_reserve_ratio(&self) -> Result<(), SecurityError> {
-        let ratio: f64 = sqlx::query_scalar(
-            r#"
-            WITH reserve_total AS (
-                SELECT COALESCE(SUM(amount), 0) as total
-                FROM reserve_balances
-                WHERE status = 'active'
-            ),
-            wallet_total AS (
-                SELECT COALESCE(SUM(balance), 0) as total
-                FROM wallets
-            )
-            SELECT CASE 
-                WHEN w.total = 0 THEN 1.0
-                ELSE r.total::float / w.total::float
-            END
-            FROM reserve_total r, wallet_total w
-            "#,
-        )
-        .fetch_one(&self.pool)
-        .await?;
+        let ratios = ReserveBalance::ratios(&self.pool).await?;
 
-        if ratio < 1.0 {
+        for ratio in ratios.iter().filter(|ratio| ratio.ratio < 1.0) {
             self.create_security_alert(
                 "low_reserve_ratio",
                 AlertSeverity::Critical,
-                "Reserve ratio below 100%",
+                &format!("{} reserve ratio below 100%", ratio.currency),
                 serde_json::json!({
-                    "current_ratio": ratio,
+                    "currency": ratio.currency,
+                    "reserve": ratio.reserve,
+                    "liabilities": ratio.liabilities,
+                    "current_ratio": ratio.ratio,
                     "threshold": 1.0
                 }),
             ).await?;
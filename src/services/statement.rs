@@ -0,0 +1,333 @@
+use crate::models::statement::{Statement, StatementError};
+use chrono::{DateTime, Duration, Utc};
+use printpdf::{BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfLayerReference};
+use rust_decimal::Decimal;
+use std::borrow::Cow;
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const ROW_HEIGHT: f32 = 5.5;
+const FONT_SIZE: f32 = 8.5;
+
+/// Left edges of the text columns and right edges of the amount columns
+const DATE_X: f32 = MARGIN;
+const DESCRIPTION_X: f32 = 42.0;
+const REFERENCE_X: f32 = 82.0;
+const DEBIT_RIGHT: f32 = 142.0;
+const CREDIT_RIGHT: f32 = 168.0;
+const BALANCE_RIGHT: f32 = PAGE_WIDTH - MARGIN;
+
+/// Renders a statement as CSV: one row per movement, framed by the opening
+/// balance, the totals and the closing balance
+pub fn render_csv(statement: &Statement) -> Result<Vec<u8>, StatementError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let amount = |value: Decimal| statement.money(value).format_amount();
+    let render_error = |e: csv::Error| StatementError::RenderError(e.to_string());
+
+    writer
+        .write_record([
+            "date", "transaction_id", "type", "description", "reference", "debit", "credit",
+            "balance", "currency",
+        ])
+        .map_err(render_error)?;
+
+    writer
+        .write_record([
+            &format_timestamp(statement.period_start),
+            "",
+            "",
+            "Opening balance",
+            "",
+            "",
+            "",
+            &amount(statement.opening_balance),
+            &statement.currency,
+        ])
+        .map_err(render_error)?;
+
+    for line in &statement.lines {
+        let transaction_type = line
+            .transaction_type
+            .as_ref()
+            .map(|transaction_type| format!("{:?}", transaction_type).to_lowercase())
+            .unwrap_or_default();
+
+        writer
+            .write_record([
+                &format_timestamp(line.posted_at),
+                &line.transaction_id.map(|id| id.to_string()).unwrap_or_default(),
+                &transaction_type,
+                &line.description,
+                &spreadsheet_safe(line.reference_id.as_deref().unwrap_or("")).into_owned(),
+                &non_zero(line.debit).map(amount).unwrap_or_default(),
+                &non_zero(line.credit).map(amount).unwrap_or_default(),
+                &amount(line.balance),
+                &statement.currency,
+            ])
+            .map_err(render_error)?;
+    }
+
+    writer
+        .write_record([
+            "",
+            "",
+            "",
+            "Totals",
+            "",
+            &amount(statement.total_debits),
+            &amount(statement.total_credits),
+            "",
+            &statement.currency,
+        ])
+        .map_err(render_error)?;
+
+    writer
+        .write_record([
+            &format_timestamp(statement.period_end),
+            "",
+            "",
+            "Closing balance",
+            "",
+            "",
+            "",
+            &amount(statement.closing_balance),
+            &statement.currency,
+        ])
+        .map_err(render_error)?;
+
+    writer
+        .into_inner()
+        .map_err(|e| StatementError::RenderError(e.to_string()))
+}
+
+/// Renders a statement as an A4 PDF using the built-in Helvetica fonts
+pub fn render_pdf(statement: &Statement) -> Result<Vec<u8>, StatementError> {
+    let render_error = |e: printpdf::Error| StatementError::RenderError(e.to_string());
+    let amount = |value: Decimal| statement.money(value).format_amount();
+
+    let (doc, page, layer) = PdfDocument::new(
+        format!("Statement {}", statement.wallet_id),
+        Mm(PAGE_WIDTH),
+        Mm(PAGE_HEIGHT),
+        "Statement",
+    );
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(render_error)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(render_error)?;
+
+    let mut layer = doc.get_page(page).get_layer(layer);
+    let mut y = PAGE_HEIGHT - MARGIN;
+
+    // Header and summary
+    layer.use_text("Account Statement", 16.0, Mm(MARGIN), Mm(y), &bold);
+    y -= 10.0;
+
+    // The period end is exclusive, so the last day shown is the day before
+    let last_day = statement.period_end - Duration::seconds(1);
+    let details = [
+        ("Account holder", statement.account_holder.clone()),
+        ("Wallet", format!("{} ({})", statement.wallet_name, statement.wallet_id)),
+        ("Currency", statement.currency.clone()),
+        (
+            "Period",
+            format!(
+                "{} to {}",
+                statement.period_start.format("%d %b %Y"),
+                last_day.format("%d %b %Y")
+            ),
+        ),
+        ("Generated", format_timestamp(statement.generated_at)),
+    ];
+    for (label, value) in details {
+        layer.use_text(label, FONT_SIZE, Mm(MARGIN), Mm(y), &bold);
+        layer.use_text(value, FONT_SIZE, Mm(MARGIN + 30.0), Mm(y), &regular);
+        y -= ROW_HEIGHT;
+    }
+    y -= ROW_HEIGHT;
+
+    let summary = [
+        ("Opening balance", statement.opening_balance),
+        ("Money in", statement.total_credits),
+        ("Money out", statement.total_debits),
+        ("Closing balance", statement.closing_balance),
+    ];
+    for (label, value) in summary {
+        layer.use_text(label, FONT_SIZE, Mm(MARGIN), Mm(y), &bold);
+        text_right(&layer, &statement.money(value).to_string(), MARGIN + 70.0, y, &regular);
+        y -= ROW_HEIGHT;
+    }
+    y -= ROW_HEIGHT;
+
+    // Movements, continued over as many pages as needed
+    table_header(&layer, y, &bold);
+    y -= ROW_HEIGHT;
+
+    let mut page_number = 1;
+    for line in &statement.lines {
+        if y < MARGIN + ROW_HEIGHT {
+            page_number += 1;
+            let (page, new_layer) =
+                doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), format!("Page {}", page_number));
+            layer = doc.get_page(page).get_layer(new_layer);
+            y = PAGE_HEIGHT - MARGIN;
+            table_header(&layer, y, &bold);
+            y -= ROW_HEIGHT;
+        }
+
+        let date = line.posted_at.format("%d %b %Y").to_string();
+        layer.use_text(date, FONT_SIZE, Mm(DATE_X), Mm(y), &regular);
+        let description = truncate(&line.description, 24);
+        layer.use_text(description, FONT_SIZE, Mm(DESCRIPTION_X), Mm(y), &regular);
+        layer.use_text(
+            truncate(line.reference_id.as_deref().unwrap_or(""), 22),
+            FONT_SIZE,
+            Mm(REFERENCE_X),
+            Mm(y),
+            &regular,
+        );
+        if let Some(debit) = non_zero(line.debit) {
+            text_right(&layer, &amount(debit), DEBIT_RIGHT, y, &regular);
+        }
+        if let Some(credit) = non_zero(line.credit) {
+            text_right(&layer, &amount(credit), CREDIT_RIGHT, y, &regular);
+        }
+        text_right(&layer, &amount(line.balance), BALANCE_RIGHT, y, &regular);
+        y -= ROW_HEIGHT;
+    }
+
+    if statement.lines.is_empty() {
+        let empty = "No transactions in this period";
+        layer.use_text(empty, FONT_SIZE, Mm(DESCRIPTION_X), Mm(y), &regular);
+    }
+
+    doc.save_to_bytes().map_err(render_error)
+}
+
+fn table_header(layer: &PdfLayerReference, y: f32, font: &IndirectFontRef) {
+    layer.use_text("Date", FONT_SIZE, Mm(DATE_X), Mm(y), font);
+    layer.use_text("Description", FONT_SIZE, Mm(DESCRIPTION_X), Mm(y), font);
+    layer.use_text("Reference", FONT_SIZE, Mm(REFERENCE_X), Mm(y), font);
+    text_right(layer, "Money out", DEBIT_RIGHT, y, font);
+    text_right(layer, "Money in", CREDIT_RIGHT, y, font);
+    text_right(layer, "Balance", BALANCE_RIGHT, y, font);
+}
+
+/// Places text so it ends at `right`. The built-in fonts carry no metrics
+/// here, so the width is estimated from Helvetica's average glyph width.
+fn text_right(layer: &PdfLayerReference, text: &str, right: f32, y: f32, font: &IndirectFontRef) {
+    const PT_TO_MM: f32 = 0.3528;
+    let width = text.chars().count() as f32 * 0.556 * FONT_SIZE * PT_TO_MM;
+    layer.use_text(text, FONT_SIZE, Mm(right - width), Mm(y), font);
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Quotes text a spreadsheet would otherwise run as a formula, by putting a
+/// `'` in front of a leading `=`, `+`, `-`, `@`, tab or carriage return. Use
+/// it on every free-text CSV cell; amounts are written as plain numbers.
+pub fn spreadsheet_safe(value: &str) -> Cow<'_, str> {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        Cow::Owned(format!("'{}", value))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+fn non_zero(amount: Decimal) -> Option<Decimal> {
+    (amount != Decimal::ZERO).then_some(amount)
+}
+
+fn format_timestamp(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{statement::StatementLine, transaction::TransactionType};
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    fn statement(reference_id: &str) -> Statement {
+        let period_start = Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap();
+        let line = |amount: Decimal, balance: Decimal, reference_id: Option<&str>| StatementLine {
+            posted_at: period_start + Duration::days(1),
+            journal_entry_id: Uuid::new_v4(),
+            transaction_id: Some(Uuid::new_v4()),
+            transaction_type: Some(TransactionType::Transfer),
+            description: if amount < Decimal::ZERO { "Transfer out" } else { "Transfer in" }.to_string(),
+            reference_id: reference_id.map(str::to_string),
+            debit: if amount < Decimal::ZERO { -amount } else { Decimal::ZERO },
+            credit: if amount > Decimal::ZERO { amount } else { Decimal::ZERO },
+            balance,
+        };
+
+        Statement {
+            wallet_id: Uuid::new_v4(),
+            account_holder: "Test User".to_string(),
+            wallet_name: "USD wallet".to_string(),
+            currency: "USD".to_string(),
+            period_start,
+            period_end: period_start + Duration::days(31),
+            opening_balance: Decimal::new(10000, 2),
+            lines: vec![
+                line(Decimal::new(2500, 2), Decimal::new(12500, 2), Some(reference_id)),
+                line(Decimal::new(-4000, 2), Decimal::new(8500, 2), None),
+            ],
+            total_debits: Decimal::new(4000, 2),
+            total_credits: Decimal::new(2500, 2),
+            closing_balance: Decimal::new(8500, 2),
+            generated_at: period_start + Duration::days(40),
+        }
+    }
+
+    fn rows(csv: &[u8]) -> Vec<Vec<String>> {
+        csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(csv)
+            .records()
+            .map(|record| record.unwrap().iter().map(str::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_spreadsheet_safe() {
+        for formula in ["=1+1", "+1", "-1", "@SUM(A1)", "\tx", "\rx"] {
+            assert_eq!(spreadsheet_safe(formula), format!("'{}", formula));
+        }
+        assert_eq!(spreadsheet_safe("INV-42"), "INV-42");
+        assert_eq!(spreadsheet_safe(""), "");
+    }
+
+    #[test]
+    fn test_render_csv_frames_movements_with_balances() {
+        let rows = rows(&render_csv(&statement("INV-42")).unwrap());
+
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0][3], "description");
+        assert_eq!(rows[1][3], "Opening balance");
+        assert_eq!(rows[1][0], "2025-03-01 00:00:00 UTC");
+        assert_eq!(rows[1][7], "100.00");
+        assert_eq!(rows[2][4..8], ["INV-42", "", "25.00", "125.00"]);
+        assert_eq!(rows[3][4..8], ["", "40.00", "", "85.00"]);
+        assert_eq!(rows[4][3..7], ["Totals", "", "40.00", "25.00"]);
+        assert_eq!(rows[5][3], "Closing balance");
+        assert_eq!(rows[5][0], "2025-04-01 00:00:00 UTC");
+        assert_eq!(rows[5][7], "85.00");
+    }
+
+    #[test]
+    fn test_render_csv_escapes_formulas_in_references() {
+        let rows = rows(&render_csv(&statement("=HYPERLINK(\"http://evil\")")).unwrap());
+
+        assert_eq!(rows[2][4], "'=HYPERLINK(\"http://evil\")");
+    }
+}
@@ -0,0 +1,62 @@
+use redis::{aio::ConnectionManager, AsyncCommands};
+use uuid::Uuid;
+
+const SESSION_KEY_PREFIX: &str = "auth:denylist:session";
+const USER_KEY_PREFIX: &str = "auth:denylist:user";
+
+/// Access tokens that must be refused before they expire. Entries only need
+/// to outlive the tokens they block, so each expires after one access token
+/// lifetime.
+pub struct TokenDenylist {
+    redis: ConnectionManager,
+    ttl_secs: u64,
+}
+
+impl TokenDenylist {
+    pub fn new(redis: ConnectionManager, access_token_ttl_secs: u64) -> Self {
+        Self {
+            redis,
+            ttl_secs: access_token_ttl_secs,
+        }
+    }
+
+    /// Blocks every access token issued to a session
+    pub async fn deny_session(&self, session_id: Uuid) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.clone();
+        conn.set_ex(session_key(session_id), 1, self.ttl_secs).await
+    }
+
+    /// Blocks every access token issued to the user up to `issued_before`
+    /// (Unix microseconds). Tokens issued afterwards, e.g. by a fresh login
+    /// in the same second, are unaffected.
+    pub async fn deny_user(&self, user_id: Uuid, issued_before: i64) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.clone();
+        conn.set_ex(user_key(user_id), issued_before, self.ttl_secs).await
+    }
+
+    /// Whether an access token has been revoked. `issued_at` is in Unix
+    /// microseconds; tokens from before it was recorded carry 0.
+    pub async fn is_denied(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+        issued_at: i64,
+    ) -> Result<bool, redis::RedisError> {
+        let mut conn = self.redis.clone();
+        let (session, user): (Option<i64>, Option<i64>) = redis::pipe()
+            .get(session_key(session_id))
+            .get(user_key(user_id))
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(session.is_some() || user.is_some_and(|cutoff| issued_at <= cutoff))
+    }
+}
+
+fn session_key(session_id: Uuid) -> String {
+    format!("{}:{}", SESSION_KEY_PREFIX, session_id)
+}
+
+fn user_key(user_id: Uuid) -> String {
+    format!("{}:{}", USER_KEY_PREFIX, user_id)
+}
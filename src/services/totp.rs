@@ -0,0 +1,190 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use image::{ImageFormat, Luma};
+use qrcode::{render::svg, QrCode};
+use rand::RngCore;
+use sha1::Sha1;
+use std::io::Cursor;
+use thiserror::Error;
+
+/// RFC 6238 defaults, which every authenticator app supports
+pub const DIGITS: u32 = 6;
+pub const STEP_SECS: i64 = 30;
+/// Codes from one step either side of now are accepted, to allow for clock
+/// drift between the server and the user's device
+pub const ALLOWED_SKEW: i64 = 1;
+pub const ISSUER: &str = "NEDApay";
+
+const SECRET_BYTES: usize = 20;
+const NONCE_BYTES: usize = 12;
+
+#[derive(Debug, Error)]
+pub enum TotpError {
+    #[error("TOTP_ENCRYPTION_KEY must be 32 bytes, base64 encoded")]
+    InvalidKey,
+    #[error("Could not decrypt TOTP secret")]
+    Decryption,
+    #[error("Could not render QR code: {0}")]
+    QrCode(String),
+}
+
+/// A fresh 160-bit shared secret, as recommended by RFC 4226
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_BYTES];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+/// The secret as users type it into an authenticator app
+pub fn encode_secret(secret: &[u8]) -> String {
+    BASE32_NOPAD.encode(secret)
+}
+
+/// The time step a Unix timestamp falls in
+pub fn time_step(unix_secs: i64) -> i64 {
+    unix_secs.div_euclid(STEP_SECS)
+}
+
+/// The code for one time step (RFC 4226 HOTP with the step as counter)
+pub fn code_at(secret: &[u8], step: i64) -> String {
+    let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&(step as u64).to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // Dynamic truncation
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]])
+        & 0x7fff_ffff;
+
+    format!("{:0width$}", binary % 10u32.pow(DIGITS), width = DIGITS as usize)
+}
+
+/// Finds the step within the allowed skew whose code matches. Callers must
+/// reject steps at or before the last one accepted, so a code cannot be
+/// replayed.
+pub fn matching_step(secret: &[u8], code: &str, unix_secs: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let now = time_step(unix_secs);
+    (now - ALLOWED_SKEW..=now + ALLOWED_SKEW)
+        .find(|step| constant_time_eq(code_at(secret, *step).as_bytes(), code.as_bytes()))
+}
+
+/// Key URI understood by authenticator apps
+/// (https://github.com/google/google-authenticator/wiki/Key-Uri-Format)
+pub fn otpauth_uri(secret: &[u8], account: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+        issuer = ISSUER,
+        account = percent_encode(account),
+        secret = encode_secret(secret),
+        digits = DIGITS,
+        period = STEP_SECS,
+    )
+}
+
+pub fn qr_svg(uri: &str) -> Result<String, TotpError> {
+    let code = QrCode::new(uri).map_err(|e| TotpError::QrCode(e.to_string()))?;
+    Ok(code.render::<svg::Color>().min_dimensions(200, 200).build())
+}
+
+/// The QR code as a PNG data URI, ready for an `<img>` tag
+pub fn qr_png_data_uri(uri: &str) -> Result<String, TotpError> {
+    let code = QrCode::new(uri).map_err(|e| TotpError::QrCode(e.to_string()))?;
+    let image = code.render::<Luma<u8>>().min_dimensions(200, 200).build();
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| TotpError::QrCode(e.to_string()))?;
+
+    Ok(format!("data:image/png;base64,{}", STANDARD.encode(png)))
+}
+
+/// Encrypts a secret for storage with AES-256-GCM under
+/// TOTP_ENCRYPTION_KEY. The random nonce is stored in front of the
+/// ciphertext.
+pub fn encrypt_secret(secret: &[u8]) -> Result<Vec<u8>, TotpError> {
+    let cipher = cipher()?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, secret)
+        .map_err(|_| TotpError::InvalidKey)?;
+
+    let mut stored = nonce.to_vec();
+    stored.extend(ciphertext);
+    Ok(stored)
+}
+
+pub fn decrypt_secret(stored: &[u8]) -> Result<Vec<u8>, TotpError> {
+    if stored.len() <= NONCE_BYTES {
+        return Err(TotpError::Decryption);
+    }
+    let (nonce, ciphertext) = stored.split_at(NONCE_BYTES);
+
+    cipher()?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| TotpError::Decryption)
+}
+
+fn cipher() -> Result<Aes256Gcm, TotpError> {
+    let key = std::env::var("TOTP_ENCRYPTION_KEY")
+        .ok()
+        .and_then(|key| STANDARD.decode(key).ok())
+        .filter(|key| key.len() == 32)
+        .ok_or(TotpError::InvalidKey)?;
+
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'@' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B, SHA-1, truncated to six digits
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_rfc_6238_vectors() {
+        assert_eq!(code_at(RFC_SECRET, time_step(59)), "287082");
+        assert_eq!(code_at(RFC_SECRET, time_step(1_111_111_109)), "081804");
+        assert_eq!(code_at(RFC_SECRET, time_step(1_234_567_890)), "005924");
+        assert_eq!(code_at(RFC_SECRET, time_step(2_000_000_000)), "279037");
+    }
+
+    #[test]
+    fn test_matching_step_allows_one_step_of_skew() {
+        let now = 1_111_111_109;
+        let previous = code_at(RFC_SECRET, time_step(now) - 1);
+        let stale = code_at(RFC_SECRET, time_step(now) - 2);
+
+        assert_eq!(matching_step(RFC_SECRET, "081804", now), Some(time_step(now)));
+        assert_eq!(matching_step(RFC_SECRET, &previous, now), Some(time_step(now) - 1));
+        assert_eq!(matching_step(RFC_SECRET, &stale, now), None);
+        assert_eq!(matching_step(RFC_SECRET, "08180", now), None);
+    }
+}
@@ -0,0 +1,168 @@
+use serde_json::Value;
+use std::future::Future;
+use tracing::{info_span, Instrument};
+use uuid::Uuid;
+
+tokio::task_local! {
+    static CONTEXT: RequestContext;
+}
+
+/// W3C trace context (https://www.w3.org/TR/trace-context/) for the current
+/// hop. `span_id` is ours; `parent_id` is the caller's, when there was one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_id: Option<String>,
+    pub flags: u8,
+}
+
+impl TraceContext {
+    /// Continues the caller's trace from a `traceparent` header, or starts a
+    /// new one when the header is missing or malformed
+    pub fn from_traceparent(header: Option<&str>) -> Self {
+        match header.and_then(parse_traceparent) {
+            Some((trace_id, parent_id, flags)) => Self {
+                trace_id,
+                span_id: new_span_id(),
+                parent_id: Some(parent_id),
+                flags,
+            },
+            None => Self {
+                trace_id: Uuid::new_v4().simple().to_string(),
+                span_id: new_span_id(),
+                parent_id: None,
+                flags: 0x01,
+            },
+        }
+    }
+
+    /// `traceparent` naming this hop as the parent, for responses and any
+    /// downstream calls
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{:02x}", self.trace_id, self.span_id, self.flags)
+    }
+}
+
+/// Identifies the unit of work being run: an HTTP request, or one run of a
+/// background job
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub request_id: String,
+    pub trace: TraceContext,
+}
+
+impl RequestContext {
+    /// A fresh context for work that no request started
+    pub fn generate() -> Self {
+        Self {
+            request_id: Uuid::new_v4().to_string(),
+            trace: TraceContext::from_traceparent(None),
+        }
+    }
+
+    /// Runs `future` with this context current, inside a span carrying the
+    /// request and trace IDs so every log line under it can be correlated
+    pub async fn scope<F: Future>(self, operation: &'static str, future: F) -> F::Output {
+        let span = info_span!(
+            "request",
+            operation,
+            request_id = %self.request_id,
+            trace_id = %self.trace.trace_id,
+            span_id = %self.trace.span_id,
+        );
+
+        CONTEXT.scope(self, future.instrument(span)).await
+    }
+}
+
+pub fn current() -> Option<RequestContext> {
+    CONTEXT.try_with(|context| context.clone()).ok()
+}
+
+pub fn current_request_id() -> Option<String> {
+    CONTEXT.try_with(|context| context.request_id.clone()).ok()
+}
+
+/// Records the current request and trace IDs on transaction metadata, so a
+/// transaction can be traced back to the request that created it. Metadata
+/// that is not a JSON object is left alone.
+pub fn stamp_metadata(metadata: Option<Value>) -> Option<Value> {
+    let Some(context) = current() else {
+        return metadata;
+    };
+
+    let mut metadata = metadata.unwrap_or_else(|| Value::Object(Default::default()));
+    if let Value::Object(fields) = &mut metadata {
+        fields.insert("request_id".to_string(), Value::String(context.request_id));
+        fields.insert("trace_id".to_string(), Value::String(context.trace.trace_id));
+    }
+
+    Some(metadata)
+}
+
+fn new_span_id() -> String {
+    Uuid::new_v4().simple().to_string()[..16].to_string()
+}
+
+fn parse_traceparent(header: &str) -> Option<(String, String, u8)> {
+    let mut parts = header.trim().split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let parent_id = parts.next()?;
+    let flags = parts.next()?;
+
+    // Later versions may append fields; version 00 has exactly four
+    if version.len() != 2 || !is_lower_hex(version) || version == "ff" {
+        return None;
+    }
+    if version == "00" && parts.next().is_some() {
+        return None;
+    }
+
+    let valid = |id: &str, len: usize| {
+        id.len() == len && is_lower_hex(id) && id.bytes().any(|b| b != b'0')
+    };
+    if !valid(trace_id, 32) || !valid(parent_id, 16) || flags.len() != 2 {
+        return None;
+    }
+    let flags = u8::from_str_radix(flags, 16).ok()?;
+
+    Some((trace_id.to_string(), parent_id.to_string(), flags))
+}
+
+fn is_lower_hex(s: &str) -> bool {
+    s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent_continues_valid_trace() {
+        let trace = TraceContext::from_traceparent(Some(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ));
+
+        assert_eq!(trace.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(trace.parent_id.as_deref(), Some("00f067aa0ba902b7"));
+        assert_ne!(trace.span_id, "00f067aa0ba902b7");
+        assert_eq!(trace.flags, 0x01);
+        assert!(trace.traceparent().starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+    }
+
+    #[test]
+    fn test_malformed_traceparent_starts_new_trace() {
+        for header in [
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "garbage",
+        ] {
+            let trace = TraceContext::from_traceparent(Some(header));
+            assert!(trace.parent_id.is_none(), "{}", header);
+            assert_eq!(trace.trace_id.len(), 32);
+        }
+    }
+}